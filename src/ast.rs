@@ -1,6 +1,9 @@
-use crate::expr::{ExprVisitor, UnaryData, BinaryData, GroupingData, Expr, LogicalData, AssignData, VariableData, CallData};
+use crate::expr::{LiteralData, ExprVisitor, UnaryData, BinaryData, GroupingData, Expr, LogicalData, ConditionalData, AssignData, UpdateData, VariableData, CallData, GetData, InterpolationData};
 use crate::stmt::{StmtVisitor, Stmt};
 use crate::literal::Literal;
+use crate::token::Token;
+
+use serde_json::{json, Value};
 
 /// Returns a string representation of the expression in paranthesize.
 macro_rules! parenthesize {
@@ -30,14 +33,18 @@ impl ASTPrinter {
 }
 
 impl ExprVisitor<String> for ASTPrinter {
-    fn visit_literal_expr(&mut self, literal: &Literal) -> String {
-        literal.to_string() // Uses fmt::Display impl for Literal
+    fn visit_literal_expr(&mut self, literal: &LiteralData) -> String {
+        literal.value.to_string() // Uses fmt::Display impl for Literal
     }
 
     fn visit_logical_expr(&mut self, logical: &LogicalData) -> String {
         parenthesize!(self, &logical.operator.lexeme, &logical.left, &logical.right)
     }
 
+    fn visit_conditional_expr(&mut self, conditional: &ConditionalData) -> String {
+        parenthesize!(self, "?:", &conditional.condition, &conditional.then_branch, &conditional.else_branch)
+    }
+
     fn visit_unary_expr(&mut self, unary: &UnaryData) -> String {
         parenthesize!(self, &unary.operator.lexeme, &unary.expr)
     }
@@ -58,9 +65,20 @@ impl ExprVisitor<String> for ASTPrinter {
         parenthesize!(self, format!("= {}", &assign.name.lexeme).as_str(), assign.value)
     }
 
+    fn visit_update_expr(&mut self, update: &UpdateData) -> String {
+        match (&update.value, update.prefix) {
+            (Some(value), _) => parenthesize!(self, format!("{} {}", update.operator.lexeme, update.name.lexeme).as_str(), value),
+            (None, true) => format!("({} {})", update.operator.lexeme, update.name.lexeme),
+            (None, false) => format!("({} {})", update.name.lexeme, update.operator.lexeme),
+        }
+    }
+
     fn visit_call_expr(&mut self, call: &CallData) -> String {
         let mut string = String::new();
         string += &call.callee.accept(self);
+        if call.optional.is_some() {
+            string += "?.";
+        }
         string += "(";
         for arg in &call.arguments {
             string += &arg.accept(self);
@@ -70,6 +88,23 @@ impl ExprVisitor<String> for ASTPrinter {
         string += ")";
         string
     }
+
+    fn visit_get_expr(&mut self, get: &GetData) -> String {
+        format!("{}.{}", get.object.accept(self), get.name.lexeme)
+    }
+
+    fn visit_interpolation_expr(&mut self, interpolation: &InterpolationData) -> String {
+        let mut string = String::from("(interpolation");
+        for (index, part) in interpolation.strings.iter().enumerate() {
+            string += &format!(" {part:?}");
+            if let Some(expr) = interpolation.expressions.get(index) {
+                string += " ";
+                string += &expr.accept(self);
+            }
+        }
+        string += ")";
+        string
+    }
 }
 
 impl StmtVisitor<String> for ASTPrinter {
@@ -131,7 +166,7 @@ impl StmtVisitor<String> for ASTPrinter {
 
     fn visit_return_stmt(&mut self, stmt: &Stmt) -> String {
         if let Stmt::Return(data) = stmt {
-            parenthesize!(self, "return", data.value.clone().unwrap_or(Expr::Literal(Literal::Null)))
+            parenthesize!(self, "return", data.value.clone().unwrap_or(Expr::Literal(Literal::Null.into())))
         } else {
             unreachable!()
         }
@@ -177,4 +212,493 @@ impl StmtVisitor<String> for ASTPrinter {
             unreachable!()
         }
     }
+
+    fn visit_import_stmt(&mut self, stmt: &Stmt) -> String {
+        if let Stmt::Import(data) = stmt {
+            format!("(import {} as {})", data.path.lexeme, data.name.lexeme)
+        } else {
+            unreachable!()
+        }
+    }
+}
+
+/// Returns the position of the token as `@line:column`.
+fn position(token: &Token) -> String {
+    format!("@{}:{}", token.line, token.column)
+}
+
+/// Prints statements as S-expressions, tagging every node that has a token with its position.
+pub struct SExprPrinter;
+
+impl SExprPrinter {
+    /// Prints every statement on its own line.
+    pub fn print(&mut self, statements: &[Stmt]) -> String {
+        statements.iter().map(|stmt| stmt.accept(self) + "\n").collect()
+    }
+
+    fn list(&mut self, head: String, expressions: &[&Expr], statements: &[&Stmt]) -> String {
+        let mut parts = vec![head];
+        parts.extend(expressions.iter().map(|expr| expr.accept(self)));
+        parts.extend(statements.iter().map(|stmt| stmt.accept(self)));
+
+        format!("({})", parts.join(" "))
+    }
+}
+
+impl ExprVisitor<String> for SExprPrinter {
+    fn visit_literal_expr(&mut self, literal: &LiteralData) -> String {
+        let value = match &literal.value {
+            Literal::String(string) => format!("{string:?}"),
+            literal => literal.to_string(),
+        };
+
+        // Literals made up by the parser, like the condition of `for (;;)`, have no position.
+        match &literal.token {
+            Some(token) => format!("(literal{} {value})", position(token)),
+            None => format!("(literal {value})"),
+        }
+    }
+
+    fn visit_logical_expr(&mut self, logical: &LogicalData) -> String {
+        let head = format!("logical{} {}", position(&logical.operator), logical.operator.lexeme);
+        self.list(head, &[&logical.left, &logical.right], &[])
+    }
+
+    fn visit_conditional_expr(&mut self, conditional: &ConditionalData) -> String {
+        let head = format!("conditional{}", position(&conditional.question));
+        self.list(head, &[&conditional.condition, &conditional.then_branch, &conditional.else_branch], &[])
+    }
+
+    fn visit_unary_expr(&mut self, unary: &UnaryData) -> String {
+        let head = format!("unary{} {}", position(&unary.operator), unary.operator.lexeme);
+        self.list(head, &[&unary.expr], &[])
+    }
+
+    fn visit_binary_expr(&mut self, binary: &BinaryData) -> String {
+        let head = format!("binary{} {}", position(&binary.operator), binary.operator.lexeme);
+        self.list(head, &[&binary.left, &binary.right], &[])
+    }
+
+    fn visit_grouping_expr(&mut self, grouping: &GroupingData) -> String {
+        self.list(format!("group{}", position(&grouping.paren)), &[&grouping.expr], &[])
+    }
+
+    fn visit_variable_expr(&mut self, variable: &VariableData) -> String {
+        format!("(variable{} {})", position(&variable.name), variable.name.lexeme)
+    }
+
+    fn visit_assign_expr(&mut self, assign: &AssignData) -> String {
+        let head = format!("assign{} {}", position(&assign.name), assign.name.lexeme);
+        self.list(head, &[&assign.value], &[])
+    }
+
+    fn visit_update_expr(&mut self, update: &UpdateData) -> String {
+        let (operator, name) = (&update.operator.lexeme, &update.name.lexeme);
+        let head = match update.prefix {
+            true => format!("update{} {operator} {name}", position(&update.operator)),
+            false => format!("update{} {name} {operator}", position(&update.operator)),
+        };
+
+        let value: Vec<&Expr> = update.value.iter().map(|value| value.as_ref()).collect();
+        self.list(head, &value, &[])
+    }
+
+    fn visit_call_expr(&mut self, call: &CallData) -> String {
+        let mut expressions = vec![call.callee.as_ref()];
+        expressions.extend(&call.arguments);
+
+        let head = match call.optional {
+            Some(_) => format!("call{} ?.", position(&call.paren)),
+            None => format!("call{}", position(&call.paren)),
+        };
+
+        self.list(head, &expressions, &[])
+    }
+
+    fn visit_get_expr(&mut self, get: &GetData) -> String {
+        let head = format!("get{} {}", position(&get.name), get.name.lexeme);
+        self.list(head, &[&get.object], &[])
+    }
+
+    fn visit_interpolation_expr(&mut self, interpolation: &InterpolationData) -> String {
+        let mut parts = vec![format!("interpolation{}", position(&interpolation.token))];
+
+        for (index, string) in interpolation.strings.iter().enumerate() {
+            parts.push(format!("{string:?}"));
+            parts.extend(interpolation.expressions.get(index).map(|expr| expr.accept(self)));
+        }
+
+        format!("({})", parts.join(" "))
+    }
+}
+
+impl StmtVisitor<String> for SExprPrinter {
+    fn visit_expression_stmt(&mut self, stmt: &Stmt) -> String {
+        let Stmt::Expression(data) = stmt else { unreachable!() };
+        let head = match data.expr.token() {
+            Some(token) => format!("expression{}", position(token)),
+            None => "expression".to_string(),
+        };
+
+        self.list(head, &[&data.expr], &[])
+    }
+
+    fn visit_function_stmt(&mut self, stmt: &Stmt) -> String {
+        let Stmt::Function(data) = stmt else { unreachable!() };
+        let params: Vec<String> = data.params.iter().map(|param| param.lexeme.clone()).collect();
+        let head = format!("fun{} {} ({})", position(&data.name), data.name.lexeme, params.join(" "));
+
+        self.list(head, &[], &data.body.iter().collect::<Vec<&Stmt>>())
+    }
+
+    fn visit_if_stmt(&mut self, stmt: &Stmt) -> String {
+        let Stmt::If(data) = stmt else { unreachable!() };
+        let mut branches = vec![data.then_branch.as_ref()];
+        branches.extend(data.else_branch.as_deref());
+
+        self.list(format!("if{}", position(&data.keyword)), &[&data.condition], &branches)
+    }
+
+    fn visit_print_stmt(&mut self, stmt: &Stmt) -> String {
+        let Stmt::Print(data) = stmt else { unreachable!() };
+        self.list(format!("print{}", position(&data.keyword)), &[&data.expr], &[])
+    }
+
+    fn visit_return_stmt(&mut self, stmt: &Stmt) -> String {
+        let Stmt::Return(data) = stmt else { unreachable!() };
+        let value: Vec<&Expr> = data.value.iter().collect();
+
+        self.list(format!("return{}", position(&data.keyword)), &value, &[])
+    }
+
+    fn visit_var_stmt(&mut self, stmt: &Stmt) -> String {
+        let Stmt::Var(data) = stmt else { unreachable!() };
+        let initializer: Vec<&Expr> = data.initializer.iter().collect();
+
+        self.list(format!("var{} {}", position(&data.name), data.name.lexeme), &initializer, &[])
+    }
+
+    fn visit_while_stmt(&mut self, stmt: &Stmt) -> String {
+        let Stmt::While(data) = stmt else { unreachable!() };
+        self.list(format!("while{}", position(&data.keyword)), &[&data.condition], &[&data.body])
+    }
+
+    fn visit_block_stmt(&mut self, stmt: &Stmt) -> String {
+        let Stmt::Block(data) = stmt else { unreachable!() };
+        self.list(format!("block{}", position(&data.start)), &[], &data.statements.iter().collect::<Vec<&Stmt>>())
+    }
+
+    fn visit_import_stmt(&mut self, stmt: &Stmt) -> String {
+        let Stmt::Import(data) = stmt else { unreachable!() };
+        format!("(import{} {} {})", position(&data.keyword), data.path.lexeme, data.name.lexeme)
+    }
+}
+
+/// Returns the token as a JSON object.
+pub fn token_json(token: &Token) -> Value {
+    json!({
+        "type": format!("{:?}", token.r#type),
+        "lexeme": token.lexeme,
+        "literal": token.literal.as_ref().map(literal_json),
+        "line": token.line,
+        "column": token.column,
+    })
+}
+
+fn literal_json(literal: &Literal) -> Value {
+    match literal {
+        Literal::String(string) => json!(string),
+        // Widen through the shortest representation, so 0.1 stays 0.1.
+        Literal::Number(number) => json!(number),
+        Literal::Bool(bool) => json!(bool),
+        Literal::Null => Value::Null,
+    }
+}
+
+/// Returns the node as a JSON object, positioned at its token.
+fn node(kind: &str, token: Option<&Token>, fields: Value) -> Value {
+    let mut node = json!({ "node": kind });
+
+    if let Some(token) = token {
+        node["line"] = json!(token.line);
+        node["column"] = json!(token.column);
+    }
+
+    if let (Value::Object(node), Value::Object(fields)) = (&mut node, fields) {
+        node.extend(fields);
+    }
+
+    node
+}
+
+/// Adds the doc comment of a declaration to its node, when it has one.
+fn documented(mut node: Value, doc: &Option<String>) -> Value {
+    if let Some(doc) = doc {
+        node["doc"] = json!(doc);
+    }
+
+    node
+}
+
+/// Prints statements as JSON, with the position of every node that has a token.
+pub struct JSONPrinter;
+
+impl JSONPrinter {
+    /// Prints the statements as a JSON array.
+    pub fn print(&mut self, statements: &[Stmt]) -> Value {
+        Value::Array(statements.iter().map(|stmt| stmt.accept(self)).collect())
+    }
+}
+
+impl ExprVisitor<Value> for JSONPrinter {
+    fn visit_literal_expr(&mut self, literal: &LiteralData) -> Value {
+        node("Literal", literal.token.as_ref(), json!({ "value": literal_json(&literal.value) }))
+    }
+
+    fn visit_logical_expr(&mut self, logical: &LogicalData) -> Value {
+        node("Logical", Some(&logical.operator), json!({
+            "operator": logical.operator.lexeme,
+            "left": logical.left.accept(self),
+            "right": logical.right.accept(self),
+        }))
+    }
+
+    fn visit_conditional_expr(&mut self, conditional: &ConditionalData) -> Value {
+        node("Conditional", Some(&conditional.question), json!({
+            "condition": conditional.condition.accept(self),
+            "then": conditional.then_branch.accept(self),
+            "else": conditional.else_branch.accept(self),
+        }))
+    }
+
+    fn visit_unary_expr(&mut self, unary: &UnaryData) -> Value {
+        node("Unary", Some(&unary.operator), json!({
+            "operator": unary.operator.lexeme,
+            "expr": unary.expr.accept(self),
+        }))
+    }
+
+    fn visit_binary_expr(&mut self, binary: &BinaryData) -> Value {
+        node("Binary", Some(&binary.operator), json!({
+            "operator": binary.operator.lexeme,
+            "left": binary.left.accept(self),
+            "right": binary.right.accept(self),
+        }))
+    }
+
+    fn visit_grouping_expr(&mut self, grouping: &GroupingData) -> Value {
+        node("Grouping", Some(&grouping.paren), json!({ "expr": grouping.expr.accept(self) }))
+    }
+
+    fn visit_variable_expr(&mut self, variable: &VariableData) -> Value {
+        node("Variable", Some(&variable.name), json!({ "name": variable.name.lexeme }))
+    }
+
+    fn visit_assign_expr(&mut self, assign: &AssignData) -> Value {
+        node("Assign", Some(&assign.name), json!({
+            "name": assign.name.lexeme,
+            "value": assign.value.accept(self),
+        }))
+    }
+
+    fn visit_update_expr(&mut self, update: &UpdateData) -> Value {
+        node("Update", Some(&update.operator), json!({
+            "name": update.name.lexeme,
+            "operator": update.operator.lexeme,
+            "value": update.value.as_ref().map(|value| value.accept(self)),
+            "prefix": update.prefix,
+        }))
+    }
+
+    fn visit_call_expr(&mut self, call: &CallData) -> Value {
+        let arguments: Vec<Value> = call.arguments.iter().map(|argument| argument.accept(self)).collect();
+
+        let mut node = node("Call", Some(&call.paren), json!({
+            "callee": call.callee.accept(self),
+            "arguments": arguments,
+        }));
+
+        if call.optional.is_some() {
+            node["optional"] = json!(true);
+        }
+
+        node
+    }
+
+    fn visit_get_expr(&mut self, get: &GetData) -> Value {
+        node("Get", Some(&get.name), json!({
+            "object": get.object.accept(self),
+            "name": get.name.lexeme,
+        }))
+    }
+
+    fn visit_interpolation_expr(&mut self, interpolation: &InterpolationData) -> Value {
+        let expressions: Vec<Value> = interpolation.expressions.iter().map(|expr| expr.accept(self)).collect();
+
+        node("Interpolation", Some(&interpolation.token), json!({
+            "strings": interpolation.strings,
+            "expressions": expressions,
+        }))
+    }
+}
+
+impl StmtVisitor<Value> for JSONPrinter {
+    fn visit_expression_stmt(&mut self, stmt: &Stmt) -> Value {
+        let Stmt::Expression(data) = stmt else { unreachable!() };
+        node("Expression", data.expr.token(), json!({ "expr": data.expr.accept(self) }))
+    }
+
+    fn visit_function_stmt(&mut self, stmt: &Stmt) -> Value {
+        let Stmt::Function(data) = stmt else { unreachable!() };
+        let params: Vec<&String> = data.params.iter().map(|param| &param.lexeme).collect();
+
+        documented(node("Function", Some(&data.name), json!({
+            "name": data.name.lexeme,
+            "params": params,
+            "body": self.print(&data.body),
+        })), &data.doc)
+    }
+
+    fn visit_if_stmt(&mut self, stmt: &Stmt) -> Value {
+        let Stmt::If(data) = stmt else { unreachable!() };
+
+        node("If", Some(&data.keyword), json!({
+            "condition": data.condition.accept(self),
+            "then": data.then_branch.accept(self),
+            "else": data.else_branch.as_ref().map(|stmt| stmt.accept(self)),
+        }))
+    }
+
+    fn visit_print_stmt(&mut self, stmt: &Stmt) -> Value {
+        let Stmt::Print(data) = stmt else { unreachable!() };
+        node("Print", Some(&data.keyword), json!({ "expr": data.expr.accept(self) }))
+    }
+
+    fn visit_return_stmt(&mut self, stmt: &Stmt) -> Value {
+        let Stmt::Return(data) = stmt else { unreachable!() };
+        node("Return", Some(&data.keyword), json!({ "value": data.value.as_ref().map(|expr| expr.accept(self)) }))
+    }
+
+    fn visit_var_stmt(&mut self, stmt: &Stmt) -> Value {
+        let Stmt::Var(data) = stmt else { unreachable!() };
+
+        documented(node("Var", Some(&data.name), json!({
+            "name": data.name.lexeme,
+            "initializer": data.initializer.as_ref().map(|expr| expr.accept(self)),
+        })), &data.doc)
+    }
+
+    fn visit_while_stmt(&mut self, stmt: &Stmt) -> Value {
+        let Stmt::While(data) = stmt else { unreachable!() };
+
+        node("While", Some(&data.keyword), json!({
+            "condition": data.condition.accept(self),
+            "body": data.body.accept(self),
+        }))
+    }
+
+    fn visit_block_stmt(&mut self, stmt: &Stmt) -> Value {
+        let Stmt::Block(data) = stmt else { unreachable!() };
+        node("Block", Some(&data.start), json!({ "statements": self.print(&data.statements) }))
+    }
+
+    fn visit_import_stmt(&mut self, stmt: &Stmt) -> Value {
+        let Stmt::Import(data) = stmt else { unreachable!() };
+
+        node("Import", Some(&data.keyword), json!({
+            "path": data.path.literal.as_ref().map(literal_json),
+            "name": data.name.lexeme,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+
+    fn parse(source: &str) -> Vec<Stmt> {
+        Parser::new(Scanner::new(source.to_string()).scan_tokens()).parse()
+    }
+
+    #[test]
+    fn sexpr() {
+        let statements = parse("\
+fun f(a, b) { return a or -b; }
+var x = f(1, \"two\");
+if (x) print (x); else { x = null; }
+while (false) x;
+");
+
+        assert_eq!(SExprPrinter.print(&statements), "\
+(fun@1:4 f (a b) (return@1:14 (logical@1:23 or (variable@1:21 a) (unary@1:26 - (variable@1:27 b)))))
+(var@2:4 x (call@2:18 (variable@2:8 f) (literal@2:10 1) (literal@2:13 \"two\")))
+(if@3:0 (variable@3:4 x) (print@3:7 (group@3:13 (variable@3:14 x))) (block@3:23 (expression@3:25 (assign@3:25 x (literal@3:29 null)))))
+(while@4:0 (literal@4:7 false) (expression@4:14 (variable@4:14 x)))
+");
+    }
+
+    #[test]
+    fn json() {
+        let statements = parse("var x = 1 + 0.1;\n");
+
+        assert_eq!(JSONPrinter.print(&statements), json!([{
+            "node": "Var",
+            "line": 1,
+            "column": 4,
+            "name": "x",
+            "initializer": {
+                "node": "Binary",
+                "line": 1,
+                "column": 10,
+                "operator": "+",
+                "left": { "node": "Literal", "line": 1, "column": 8, "value": 1.0 },
+                "right": { "node": "Literal", "line": 1, "column": 12, "value": 0.1 },
+            },
+        }]));
+
+        let statements = parse("{\n  (a);\n}\n");
+
+        assert_eq!(JSONPrinter.print(&statements), json!([{
+            "node": "Block",
+            "line": 1,
+            "column": 0,
+            "statements": [{
+                "node": "Expression",
+                "line": 2,
+                "column": 2,
+                "expr": {
+                    "node": "Grouping",
+                    "line": 2,
+                    "column": 2,
+                    "expr": { "node": "Variable", "line": 2, "column": 3, "name": "a" },
+                },
+            }],
+        }]));
+
+        let statements = parse("/// The answer.\nvar x;\n");
+
+        assert_eq!(JSONPrinter.print(&statements), json!([{
+            "node": "Var",
+            "line": 2,
+            "column": 4,
+            "name": "x",
+            "initializer": null,
+            "doc": "The answer.",
+        }]));
+    }
+
+    #[test]
+    fn token() {
+        let tokens = Scanner::new("\n  \"a\"".to_string()).scan_tokens();
+
+        assert_eq!(token_json(&tokens[0]), json!({
+            "type": "String",
+            "lexeme": "\"a\"",
+            "literal": "a",
+            "line": 2,
+            "column": 2,
+        }));
+    }
 }
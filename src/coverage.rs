@@ -0,0 +1,332 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::Path;
+use std::rc::Rc;
+
+use crate::error::RuntimeError;
+use crate::expr::Expr;
+use crate::interpreter::{Hook, Interpreter};
+use crate::stmt::Stmt;
+use crate::token::Token;
+
+/// Counts of the two ways a condition can go.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Branch {
+    /// The body or the right operand ran.
+    pub taken: u64,
+    pub not_taken: u64,
+}
+
+/// Line and branch counts of a script, recorded by a `Collector`.
+#[derive(Debug, Default)]
+pub struct Coverage {
+    /// Number of statements executed on every line holding one.
+    pub lines: BTreeMap<usize, u64>,
+    /// Branch counts of every `if`, `while`, `and` and `or`, by line and column of its keyword.
+    pub branches: BTreeMap<(usize, u32), Branch>,
+    /// Coverage of the modules imported by the script, by name.
+    pub modules: BTreeMap<String, Coverage>,
+}
+
+impl Coverage {
+    /// Creates an empty coverage listing every line and branch of the statements.
+    pub fn new(statements: &[Stmt]) -> Self {
+        let mut coverage = Coverage::default();
+        statements.iter().for_each(|stmt| coverage.register(stmt));
+        coverage
+    }
+
+    fn register(&mut self, stmt: &Stmt) {
+        fn walk(expr: &Expr, coverage: &mut Coverage) {
+            match expr {
+                Expr::Literal(_) | Expr::Variable(_) => (),
+                Expr::Logical(data) => {
+                    coverage.branch(&data.operator);
+                    walk(&data.left, coverage);
+                    walk(&data.right, coverage);
+                },
+                Expr::Conditional(data) => {
+                    coverage.branch(&data.question);
+                    walk(&data.condition, coverage);
+                    walk(&data.then_branch, coverage);
+                    walk(&data.else_branch, coverage);
+                },
+                Expr::Unary(data) => walk(&data.expr, coverage),
+                Expr::Binary(data) => { walk(&data.left, coverage); walk(&data.right, coverage) },
+                Expr::Grouping(data) => walk(&data.expr, coverage),
+                Expr::Assign(data) => walk(&data.value, coverage),
+                Expr::Update(data) => data.value.iter().for_each(|value| walk(value, coverage)),
+                Expr::Call(data) => {
+                    if let Some(question) = &data.optional {
+                        coverage.branch(question);
+                    }
+                    walk(&data.callee, coverage);
+                    data.arguments.iter().for_each(|argument| walk(argument, coverage));
+                },
+                Expr::Get(data) => walk(&data.object, coverage),
+                Expr::Interpolation(data) => data.expressions.iter().for_each(|expr| walk(expr, coverage)),
+            }
+        }
+
+        if let (false, Some(line)) = (matches!(stmt, Stmt::Block(_)), stmt.line()) {
+            self.lines.entry(line).or_default();
+        }
+
+        match stmt {
+            Stmt::Expression(data) => walk(&data.expr, self),
+            Stmt::Function(data) => data.body.iter().for_each(|stmt| self.register(stmt)),
+            Stmt::If(data) => {
+                self.branch(&data.keyword);
+                walk(&data.condition, self);
+                self.register(&data.then_branch);
+                if let Some(else_branch) = &data.else_branch {
+                    self.register(else_branch);
+                }
+            },
+            Stmt::Print(data) => walk(&data.expr, self),
+            Stmt::Return(data) => if let Some(value) = &data.value { walk(value, self) },
+            Stmt::Var(data) => if let Some(value) = &data.initializer { walk(value, self) },
+            Stmt::While(data) => {
+                self.branch(&data.keyword);
+                walk(&data.condition, self);
+                self.register(&data.body);
+            },
+            Stmt::Block(data) => data.statements.iter().for_each(|stmt| self.register(stmt)),
+            Stmt::Import(_) => (),
+        }
+    }
+
+    fn branch(&mut self, keyword: &Token) -> &mut Branch {
+        self.branches.entry((keyword.line, keyword.column)).or_default()
+    }
+
+    /// Returns the coverage of the module, or of the script itself for `None`.
+    fn file(&mut self, module: Option<&str>) -> &mut Coverage {
+        match module {
+            Some(module) => self.modules.entry(module.to_string()).or_default(),
+            None => self,
+        }
+    }
+
+    /// Returns the coverage as an lcov tracefile, with a record for the script and one for every module.
+    ///
+    /// Modules are named by their path from the directory of the script.
+    pub fn lcov(&self, path: &str) -> String {
+        let directory = Path::new(path).parent().unwrap_or(Path::new(""));
+        let mut lcov = self.record(path);
+
+        for (module, coverage) in &self.modules {
+            lcov += &coverage.record(&directory.join(module).to_string_lossy());
+        }
+
+        lcov
+    }
+
+    /// Returns the lcov record of the lines and branches for the source file.
+    fn record(&self, path: &str) -> String {
+        let mut lcov = format!("TN:\nSF:{path}\n");
+
+        // Branches are numbered by their position on the line.
+        let mut block = 0;
+        let mut previous = 0;
+
+        for (&(line, _), branch) in &self.branches {
+            block = if line == previous { block + 1 } else { 0 };
+            previous = line;
+
+            for (number, count) in [branch.taken, branch.not_taken].into_iter().enumerate() {
+                if branch.taken + branch.not_taken == 0 {
+                    writeln!(lcov, "BRDA:{line},{block},{number},-").unwrap();
+                } else {
+                    writeln!(lcov, "BRDA:{line},{block},{number},{count}").unwrap();
+                }
+            }
+        }
+
+        let branches = self.branches.values().flat_map(|branch| [branch.taken, branch.not_taken]);
+
+        writeln!(lcov, "BRF:{}", self.branches.len() * 2).unwrap();
+        writeln!(lcov, "BRH:{}", branches.filter(|count| *count > 0).count()).unwrap();
+
+        for (line, count) in &self.lines {
+            writeln!(lcov, "DA:{line},{count}").unwrap();
+        }
+
+        writeln!(lcov, "LF:{}", self.lines.len()).unwrap();
+        writeln!(lcov, "LH:{}", self.lines.values().filter(|count| **count > 0).count()).unwrap();
+        lcov.push_str("end_of_record\n");
+
+        lcov
+    }
+}
+
+/// Records executed statements and branches into a shared `Coverage`.
+pub struct Collector {
+    coverage: Rc<RefCell<Coverage>>,
+}
+
+impl Collector {
+    pub fn new(coverage: Rc<RefCell<Coverage>>) -> Self {
+        Collector { coverage }
+    }
+}
+
+impl Hook for Collector {
+    fn statement(&mut self, interpreter: &mut Interpreter, stmt: &Stmt) -> Result<(), RuntimeError> {
+        if let (false, Some(line)) = (matches!(stmt, Stmt::Block(_)), stmt.line()) {
+            *self.coverage.borrow_mut().file(interpreter.module()).lines.entry(line).or_default() += 1;
+        }
+
+        Ok(())
+    }
+
+    fn branch(&mut self, interpreter: &Interpreter, keyword: &Token, taken: bool) {
+        let mut coverage = self.coverage.borrow_mut();
+        let branch = coverage.file(interpreter.module()).branch(keyword);
+
+        if taken {
+            branch.taken += 1;
+        } else {
+            branch.not_taken += 1;
+        }
+    }
+
+    fn import(&mut self, _interpreter: &Interpreter, module: &str, _source: &str, statements: &[Stmt]) {
+        self.coverage.borrow_mut().modules.insert(module.to_string(), Coverage::new(statements));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+    use crate::testing::files;
+
+    fn cover(source: &str) -> Coverage {
+        let tokens = Scanner::new(source.to_string()).scan_tokens();
+        let statements = Parser::new(tokens).parse();
+
+        let coverage = Rc::new(RefCell::new(Coverage::new(&statements)));
+        let mut interpreter = Interpreter::new();
+        interpreter.set_output(Box::new(std::io::sink()));
+        interpreter.add_hook(Box::new(Collector::new(Rc::clone(&coverage))));
+        let _ = interpreter.interpret(&statements);
+
+        coverage.take()
+    }
+
+    #[test]
+    fn lines() {
+        let coverage = cover("\
+fun f(n) {
+  if (n > 1) {
+    print \"big\";
+  } else {
+    print \"small\";
+  }
+}
+f(0);
+f(1);
+");
+
+        let lines: Vec<(usize, u64)> = coverage.lines.into_iter().collect();
+        assert_eq!(lines, vec![(1, 1), (2, 2), (3, 0), (5, 2), (8, 1), (9, 1)]);
+    }
+
+    #[test]
+    fn branches() {
+        let coverage = cover("\
+var i = 0;
+while (i < 3) i = i + 1;
+if (i == 3 or undefined) print i;
+if (false and undefined) print i;
+");
+
+        let branches: Vec<(usize, u64, u64)> = coverage.branches
+            .iter()
+            .map(|(&(line, _), branch)| (line, branch.taken, branch.not_taken))
+            .collect();
+
+        assert_eq!(branches, vec![(2, 3, 1), (3, 1, 0), (3, 0, 1), (4, 0, 1), (4, 0, 1)]);
+    }
+
+    #[test]
+    fn expression_branches() {
+        let coverage = cover("var a = 1;\nprint a > 0 ? a : -a;\nprint a < 0 ? a : -a;\nprint a ?? 2;\nprint clock?.();\n");
+
+        let branches: Vec<(usize, u64, u64)> = coverage.branches
+            .iter()
+            .map(|(&(line, _), branch)| (line, branch.taken, branch.not_taken))
+            .collect();
+
+        assert_eq!(branches, vec![(2, 1, 0), (3, 0, 1), (4, 0, 1), (5, 1, 0)]);
+    }
+
+    #[test]
+    fn lcov() {
+        let coverage = cover("var a = true;\nif (a) print a;\nfun f() { if (a) return; }\n");
+
+        assert_eq!(coverage.lcov("test.lox"), "\
+TN:
+SF:test.lox
+BRDA:2,0,0,1
+BRDA:2,0,1,0
+BRDA:3,0,0,-
+BRDA:3,0,1,-
+BRF:4
+BRH:1
+DA:1,1
+DA:2,2
+DA:3,1
+LF:3
+LH:3
+end_of_record
+");
+    }
+
+    #[test]
+    fn modules() {
+        let directory = files("coverage", &[
+            ("main.lox", "import \"lib.lox\" as lib;\nprint lib.f(1);\n"),
+            ("lib.lox", "fun f(n) {\n  if (n > 0) return 1;\n  return 0;\n}\n"),
+        ]);
+
+        let path = directory.join("main.lox");
+        let tokens = Scanner::new(std::fs::read_to_string(&path).unwrap()).scan_tokens();
+        let statements = Parser::new(tokens).parse();
+
+        let coverage = Rc::new(RefCell::new(Coverage::new(&statements)));
+        let mut interpreter = Interpreter::new();
+        interpreter.set_script(&path);
+        interpreter.set_output(Box::new(std::io::sink()));
+        interpreter.add_hook(Box::new(Collector::new(Rc::clone(&coverage))));
+        interpreter.interpret(&statements).unwrap();
+
+        // The lines of the module are kept out of the record of the script.
+        assert_eq!(coverage.borrow().lcov("main.lox"), "\
+TN:
+SF:main.lox
+BRF:0
+BRH:0
+DA:1,1
+DA:2,1
+LF:2
+LH:2
+end_of_record
+TN:
+SF:lib.lox
+BRDA:2,0,0,1
+BRDA:2,0,1,0
+BRF:2
+BRH:1
+DA:1,1
+DA:2,2
+DA:3,0
+LF:3
+LH:2
+end_of_record
+");
+    }
+}
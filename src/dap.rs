@@ -0,0 +1,644 @@
+use std::cell::RefCell;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::rc::Rc;
+
+use serde_json::{json, Value};
+
+use crate::debugger::{self, Breakpoint, Command, Debugger, Frontend, Location, Reason, Session};
+use crate::error::RuntimeErrorKind;
+use crate::lsp;
+use crate::object::Object;
+use crate::parser::Parser;
+use crate::scanner::Scanner;
+use crate::stmt::Stmt;
+
+/// The interpreter runs on a single thread.
+const THREAD: u64 = 1;
+
+/// Connection to the client, shared by the server, its frontend and the output of the program.
+struct Connection<R: BufRead, W: Write> {
+    reader: R,
+    writer: W,
+    seq: u64,
+    disconnected: bool,
+}
+
+type Shared<R, W> = Rc<RefCell<Connection<R, W>>>;
+
+impl<R: BufRead, W: Write> Connection<R, W> {
+    /// Reads the next request, or `None` once the client is gone.
+    fn receive(&mut self) -> Option<Value> {
+        match lsp::receive(&mut self.reader) {
+            Ok(Some(request)) => Some(request),
+            _ => {
+                self.disconnected = true;
+                None
+            },
+        }
+    }
+
+    fn send(&mut self, mut message: Value) {
+        self.seq += 1;
+        message["seq"] = json!(self.seq);
+
+        if lsp::send(&mut self.writer, &message).is_err() {
+            self.disconnected = true;
+        }
+    }
+
+    fn respond(&mut self, request: &Value, body: Result<Value, String>) {
+        let mut response = json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": body.is_ok(),
+        });
+
+        match body {
+            Ok(body) => response["body"] = body,
+            Err(message) => response["message"] = json!(message),
+        }
+
+        self.send(response);
+    }
+
+    fn event(&mut self, event: &str, body: Value) {
+        self.send(json!({ "type": "event", "event": event, "body": body }));
+    }
+}
+
+fn capabilities() -> Value {
+    json!({
+        "supportsConfigurationDoneRequest": true,
+        "supportsFunctionBreakpoints": true,
+        "supportsConditionalBreakpoints": true,
+        "supportsEvaluateForHovers": true,
+        "supportsTerminateRequest": true,
+    })
+}
+
+fn threads() -> Value {
+    json!({ "threads": [{ "id": THREAD, "name": "main" }] })
+}
+
+/// Replaces the line breakpoints, or the function breakpoints with `functions`.
+fn set_breakpoints(breakpoints: &mut Vec<Breakpoint>, arguments: &Value, functions: bool) -> Value {
+    breakpoints.retain(|breakpoint| matches!(breakpoint.location, Location::Line(_)) == functions);
+
+    let requested = arguments["breakpoints"].as_array().cloned().unwrap_or_default();
+    let mut results = vec![];
+
+    for requested in requested {
+        let location = match functions {
+            true => Location::Function(requested["name"].as_str().unwrap_or_default().to_string()),
+            false => Location::Line(requested["line"].as_u64().unwrap_or_default() as usize),
+        };
+
+        let condition = requested["condition"]
+            .as_str()
+            .filter(|condition| !condition.is_empty())
+            .map(debugger::parse_expression)
+            .transpose();
+
+        let mut result = json!({ "verified": condition.is_ok() });
+
+        if let Location::Line(line) = location {
+            result["line"] = json!(line);
+        }
+
+        match condition {
+            Ok(condition) => breakpoints.push(Breakpoint { location, condition }),
+            Err(message) => result["message"] = json!(message),
+        }
+
+        results.push(result);
+    }
+
+    json!({ "breakpoints": results })
+}
+
+/// Reads and parses the program to debug.
+fn launch(arguments: &Value) -> Result<(String, Vec<Stmt>), String> {
+    let path = arguments["program"].as_str().ok_or("Missing program to launch")?;
+    let source = fs::read_to_string(path).map_err(|error| format!("Could not read {path}: {error}"))?;
+
+    let mut scanner = Scanner::new(source).quiet();
+    let tokens = scanner.scan_tokens();
+    let mut parser = Parser::new(tokens).quiet();
+    let statements = parser.parse();
+
+    if let Some(error) = scanner.errors.first() {
+        return Err(format!("[line {}] Error: {}", error.line, error.message));
+    }
+
+    if let Some(error) = parser.errors.first() {
+        return Err(format!("[line {}] Error at '{}': {}", error.token.line, error.token.lexeme, error.message));
+    }
+
+    Ok((path.to_string(), statements))
+}
+
+/// Sends the output of the program as output events, a line at a time.
+struct Output<R: BufRead, W: Write> {
+    connection: Shared<R, W>,
+    buffer: Vec<u8>,
+}
+
+impl<R: BufRead, W: Write> Output<R, W> {
+    fn send(&mut self, end: usize) {
+        let output: Vec<u8> = self.buffer.drain(..end).collect();
+        let body = json!({ "category": "stdout", "output": String::from_utf8_lossy(&output) });
+        self.connection.borrow_mut().event("output", body);
+    }
+}
+
+impl<R: BufRead, W: Write> Write for Output<R, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+
+        if let Some(newline) = self.buffer.iter().rposition(|byte| *byte == b'\n') {
+            self.send(newline + 1);
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.buffer.is_empty() {
+            self.send(self.buffer.len());
+        }
+
+        Ok(())
+    }
+}
+
+/// Answers the requests of the client while the program is paused.
+struct Adapter<R: BufRead, W: Write> {
+    connection: Shared<R, W>,
+    path: String,
+    // Variables of the scopes sent since the last pause, referenced by their position plus one.
+    scopes: Vec<Vec<(String, Object)>>,
+}
+
+impl<R: BufRead, W: Write> Adapter<R, W> {
+    /// Returns the source of a frame, modules are named by their path from the program's directory.
+    fn source(&self, module: Option<&String>) -> Value {
+        let program = Path::new(&self.path);
+        let path = match module {
+            Some(module) => program.parent().unwrap_or(Path::new("")).join(module),
+            None => program.to_path_buf(),
+        };
+
+        json!({
+            "name": path.file_name().map(|name| name.to_string_lossy()),
+            "path": path.to_string_lossy(),
+        })
+    }
+
+    /// Handles a request and returns its response, and how to resume if it ends the pause.
+    fn request(&mut self, session: &mut Session, request: &Value) -> (Result<Value, String>, Option<Command>) {
+        let arguments = &request["arguments"];
+
+        let body = match request["command"].as_str().unwrap_or_default() {
+            "threads" => Ok(threads()),
+            "stackTrace" => {
+                let frames: Vec<Value> = session
+                    .call_stack()
+                    .iter()
+                    .enumerate()
+                    .map(|(id, frame)| json!({
+                        "id": id,
+                        "name": frame.function,
+                        "line": frame.line,
+                        "column": 1,
+                        "source": self.source(frame.module.as_deref()),
+                    }))
+                    .collect();
+
+                Ok(json!({ "stackFrames": frames, "totalFrames": frames.len() }))
+            },
+            "scopes" => {
+                let frame = arguments["frameId"].as_u64().unwrap_or_default() as usize;
+                let scopes = session.scopes(frame);
+                let count = scopes.len();
+
+                let scopes: Vec<Value> = scopes
+                    .into_iter()
+                    .enumerate()
+                    .map(|(depth, variables)| {
+                        let name = match depth {
+                            depth if depth + 1 == count => "Globals",
+                            0 => "Locals",
+                            _ => "Closure",
+                        };
+
+                        self.scopes.push(variables);
+                        json!({ "name": name, "variablesReference": self.scopes.len(), "expensive": false })
+                    })
+                    .collect();
+
+                Ok(json!({ "scopes": scopes }))
+            },
+            "variables" => {
+                let reference = arguments["variablesReference"].as_u64().unwrap_or_default() as usize;
+
+                let variables: Vec<Value> = self.scopes
+                    .get(reference.wrapping_sub(1))
+                    .map(|variables| variables.iter().map(|(name, value)| json!({
+                        "name": name,
+                        "value": value.to_string(),
+                        "variablesReference": 0,
+                    })).collect())
+                    .unwrap_or_default();
+
+                Ok(json!({ "variables": variables }))
+            },
+            "evaluate" => session
+                .evaluate(arguments["expression"].as_str().unwrap_or_default())
+                .map(|value| json!({ "result": value.to_string(), "variablesReference": 0 })),
+            "setBreakpoints" => Ok(set_breakpoints(session.breakpoints(), arguments, false)),
+            "setFunctionBreakpoints" => Ok(set_breakpoints(session.breakpoints(), arguments, true)),
+            "continue" => return (Ok(json!({ "allThreadsContinued": true })), Some(Command::Continue)),
+            "next" => return (Ok(Value::Null), Some(Command::StepOver)),
+            "stepIn" => return (Ok(Value::Null), Some(Command::StepIn)),
+            "stepOut" => return (Ok(Value::Null), Some(Command::StepOut)),
+            "disconnect" | "terminate" => {
+                self.connection.borrow_mut().disconnected = true;
+                return (Ok(Value::Null), Some(Command::Quit));
+            },
+            command => Err(format!("Unsupported request {command}")),
+        };
+
+        (body, None)
+    }
+}
+
+impl<R: BufRead, W: Write> Frontend for Adapter<R, W> {
+    fn paused(&mut self, session: &mut Session, reason: Reason) -> Command {
+        let reason = match reason {
+            Reason::Entry => "entry",
+            Reason::Step => "step",
+            Reason::Breakpoint(_) => "breakpoint",
+        };
+
+        self.scopes.clear();
+        self.connection.borrow_mut().event("stopped", json!({
+            "reason": reason,
+            "threadId": THREAD,
+            "allThreadsStopped": true,
+        }));
+
+        loop {
+            // The connection is not borrowed while handling the request, evaluating may print.
+            let Some(request) = self.connection.borrow_mut().receive() else {
+                return Command::Quit;
+            };
+
+            let (body, command) = self.request(session, &request);
+            self.connection.borrow_mut().respond(&request, body);
+
+            if let Some(command) = command {
+                return command;
+            }
+        }
+    }
+}
+
+/// Debug adapter speaking the Debug Adapter Protocol over a pair of streams, usually stdin and stdout.
+///
+/// Serves a single launch of a program, whose output is sent to the client as output events.
+pub struct Server<R: BufRead, W: Write> {
+    connection: Shared<R, W>,
+}
+
+impl<R: BufRead + 'static, W: Write + 'static> Server<R, W> {
+    pub fn new(reader: R, writer: W) -> Self {
+        let connection = Connection { reader, writer, seq: 0, disconnected: false };
+        Server { connection: Rc::new(RefCell::new(connection)) }
+    }
+
+    /// Serves the session until the client disconnects and returns the exit code.
+    pub fn run(&mut self) -> i32 {
+        let mut program = None;
+        let mut breakpoints = vec![];
+        let mut stop_on_entry = false;
+
+        // Configure the session until the client is done.
+        loop {
+            let Some(request) = self.connection.borrow_mut().receive() else { return 1 };
+            let arguments = &request["arguments"];
+            let command = request["command"].as_str().unwrap_or_default();
+
+            let body = match command {
+                "initialize" => Ok(capabilities()),
+                "launch" => launch(arguments).map(|launched| {
+                    program = Some(launched);
+                    stop_on_entry = arguments["stopOnEntry"].as_bool().unwrap_or(false);
+                    Value::Null
+                }),
+                "setBreakpoints" => Ok(set_breakpoints(&mut breakpoints, arguments, false)),
+                "setFunctionBreakpoints" => Ok(set_breakpoints(&mut breakpoints, arguments, true)),
+                "setExceptionBreakpoints" => Ok(json!({ "breakpoints": [] })),
+                "threads" => Ok(threads()),
+                "configurationDone" | "disconnect" | "terminate" => Ok(Value::Null),
+                command => Err(format!("Unsupported request {command}")),
+            };
+
+            let mut connection = self.connection.borrow_mut();
+            connection.respond(&request, body);
+
+            match command {
+                "initialize" => connection.event("initialized", json!({})),
+                "configurationDone" => break,
+                "disconnect" | "terminate" => return 0,
+                _ => (),
+            }
+        }
+
+        if let Some((path, statements)) = program {
+            let code = self.launch(path, statements, breakpoints, stop_on_entry);
+
+            let mut connection = self.connection.borrow_mut();
+
+            if connection.disconnected {
+                return 0;
+            }
+
+            connection.event("exited", json!({ "exitCode": code }));
+        }
+
+        self.connection.borrow_mut().event("terminated", json!({}));
+
+        // Wait for the client to disconnect.
+        loop {
+            let Some(request) = self.connection.borrow_mut().receive() else { return 1 };
+
+            let body = match request["command"].as_str().unwrap_or_default() {
+                "threads" => Ok(threads()),
+                "disconnect" | "terminate" => {
+                    self.connection.borrow_mut().respond(&request, Ok(Value::Null));
+                    return 0;
+                },
+                _ => Err("The program has terminated".to_string()),
+            };
+
+            self.connection.borrow_mut().respond(&request, body);
+        }
+    }
+
+    /// Runs the program under the debugger and returns its exit code.
+    fn launch(&mut self, path: String, statements: Vec<Stmt>, breakpoints: Vec<Breakpoint>, stop_on_entry: bool) -> i32 {
+        let mut interpreter = crate::script_interpreter(Path::new(&path));
+        let adapter = Adapter { connection: Rc::clone(&self.connection), path, scopes: vec![] };
+
+        let debugger = match stop_on_entry {
+            true => {
+                let mut debugger = Debugger::new(Box::new(adapter));
+                debugger.set_breakpoints(breakpoints);
+                debugger
+            },
+            false => Debugger::with_breakpoints(Box::new(adapter), breakpoints),
+        };
+
+        interpreter.set_output(Box::new(Output { connection: Rc::clone(&self.connection), buffer: vec![] }));
+        interpreter.add_hook(Box::new(debugger));
+
+        match interpreter.interpret(&statements) {
+            Ok(_) => 0,
+            Err(error) if error.kind == RuntimeErrorKind::Interrupted => 0,
+            Err(error) => {
+                let body = json!({ "category": "stderr", "output": format!("{error}\n") });
+                self.connection.borrow_mut().event("output", body);
+                70
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::testing::files;
+
+    /// Writer shared with the test, since the server is generic over an owned writer.
+    #[derive(Clone, Default)]
+    struct Sent(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Sent {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Writes the program to a file unique to the test and returns its path.
+    fn program(name: &str, source: &str) -> String {
+        let path = std::env::temp_dir().join(format!("rlox-dap-{}-{name}.lox", std::process::id()));
+        fs::write(&path, source).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    /// Runs a session with the scripted requests and returns the exit code and everything sent.
+    fn session(requests: Vec<(&str, Value)>) -> (i32, Vec<Value>) {
+        let mut input = vec![];
+
+        for (seq, (command, arguments)) in requests.into_iter().enumerate() {
+            let request = json!({ "seq": seq + 1, "type": "request", "command": command, "arguments": arguments });
+            lsp::send(&mut input, &request).unwrap();
+        }
+
+        let sent = Sent::default();
+        let code = Server::new(Cursor::new(input), sent.clone()).run();
+
+        let bytes = sent.0.borrow().clone();
+        let mut reader = bytes.as_slice();
+        let mut messages = vec![];
+
+        while let Ok(Some(message)) = lsp::receive(&mut reader) {
+            messages.push(message);
+        }
+
+        (code, messages)
+    }
+
+    fn response<'a>(messages: &'a [Value], command: &str) -> &'a Value {
+        messages
+            .iter()
+            .find(|message| message["type"] == "response" && message["command"] == command)
+            .expect("response to be sent")
+    }
+
+    fn events<'a>(messages: &'a [Value], event: &str) -> Vec<&'a Value> {
+        messages.iter().filter(|message| message["event"] == event).collect()
+    }
+
+    const PROGRAM: &str = "\
+var x = 10;
+fun f(a) {
+  var b = a + x;
+  return b;
+}
+print f(1);
+print f(2);
+";
+
+    #[test]
+    fn breakpoints_and_inspection() {
+        let path = program("inspect", PROGRAM);
+
+        let (code, messages) = session(vec![
+            ("initialize", json!({ "adapterID": "rlox" })),
+            ("launch", json!({ "program": path })),
+            ("setBreakpoints", json!({ "source": { "path": path }, "breakpoints": [{ "line": 4, "condition": "a == 2" }] })),
+            ("configurationDone", json!({})),
+            ("threads", json!({})),
+            ("stackTrace", json!({ "threadId": THREAD })),
+            ("scopes", json!({ "frameId": 0 })),
+            ("variables", json!({ "variablesReference": 1 })),
+            ("scopes", json!({ "frameId": 1 })),
+            ("evaluate", json!({ "expression": "b * 2", "frameId": 0 })),
+            ("continue", json!({ "threadId": THREAD })),
+            ("disconnect", json!({})),
+        ]);
+
+        assert_eq!(code, 0);
+        assert_eq!(events(&messages, "initialized").len(), 1);
+        assert_eq!(response(&messages, "setBreakpoints")["body"]["breakpoints"][0]["verified"], true);
+
+        let stopped = events(&messages, "stopped");
+        assert_eq!(stopped.len(), 1);
+        assert_eq!(stopped[0]["body"]["reason"], "breakpoint");
+
+        let frames = &response(&messages, "stackTrace")["body"]["stackFrames"];
+        assert_eq!(frames[0]["name"], "f()");
+        assert_eq!(frames[0]["line"], 4);
+        assert_eq!(frames[1]["name"], "script");
+        assert_eq!(frames[1]["line"], 7);
+
+        let scopes: Vec<&Value> = messages.iter().filter(|message| message["command"] == "scopes").collect();
+        assert_eq!(scopes[0]["body"]["scopes"][0]["name"], "Locals");
+        assert_eq!(scopes[1]["body"]["scopes"][0]["name"], "Globals");
+
+        let variables = &response(&messages, "variables")["body"]["variables"];
+        assert_eq!(variables, &json!([
+            { "name": "a", "value": "2", "variablesReference": 0 },
+            { "name": "b", "value": "12", "variablesReference": 0 },
+        ]));
+
+        assert_eq!(response(&messages, "evaluate")["body"]["result"], "24");
+
+        let output: Vec<&Value> = events(&messages, "output").iter().map(|event| &event["body"]["output"]).collect();
+        assert_eq!(output, vec!["11\n", "12\n"]);
+
+        assert_eq!(events(&messages, "exited")[0]["body"]["exitCode"], 0);
+        assert_eq!(events(&messages, "terminated").len(), 1);
+    }
+
+    #[test]
+    fn stepping() {
+        let path = program("stepping", PROGRAM);
+
+        let (_, messages) = session(vec![
+            ("initialize", json!({})),
+            ("launch", json!({ "program": path, "stopOnEntry": true })),
+            ("setFunctionBreakpoints", json!({ "breakpoints": [{ "name": "f" }] })),
+            ("configurationDone", json!({})),
+            ("next", json!({ "threadId": THREAD })),
+            ("next", json!({ "threadId": THREAD })),
+            ("continue", json!({ "threadId": THREAD })),
+            ("stepOut", json!({ "threadId": THREAD })),
+            ("stackTrace", json!({ "threadId": THREAD })),
+            ("disconnect", json!({})),
+        ]);
+
+        let reasons: Vec<&Value> = events(&messages, "stopped").iter().map(|event| &event["body"]["reason"]).collect();
+        assert_eq!(reasons, vec!["entry", "step", "step", "breakpoint", "step"]);
+
+        // Stepped out of the first call onto the second print.
+        assert_eq!(response(&messages, "stackTrace")["body"]["stackFrames"][0]["line"], 7);
+
+        // Disconnecting stops the program without running the rest.
+        assert_eq!(events(&messages, "output").len(), 1);
+        assert!(events(&messages, "terminated").is_empty());
+    }
+
+    #[test]
+    fn runtime_error() {
+        let path = program("error", "print 1;\nprint undefined;\n");
+
+        let (_, messages) = session(vec![
+            ("initialize", json!({})),
+            ("launch", json!({ "program": path })),
+            ("configurationDone", json!({})),
+            ("disconnect", json!({})),
+        ]);
+
+        let output: Vec<&Value> = events(&messages, "output").iter().map(|event| &event["body"]["output"]).collect();
+        assert_eq!(output, vec!["1\n", "[line 2] Error at 'undefined': Undefined variable 'undefined'\n  [line 2] in script\n"]);
+        assert_eq!(events(&messages, "exited")[0]["body"]["exitCode"], 70);
+    }
+
+    #[test]
+    fn imports_next_to_program() {
+        let module = program("module", "var x = 42;\n");
+        let name = Path::new(&module).file_name().unwrap().to_string_lossy().to_string();
+        let path = program("import", &format!("import \"{name}\" as lib;\nprint lib.x;\n"));
+
+        let (_, messages) = session(vec![
+            ("initialize", json!({})),
+            ("launch", json!({ "program": path })),
+            ("configurationDone", json!({})),
+            ("disconnect", json!({})),
+        ]);
+
+        let output: Vec<&Value> = events(&messages, "output").iter().map(|event| &event["body"]["output"]).collect();
+        assert_eq!(output, vec!["42\n"]);
+        assert_eq!(events(&messages, "exited")[0]["body"]["exitCode"], 0);
+    }
+
+    #[test]
+    fn module_frames() {
+        let directory = files("dap", &[
+            ("main.lox", "import \"lib/util.lox\" as util;\nprint util.f();\n"),
+            ("lib/util.lox", "fun f() {\n  return 1;\n}\n"),
+        ]);
+        let path = directory.join("main.lox").to_string_lossy().to_string();
+
+        let (_, messages) = session(vec![
+            ("initialize", json!({})),
+            ("launch", json!({ "program": path })),
+            ("setFunctionBreakpoints", json!({ "breakpoints": [{ "name": "f" }] })),
+            ("configurationDone", json!({})),
+            ("stackTrace", json!({ "threadId": THREAD })),
+            ("disconnect", json!({})),
+        ]);
+
+        let frames = &response(&messages, "stackTrace")["body"]["stackFrames"];
+        assert_eq!(frames[0]["name"], "f()");
+        assert_eq!(frames[0]["source"]["name"], "util.lox");
+        assert_eq!(frames[0]["source"]["path"], directory.join("lib").join("util.lox").to_string_lossy().as_ref());
+        assert_eq!(frames[1]["source"]["name"], "main.lox");
+        assert_eq!(frames[1]["source"]["path"], path);
+    }
+
+    #[test]
+    fn launch_errors() {
+        let path = program("parse", "print ;\n");
+
+        let (code, messages) = session(vec![
+            ("launch", json!({ "program": path })),
+            ("disconnect", json!({})),
+        ]);
+
+        assert_eq!(code, 0);
+        assert_eq!(response(&messages, "launch")["success"], false);
+        assert_eq!(response(&messages, "launch")["message"], "[line 1] Error at ';': Expected expression");
+    }
+}
@@ -0,0 +1,559 @@
+use std::io::{BufRead, Write};
+
+use crate::error::{Frame, RuntimeError};
+use crate::expr::Expr;
+use crate::interpreter::{Hook, Interpreter};
+use crate::object::Object;
+use crate::parser::Parser;
+use crate::scanner::Scanner;
+use crate::stmt::Stmt;
+
+/// Parses a single expression, such as a breakpoint condition.
+pub fn parse_expression(source: &str) -> Result<Expr, String> {
+    let tokens = Scanner::new(format!("{source};")).quiet().scan_tokens();
+    let mut parser = Parser::new(tokens).quiet();
+    let mut statements = parser.parse();
+
+    if let Some(error) = parser.errors.first() {
+        return Err(error.message.clone());
+    }
+
+    match (statements.pop(), statements.is_empty()) {
+        (Some(Stmt::Expression(data)), true) => Ok(data.expr),
+        _ => Err("Expected a single expression".to_string()),
+    }
+}
+
+/// Represents where a breakpoint pauses the execution.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Location {
+    Line(usize),
+    /// Pauses at the first statement of every call to the function.
+    Function(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Breakpoint {
+    pub location: Location,
+    /// Only pauses when the condition is truthy in the paused frame.
+    pub condition: Option<Expr>,
+}
+
+/// Represents why the execution was paused.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Reason {
+    Entry,
+    Step,
+    Breakpoint(usize),
+}
+
+/// Represents how to resume a paused execution.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Command {
+    Continue,
+    StepIn,
+    StepOver,
+    StepOut,
+    /// Stops the execution with an "Interrupted" error.
+    Quit,
+}
+
+/// Gives a frontend access to a paused execution.
+pub struct Session<'a> {
+    interpreter: &'a mut Interpreter,
+    breakpoints: &'a mut Vec<Breakpoint>,
+    line: usize,
+}
+
+impl Session<'_> {
+    /// Returns the line of the statement about to be executed.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Returns the active calls, innermost first.
+    pub fn call_stack(&self) -> Vec<Frame> {
+        self.interpreter.backtrace(self.line)
+    }
+
+    /// Returns the variables of every scope visible in the frame, innermost first.
+    ///
+    /// Frames are numbered like `call_stack`, from the paused one. Native functions are left out of
+    /// the global scope.
+    pub fn scopes(&self, frame: usize) -> Vec<Vec<(String, Object)>> {
+        let mut scopes = vec![];
+        let mut environment = self.interpreter.frame_environments().get(frame).cloned();
+
+        while let Some(current) = environment {
+            let mut variables: Vec<(String, Object)> = current
+                .borrow()
+                .variables
+                .iter()
+                .filter(|(_, value)| !matches!(value, Object::NativeFunction(_)))
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect();
+            variables.sort_by(|a, b| a.0.cmp(&b.0));
+
+            scopes.push(variables);
+            environment = current.borrow().enclosing.clone();
+        }
+
+        scopes
+    }
+
+    /// Evaluates the expression in the paused frame.
+    pub fn evaluate(&mut self, source: &str) -> Result<Object, String> {
+        let expr = parse_expression(source)?;
+        self.interpreter.evaluate(&expr).map_err(|error| error.message)
+    }
+
+    pub fn breakpoints(&mut self) -> &mut Vec<Breakpoint> {
+        self.breakpoints
+    }
+}
+
+/// Interacts with the user while the execution is paused.
+pub trait Frontend {
+    fn paused(&mut self, session: &mut Session, reason: Reason) -> Command;
+}
+
+impl<F: FnMut(&mut Session, Reason) -> Command> Frontend for F {
+    fn paused(&mut self, session: &mut Session, reason: Reason) -> Command {
+        self(session, reason)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Mode {
+    Run,
+    StepIn,
+    // Pauses once the call stack is at most this deep.
+    StepOver(usize),
+    // Pauses once the call stack is shallower than this.
+    StepOut(usize),
+}
+
+/// Pauses the execution at breakpoints and steps, driven by a frontend.
+///
+/// Execution is paused at most once per line and call depth, so a line holding several
+/// statements is stepped over at once. A statement that runs again, like the body of a loop,
+/// can be paused at again.
+pub struct Debugger {
+    frontend: Box<dyn Frontend>,
+    breakpoints: Vec<Breakpoint>,
+    mode: Mode,
+    // The line and call depth of the previous statement, with the addresses of the statements
+    // run on it since execution could last pause.
+    previous: Option<(usize, usize, Vec<usize>)>,
+    // A function breakpoint hit by the call at the given depth, pausing at its first statement.
+    pending: Option<(usize, usize)>,
+    started: bool,
+}
+
+impl Debugger {
+    /// Creates a debugger that pauses before the first statement.
+    pub fn new(frontend: Box<dyn Frontend>) -> Self {
+        Debugger {
+            frontend,
+            breakpoints: vec![],
+            mode: Mode::StepIn,
+            previous: None,
+            pending: None,
+            started: false,
+        }
+    }
+
+    /// Creates a debugger that runs until the first breakpoint.
+    pub fn with_breakpoints(frontend: Box<dyn Frontend>, breakpoints: Vec<Breakpoint>) -> Self {
+        Debugger { breakpoints, mode: Mode::Run, ..Debugger::new(frontend) }
+    }
+
+    pub fn set_breakpoints(&mut self, breakpoints: Vec<Breakpoint>) {
+        self.breakpoints = breakpoints;
+    }
+
+    /// Returns if the condition of the breakpoint holds in the current frame.
+    fn condition(interpreter: &mut Interpreter, breakpoint: &Breakpoint) -> bool {
+        match &breakpoint.condition {
+            Some(condition) => interpreter
+                .evaluate(condition)
+                .is_ok_and(|value| value.as_bool()),
+            None => true,
+        }
+    }
+
+    fn breakpoint(&mut self, interpreter: &mut Interpreter, line: usize, depth: usize) -> Option<usize> {
+        // The call was entered by the previous statement, unless its body is empty.
+        if let Some((index, pending)) = self.pending.take() {
+            if pending == depth {
+                let breakpoint = self.breakpoints.get(index)?;
+                return Debugger::condition(interpreter, breakpoint).then_some(index);
+            }
+        }
+
+        self.breakpoints.iter().position(|breakpoint| {
+            breakpoint.location == Location::Line(line) && Debugger::condition(interpreter, breakpoint)
+        })
+    }
+}
+
+impl Hook for Debugger {
+    fn statement(&mut self, interpreter: &mut Interpreter, stmt: &Stmt) -> Result<(), RuntimeError> {
+        // The statements of a block are paused at on their own.
+        if matches!(stmt, Stmt::Block(_)) {
+            return Ok(());
+        }
+
+        // Statements without a token, such as `1;`, can't be paused at.
+        let Some(token) = stmt.token() else { return Ok(()) };
+        let line = token.line;
+        let depth = interpreter.call_stack().len();
+
+        let address = stmt as *const Stmt as usize;
+
+        match &mut self.previous {
+            Some((previous, previous_depth, seen))
+                if (*previous, *previous_depth) == (line, depth) && !seen.contains(&address) =>
+            {
+                seen.push(address);
+                return Ok(());
+            },
+            _ => self.previous = Some((line, depth, vec![address])),
+        }
+
+        let reason = match (self.breakpoint(interpreter, line, depth), self.mode) {
+            (Some(index), _) => Reason::Breakpoint(index),
+            (None, Mode::StepIn) => Reason::Step,
+            (None, Mode::StepOver(target)) if depth <= target => Reason::Step,
+            (None, Mode::StepOut(target)) if depth < target => Reason::Step,
+            _ => return Ok(()),
+        };
+
+        let reason = match reason {
+            Reason::Step if !self.started => Reason::Entry,
+            _ => reason,
+        };
+        self.started = true;
+
+        let mut session = Session { interpreter, breakpoints: &mut self.breakpoints, line };
+
+        self.mode = match self.frontend.paused(&mut session, reason) {
+            Command::Continue => Mode::Run,
+            Command::StepIn => Mode::StepIn,
+            Command::StepOver => Mode::StepOver(depth),
+            Command::StepOut => Mode::StepOut(depth),
+            Command::Quit => return Err(RuntimeError::interrupted(token.clone())),
+        };
+
+        Ok(())
+    }
+
+    fn enter(&mut self, interpreter: &Interpreter, function: &str, _arguments: &[Object]) {
+        let location = Location::Function(function.to_string());
+
+        if let Some(index) = self.breakpoints.iter().position(|breakpoint| breakpoint.location == location) {
+            self.pending = Some((index, interpreter.call_stack().len()));
+        }
+    }
+}
+
+const HELP: &str = "\
+break <line|function> [if <condition>]  set a breakpoint
+delete <number>                         delete a breakpoint
+continue, step, next, finish            resume, step in, over or out
+print <expression>                      evaluate in the paused frame
+env                                     show the variables of every scope
+backtrace                               show the call stack
+quit                                    stop the program";
+
+/// Frontend reading commands from a terminal.
+pub struct Console<R: BufRead, W: Write> {
+    input: R,
+    output: W,
+    source: Vec<String>,
+}
+
+impl<R: BufRead, W: Write> Console<R, W> {
+    /// Creates a console showing lines from the source of the debugged program.
+    pub fn new(input: R, output: W, source: &str) -> Self {
+        Console { input, output, source: source.lines().map(String::from).collect() }
+    }
+
+    fn say(&mut self, text: &str) {
+        writeln!(self.output, "{text}").unwrap();
+    }
+
+    /// Parses `<line|function> [if <condition>]`.
+    fn breakpoint(arguments: &str) -> Result<Breakpoint, String> {
+        let (location, condition) = match arguments.split_once(" if ") {
+            Some((location, condition)) => (location.trim(), Some(parse_expression(condition)?)),
+            None => (arguments.trim(), None),
+        };
+
+        let location = match location.parse::<usize>() {
+            Ok(line) => Location::Line(line),
+            Err(_) if !location.is_empty() => Location::Function(location.to_string()),
+            Err(_) => return Err("Expected a line or a function".to_string()),
+        };
+
+        Ok(Breakpoint { location, condition })
+    }
+
+    fn command(&mut self, session: &mut Session, command: &str, arguments: &str) -> Option<Command> {
+        match command {
+            "c" | "continue" => return Some(Command::Continue),
+            "s" | "step" => return Some(Command::StepIn),
+            "n" | "next" => return Some(Command::StepOver),
+            "f" | "finish" => return Some(Command::StepOut),
+            "q" | "quit" => return Some(Command::Quit),
+            "b" | "break" => match Console::<R, W>::breakpoint(arguments) {
+                Ok(breakpoint) => {
+                    let message = match &breakpoint.location {
+                        Location::Line(line) => format!("at line {line}"),
+                        Location::Function(function) => format!("in {function}()"),
+                    };
+
+                    session.breakpoints().push(breakpoint);
+                    self.say(&format!("Breakpoint {} {message}", session.breakpoints().len()));
+                },
+                Err(message) => self.say(&format!("Error: {message}")),
+            },
+            "d" | "delete" => match arguments.parse::<usize>() {
+                Ok(number) if (1..=session.breakpoints().len()).contains(&number) => {
+                    session.breakpoints().remove(number - 1);
+                },
+                _ => self.say("Error: No such breakpoint"),
+            },
+            "p" | "print" => match session.evaluate(arguments) {
+                Ok(value) => self.say(&value.to_string()),
+                Err(message) => self.say(&format!("Error: {message}")),
+            },
+            "e" | "env" => {
+                let scopes = session.scopes(0);
+
+                for (depth, scope) in scopes.iter().enumerate() {
+                    let name = if depth + 1 == scopes.len() { "globals".to_string() } else { format!("scope {depth}") };
+                    self.say(&format!("{name}:"));
+
+                    for (variable, value) in scope {
+                        self.say(&format!("  {variable} = {value}"));
+                    }
+                }
+            },
+            "bt" | "backtrace" => {
+                for frame in session.call_stack() {
+                    self.say(&format!("  {frame}"));
+                }
+            },
+            "h" | "help" => self.say(HELP),
+            "" => (),
+            _ => self.say(&format!("Unknown command '{command}', type 'help' for a list")),
+        }
+
+        None
+    }
+}
+
+impl<R: BufRead, W: Write> Frontend for Console<R, W> {
+    fn paused(&mut self, session: &mut Session, reason: Reason) -> Command {
+        let line = session.line();
+
+        match reason {
+            Reason::Entry => self.say(&format!("Paused at line {line}")),
+            Reason::Step => (),
+            Reason::Breakpoint(index) => self.say(&format!("Breakpoint {} hit at line {line}", index + 1)),
+        }
+
+        let text = self.source.get(line - 1).cloned().unwrap_or_default();
+        self.say(&format!("{line:>4} | {text}"));
+
+        loop {
+            write!(self.output, "(debug) ").unwrap();
+            self.output.flush().unwrap();
+
+            let mut input = String::new();
+
+            // Stop the program at the end of input.
+            if self.input.read_line(&mut input).unwrap_or(0) == 0 {
+                return Command::Quit;
+            }
+
+            let input = input.trim();
+            let (command, arguments) = input.split_once(' ').unwrap_or((input, ""));
+
+            if let Some(command) = self.command(session, command, arguments.trim()) {
+                return command;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::error::RuntimeErrorKind;
+
+    fn program(source: &str) -> Vec<Stmt> {
+        let tokens = Scanner::new(source.to_string()).scan_tokens();
+        Parser::new(tokens).parse()
+    }
+
+    /// Runs the program, pausing with the commands in order and recording where it paused.
+    fn debug(source: &str, breakpoints: Vec<Breakpoint>, commands: Vec<Command>) -> Vec<(usize, Reason, usize)> {
+        let pauses = Rc::new(RefCell::new(vec![]));
+        let mut commands = commands.into_iter();
+
+        let recorder = Rc::clone(&pauses);
+        let frontend = move |session: &mut Session, reason: Reason| {
+            recorder.borrow_mut().push((session.line(), reason, session.call_stack().len()));
+            commands.next().unwrap_or(Command::Continue)
+        };
+
+        let debugger = match breakpoints.is_empty() {
+            true => Debugger::new(Box::new(frontend)),
+            false => Debugger::with_breakpoints(Box::new(frontend), breakpoints),
+        };
+
+        let mut interpreter = Interpreter::new();
+        interpreter.add_hook(Box::new(debugger));
+        interpreter.interpret(&program(source)).unwrap();
+
+        pauses.take()
+    }
+
+    fn line(line: usize) -> Breakpoint {
+        Breakpoint { location: Location::Line(line), condition: None }
+    }
+
+    const PROGRAM: &str = "\
+fun f(a) {
+  var b = a + 1;
+  return b;
+}
+var x = f(1);
+print x;
+";
+
+    #[test]
+    fn step_in() {
+        let pauses = debug(PROGRAM, vec![], vec![Command::StepIn; 5]);
+
+        assert_eq!(pauses, vec![
+            (1, Reason::Entry, 1),
+            (5, Reason::Step, 1),
+            (2, Reason::Step, 2),
+            (3, Reason::Step, 2),
+            (6, Reason::Step, 1),
+        ]);
+    }
+
+    #[test]
+    fn step_over_and_out() {
+        let pauses = debug(PROGRAM, vec![], vec![Command::StepOver, Command::StepOver, Command::StepOver]);
+        assert_eq!(pauses.iter().map(|pause| pause.0).collect::<Vec<usize>>(), vec![1, 5, 6]);
+
+        let pauses = debug(PROGRAM, vec![line(2)], vec![Command::StepOut]);
+        assert_eq!(pauses.iter().map(|pause| pause.0).collect::<Vec<usize>>(), vec![2, 6]);
+    }
+
+    #[test]
+    fn breakpoints() {
+        let source = "for (var i = 0; i < 3; i = i + 1) {\n  print i;\n}\n";
+
+        let pauses = debug(source, vec![line(2)], vec![]);
+        assert_eq!(pauses.len(), 3);
+
+        let condition = Breakpoint { location: Location::Line(2), condition: Some(parse_expression("i == 2").unwrap()) };
+        let pauses = debug(source, vec![condition], vec![]);
+        assert_eq!(pauses, vec![(2, Reason::Breakpoint(0), 1)]);
+
+        // The body of a loop runs again on the same line.
+        let pauses = debug("var i = 0;\nwhile (i < 3)\n  i = i + 1;\nprint i;\n", vec![line(3)], vec![]);
+        assert_eq!(pauses.len(), 3);
+
+        let pauses = debug("var i = 0;\nwhile (i < 3) { i = i + 1; }\n", vec![line(2)], vec![]);
+        assert_eq!(pauses.len(), 3);
+
+        let pauses = debug("var i = 0;\nwhile (i < 3) { i = i + 1; }\n", vec![], vec![Command::StepIn; 5]);
+        assert_eq!(pauses.iter().map(|pause| pause.0).collect::<Vec<usize>>(), vec![1, 2, 2, 2]);
+
+        let function = Breakpoint { location: Location::Function("f".to_string()), condition: None };
+        let pauses = debug(PROGRAM, vec![function], vec![]);
+        assert_eq!(pauses, vec![(2, Reason::Breakpoint(0), 2)]);
+    }
+
+    #[test]
+    fn inspect_paused_frame() {
+        let values = Rc::new(RefCell::new(vec![]));
+
+        let recorder = Rc::clone(&values);
+        let frontend = move |session: &mut Session, _: Reason| {
+            let scopes = session.scopes(0);
+            recorder.borrow_mut().push(format!("{:?}", scopes.iter().map(|scope| scope.len()).collect::<Vec<usize>>()));
+            recorder.borrow_mut().push(session.evaluate("a * 10").unwrap().to_string());
+            Command::Continue
+        };
+
+        let mut interpreter = Interpreter::new();
+        interpreter.add_hook(Box::new(Debugger::with_breakpoints(Box::new(frontend), vec![line(3)])));
+        interpreter.interpret(&program(PROGRAM)).unwrap();
+
+        // The function scope holds `a` and `b`, the globals hold `f`.
+        assert_eq!(*values.borrow(), vec!["[2, 1]", "10"]);
+    }
+
+    #[test]
+    fn quit() {
+        let mut interpreter = Interpreter::new();
+        interpreter.add_hook(Box::new(Debugger::new(Box::new(|_: &mut Session, _: Reason| Command::Quit))));
+
+        let error = interpreter.interpret(&program(PROGRAM)).unwrap_err();
+        assert_eq!(error.kind, RuntimeErrorKind::Interrupted);
+    }
+
+    /// Writer shared with the test, since the console is owned by the debugger.
+    #[derive(Clone, Default)]
+    struct Output(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn console() {
+        let input = "break 3 if a == 1\ncontinue\nprint b\nenv\nbacktrace\nfoo\ncontinue\n";
+        let output = Output::default();
+
+        let console = Console::new(input.as_bytes(), output.clone(), PROGRAM);
+        let mut interpreter = Interpreter::new();
+        interpreter.add_hook(Box::new(Debugger::new(Box::new(console))));
+        interpreter.interpret(&program(PROGRAM)).unwrap();
+
+        let output = String::from_utf8(output.0.borrow().clone()).unwrap();
+        assert_eq!(output, "\
+Paused at line 1
+   1 | fun f(a) {
+(debug) Breakpoint 1 at line 3
+(debug) Breakpoint 1 hit at line 3
+   3 |   return b;
+(debug) 2
+(debug) scope 0:
+  a = 1
+  b = 2
+globals:
+  f = <fn f>
+(debug)   [line 3] in f()
+  [line 5] in script
+(debug) Unknown command 'foo', type 'help' for a list
+(debug) ");
+    }
+}
@@ -4,7 +4,7 @@ use std::rc::Rc;
 
 use crate::object::Object;
 use crate::token::Token;
-use crate::error::{RuntimeError, rloxError};
+use crate::error::RuntimeError;
 
 #[derive(Debug, Clone)]
 pub struct Environment {
@@ -24,21 +24,18 @@ impl Environment {
         self.variables.insert(name.to_owned(), value);
     }
 
-    pub fn assign(&mut self, name: &Token, value: Object) {
+    pub fn assign(&mut self, name: &Token, value: Object) -> Result<(), RuntimeError> {
         if self.variables.contains_key(&name.lexeme) {
             self.variables.insert(name.lexeme.to_owned(), value);
-            return
+            return Ok(());
         }
 
         if let Some(enclosing) = &mut self.enclosing {
-            enclosing.borrow_mut().assign(name, value);
-            return
+            return enclosing.borrow_mut().assign(name, value);
         }
 
-        RuntimeError {
-            token: name.to_owned(),
-            message: format!("Undefined variable '{}'", name.lexeme),
-        }.throw();
+        let message = format!("Undefined variable '{}'", name.lexeme);
+        Err(RuntimeError::new(name.to_owned(), message))
     }
 
     pub fn get(&self, name: &Token) -> Result<Object, RuntimeError> {
@@ -51,7 +48,7 @@ impl Environment {
         }
 
         let message = format!("Undefined variable '{}'", name.lexeme);
-        Err(RuntimeError { token: name.clone(), message })
+        Err(RuntimeError::new(name.clone(), message))
     }
 }
 
@@ -142,8 +139,8 @@ mod test {
         env.define("a", Object::from(1.0));
         env.define("b", Object::from(2.0));
 
-        env.assign(&Token::new(Type::Identifier, "a".to_string(), None, 1), Object::from(3.0));
-        env.assign(&Token::new(Type::Identifier, "b".to_string(), None, 1), Object::from(4.0));
+        env.assign(&Token::new(Type::Identifier, "a".to_string(), None, 1), Object::from(3.0)).unwrap();
+        env.assign(&Token::new(Type::Identifier, "b".to_string(), None, 1), Object::from(4.0)).unwrap();
 
         assert_eq!(env.variables.get("a").unwrap(), &Object::from(3.0));
         assert_eq!(env.variables.get("b").unwrap(), &Object::from(4.0));
@@ -155,8 +152,9 @@ mod test {
         env.define("a", Object::from(1.0));
         env.define("b", Object::from(2.0));
 
-        env.assign(&Token::new(Type::Identifier, "c".to_string(), None, 1), Object::from(3.0));
-        assert!(env.variables.get("c").is_none());
+        let result = env.assign(&Token::new(Type::Identifier, "c".to_string(), None, 1), Object::from(3.0));
+        assert!(result.is_err());
+        assert!(!env.variables.contains_key("c"));
    }
 
     #[test]
@@ -169,10 +167,10 @@ mod test {
         env2.define("c", Object::from(3.0));
         env2.define("d", Object::from(4.0));
 
-        env2.assign(&Token::new(Type::Identifier, "a".to_string(), None, 1), Object::from(5.0));
-        env2.assign(&Token::new(Type::Identifier, "b".to_string(), None, 1), Object::from(6.0));
-        env2.assign(&Token::new(Type::Identifier, "c".to_string(), None, 1), Object::from(7.0));
-        env2.assign(&Token::new(Type::Identifier, "d".to_string(), None, 1), Object::from(8.0));
+        env2.assign(&Token::new(Type::Identifier, "a".to_string(), None, 1), Object::from(5.0)).unwrap();
+        env2.assign(&Token::new(Type::Identifier, "b".to_string(), None, 1), Object::from(6.0)).unwrap();
+        env2.assign(&Token::new(Type::Identifier, "c".to_string(), None, 1), Object::from(7.0)).unwrap();
+        env2.assign(&Token::new(Type::Identifier, "d".to_string(), None, 1), Object::from(8.0)).unwrap();
 
         assert_eq!(env2.get(&Token::new(Type::Identifier, "a".to_string(), None, 1)).unwrap(), Object::from(5.0));
         assert_eq!(env2.get(&Token::new(Type::Identifier, "b".to_string(), None, 1)).unwrap(), Object::from(6.0));
@@ -190,8 +188,8 @@ mod test {
         env2.define("a", Object::from(3.0));
         env2.define("b", Object::from(4.0));
 
-        env2.assign(&Token::new(Type::Identifier, "a".to_string(), None, 1), Object::from(5.0));
-        env2.assign(&Token::new(Type::Identifier, "b".to_string(), None, 1), Object::from(6.0));
+        env2.assign(&Token::new(Type::Identifier, "a".to_string(), None, 1), Object::from(5.0)).unwrap();
+        env2.assign(&Token::new(Type::Identifier, "b".to_string(), None, 1), Object::from(6.0)).unwrap();
 
         assert_eq!(env2.get(&Token::new(Type::Identifier, "a".to_string(), None, 1)).unwrap(), Object::from(5.0));
         assert_eq!(env2.get(&Token::new(Type::Identifier, "b".to_string(), None, 1)).unwrap(), Object::from(6.0));
@@ -1,3 +1,6 @@
+use std::fmt;
+use std::rc::Rc;
+
 use crate::{HAD_ERROR, HAD_RUNTIME_ERROR};
 use crate::object::Object;
 use crate::token::{Token, Type};
@@ -6,26 +9,41 @@ pub fn did_error() -> bool {
     unsafe { HAD_ERROR }
 }
 
+/// Clears the error flags between independent runs.
+pub fn reset() {
+    unsafe {
+        HAD_ERROR = false;
+        HAD_RUNTIME_ERROR = false;
+    }
+}
+
 #[allow(non_camel_case_types)]
 pub trait rloxError {
     fn throw(&self);
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ScanError {
     pub line: usize,
     pub location: usize,
     pub message: String,
 }
 
-impl rloxError for ScanError {
-    fn throw(&self) {
-        println!(
+impl fmt::Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
             "[line {line}:{location}] Error: {message}",
             line = self.line,
             location = self.location + 1,
             message = self.message
-        );
+        )
+    }
+}
+
+impl rloxError for ScanError {
+    fn throw(&self) {
+        println!("{self}");
 
         unsafe {
             HAD_ERROR = true;
@@ -39,22 +57,30 @@ pub struct ParseError {
     pub message: String,
 }
 
-impl rloxError for ParseError {
-    fn throw(&self) {
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         if self.token.r#type == Type::EOF {
-            println!(
+            write!(
+                f,
                 "[line {line}] Error at end: {message}",
                 line = self.token.line,
                 message = self.message
-            );
+            )
         } else {
-            println!(
+            write!(
+                f,
                 "[line {line}] Error at '{lexeme}': {message}",
                 line = self.token.line,
                 lexeme = self.token.lexeme,
                 message = self.message
-            );
+            )
         }
+    }
+}
+
+impl rloxError for ParseError {
+    fn throw(&self) {
+        println!("{self}");
 
         unsafe {
             HAD_ERROR = true;
@@ -62,20 +88,101 @@ impl rloxError for ParseError {
     }
 }
 
+/// Represents a single frame of a Lox-level stack trace.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Frame {
+    pub function: String,
+    pub line: usize,
+    /// Name of the imported module the line is in, `None` for the script itself.
+    pub module: Option<Rc<String>>,
+}
+
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} in {}", location(self.module.as_deref().map(String::as_str), self.line), self.function)
+    }
+}
+
+/// Formats a line, prefixed by the module it is in unless it is in the script itself.
+pub(crate) fn location(module: Option<&str>, line: usize) -> String {
+    match module {
+        Some(module) => format!("[{module}, line {line}]"),
+        None => format!("[line {line}]"),
+    }
+}
+
+/// Represents the reason evaluation was stopped by a runtime error.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum RuntimeErrorKind {
+    /// Raised by the script itself, such as a type error or an undefined variable.
+    Error,
+    /// Raised when the step budget or the deadline of the interpreter runs out.
+    LimitExceeded,
+    /// Raised when evaluation is cancelled through a `CancelHandle`.
+    Interrupted,
+}
+
 #[derive(Debug)]
 pub struct RuntimeError {
+    pub kind: RuntimeErrorKind,
     pub token: Token,
     pub message: String,
+    /// Stack trace of the error, innermost frame first.
+    pub trace: Vec<Frame>,
+}
+
+impl RuntimeError {
+    /// Creates a new runtime error without a stack trace.
+    pub fn new(token: Token, message: String) -> Self {
+        RuntimeError { kind: RuntimeErrorKind::Error, token, message, trace: vec![] }
+    }
+
+    /// Creates a new error for an exceeded execution limit.
+    pub fn limit_exceeded(token: Token, message: String) -> Self {
+        RuntimeError { kind: RuntimeErrorKind::LimitExceeded, token, message, trace: vec![] }
+    }
+
+    /// Creates a new error for a cancelled evaluation.
+    pub fn interrupted(token: Token) -> Self {
+        RuntimeError {
+            kind: RuntimeErrorKind::Interrupted,
+            token,
+            message: "Interrupted".to_string(),
+            trace: vec![],
+        }
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The innermost frame knows the module the error was raised in.
+        let module = self.trace.first().and_then(|frame| frame.module.as_deref());
+        write!(f, "{}", location(module.map(String::as_str), self.token.line))?;
+
+        write!(f, " Error at '{lexeme}': {message}", lexeme = self.token.lexeme, message = self.message)?;
+
+        // Collapse runs of identical frames, such as deep recursion.
+        let mut frames = self.trace.iter().peekable();
+        while let Some(frame) = frames.next() {
+            write!(f, "\n  {frame}")?;
+
+            let mut repeated = 0;
+            while frames.next_if_eq(&frame).is_some() {
+                repeated += 1;
+            }
+
+            if repeated > 0 {
+                write!(f, "\n  [previous frame repeated {repeated} more times]")?;
+            }
+        }
+
+        Ok(())
+    }
 }
 
 impl rloxError for RuntimeError {
     fn throw(&self) {
-        println!(
-            "[line {line}] Error at '{lexeme}': {message}",
-            line = self.token.line,
-            lexeme = self.token.lexeme,
-            message = self.message
-        );
+        println!("{self}");
 
         unsafe {
             HAD_RUNTIME_ERROR = true;
@@ -86,9 +193,23 @@ impl rloxError for RuntimeError {
 /// Used to return from functions
 #[derive(Debug)]
 pub struct ReturnError {
+    pub keyword: Token,
     pub value: Object,
 }
 
+/// Used to unwind the interpreter out of nested statements.
+#[derive(Debug)]
+pub enum Unwind {
+    Return(Box<ReturnError>),
+    Error(RuntimeError),
+}
+
+impl From<RuntimeError> for Unwind {
+    fn from(error: RuntimeError) -> Self {
+        Unwind::Error(error)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -138,18 +259,29 @@ mod test {
 
     #[test]
     fn runtime_error() {
-        let error = RuntimeError {
-            token: Token::new(
+        let error = RuntimeError::new(
+            Token::new(
                 Type::And,
                 String::from("lex"),
                 Some(Literal::String(String::from("xel"))),
                 12,
             ),
-            message: String::from("test"),
-        };
+            String::from("test"),
+        );
 
         error.throw();
 
         assert!(did_error());
     }
+
+    #[test]
+    fn display_frame() {
+        let frame = Frame { function: String::from("fib()"), line: 3, module: None };
+
+        assert_eq!(format!("{frame}"), "[line 3] in fib()");
+
+        let frame = Frame { function: String::from("fib()"), line: 3, module: Some(Rc::new(String::from("lib.lox"))) };
+
+        assert_eq!(format!("{frame}"), "[lib.lox, line 3] in fib()");
+    }
 }
@@ -8,6 +8,29 @@ pub struct LogicalData {
     pub right: Box<Expr>,
 }
 
+/// Represents a conditional expression such as `a ? b : c`, where only the chosen branch is evaluated.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ConditionalData {
+    pub condition: Box<Expr>,
+    pub question: Token,
+    pub then_branch: Box<Expr>,
+    pub else_branch: Box<Expr>,
+}
+
+/// Represents a literal value, with the token it was written as.
+#[derive(Debug, PartialEq, Clone)]
+pub struct LiteralData {
+    pub value: Literal,
+    /// `None` for a value the parser added that is not in the source.
+    pub token: Option<Token>,
+}
+
+impl From<Literal> for LiteralData {
+    fn from(value: Literal) -> Self {
+        LiteralData { value, token: None }
+    }
+}
+
 /// Represents a unary expression's data in the language.
 #[derive(Debug, PartialEq, Clone)]
 pub struct UnaryData {
@@ -26,6 +49,7 @@ pub struct BinaryData {
 /// Represents a grouping expression's data in the language.
 #[derive(Debug, PartialEq, Clone)]
 pub struct GroupingData {
+    pub paren: Token,
     pub expr: Box<Expr>,
 }
 
@@ -40,24 +64,58 @@ pub struct AssignData {
     pub value: Box<Expr>,
 }
 
+/// Represents a compound assignment such as `a += 1`, or an increment or decrement such as `a++`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct UpdateData {
+    pub name: Token,
+    pub operator: Token,
+    /// The right hand side of a compound assignment, `None` for `++` and `--`.
+    pub value: Option<Box<Expr>>,
+    /// Whether the expression evaluates to the updated value rather than the previous one.
+    pub prefix: bool,
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct CallData {
     pub callee: Box<Expr>,
     pub paren: Token,
     pub arguments: Vec<Expr>,
+    /// The `?.` token of a call that only happens when the callee is not null.
+    pub optional: Option<Token>,
+}
+
+/// Represents a property access, such as a binding of a module.
+#[derive(Debug, PartialEq, Clone)]
+pub struct GetData {
+    pub object: Box<Expr>,
+    pub name: Token,
+}
+
+/// Represents a string with embedded expressions, such as `"total: ${a + b} items"`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct InterpolationData {
+    /// The token of the first part of the string.
+    pub token: Token,
+    /// The parts of the string around the expressions, one more than there are expressions.
+    pub strings: Vec<String>,
+    pub expressions: Vec<Expr>,
 }
 
 /// Represents an expression in the language.
 #[derive(Debug, PartialEq, Clone)]
 pub enum Expr {
-    Literal(Literal), // Literal is defined in token.rs
+    Literal(LiteralData),
     Logical(LogicalData),
+    Conditional(ConditionalData),
     Unary(UnaryData),
     Binary(BinaryData),
     Grouping(GroupingData),
     Variable(VariableData),
     Assign(AssignData),
+    Update(UpdateData),
     Call(CallData),
+    Get(GetData),
+    Interpolation(InterpolationData),
 }
 
 impl Expr {
@@ -68,25 +126,59 @@ impl Expr {
         match self {
             Literal(args) => visitor.visit_literal_expr(args),
             Logical(args) => visitor.visit_logical_expr(args),
+            Conditional(args) => visitor.visit_conditional_expr(args),
             Unary(args) => visitor.visit_unary_expr(args),
             Binary(args) => visitor.visit_binary_expr(args),
             Grouping(args) => visitor.visit_grouping_expr(args),
             Variable(args) => visitor.visit_variable_expr(args),
             Assign(args) => visitor.visit_assign_expr(args),
+            Update(args) => visitor.visit_update_expr(args),
             Call(args) => visitor.visit_call_expr(args),
+            Get(args) => visitor.visit_get_expr(args),
+            Interpolation(args) => visitor.visit_interpolation_expr(args),
         }
     }
 }
 
+impl Expr {
+    /// Returns the first token of the expression, if it has any tokens.
+    pub fn token(&self) -> Option<&Token> {
+        match self {
+            Expr::Literal(data) => data.token.as_ref(),
+            Expr::Logical(data) => data.left.token().or(Some(&data.operator)),
+            Expr::Conditional(data) => data.condition.token().or(Some(&data.question)),
+            Expr::Unary(data) => Some(&data.operator),
+            Expr::Binary(data) => data.left.token().or(Some(&data.operator)),
+            Expr::Grouping(data) => Some(&data.paren),
+            Expr::Variable(data) => Some(&data.name),
+            Expr::Assign(data) => Some(&data.name),
+            Expr::Update(data) if data.prefix && data.value.is_none() => Some(&data.operator),
+            Expr::Update(data) => Some(&data.name),
+            Expr::Call(data) => data.callee.token().or(Some(&data.paren)),
+            Expr::Get(data) => data.object.token().or(Some(&data.name)),
+            Expr::Interpolation(data) => Some(&data.token),
+        }
+    }
+
+    /// Returns the line of the first token of the expression, if it has any tokens.
+    pub fn line(&self) -> Option<usize> {
+        self.token().map(|token| token.line)
+    }
+}
+
 pub trait ExprVisitor<T> {
-    fn visit_literal_expr(&mut self, literal: &Literal) -> T;
+    fn visit_literal_expr(&mut self, literal: &LiteralData) -> T;
     fn visit_logical_expr(&mut self, logical: &LogicalData) -> T;
+    fn visit_conditional_expr(&mut self, conditional: &ConditionalData) -> T;
     fn visit_unary_expr(&mut self, unary: &UnaryData) -> T;
     fn visit_binary_expr(&mut self, binary: &BinaryData) -> T;
     fn visit_grouping_expr(&mut self, grouping: &GroupingData) -> T;
     fn visit_variable_expr(&mut self, variable: &VariableData) -> T;
     fn visit_assign_expr(&mut self, assign: &AssignData) -> T;
+    fn visit_update_expr(&mut self, update: &UpdateData) -> T;
     fn visit_call_expr(&mut self, call: &CallData) -> T;
+    fn visit_get_expr(&mut self, get: &GetData) -> T;
+    fn visit_interpolation_expr(&mut self, interpolation: &InterpolationData) -> T;
 }
 
 #[cfg(test)]
@@ -96,17 +188,17 @@ mod test {
 
     #[test]
     fn create_literal() {
-        let expr = Expr::Literal(Literal::Number(12.0)); 
+        let expr = Expr::Literal(Literal::Number(12.0).into()); 
         let _literal = Literal::Number(12.0);
-        assert!(matches!(expr, Expr::Literal(Literal::Number(_literal))))
+        assert!(matches!(expr, Expr::Literal(LiteralData { value: Literal::Number(_literal), .. })))
     }
 
     #[test]
     fn create_logical() {
         let expr = Expr::Logical(LogicalData {
-            left: Box::new(Expr::Literal(Literal::Bool(true))),
+            left: Box::new(Expr::Literal(Literal::Bool(true).into())),
             operator: Token::new(Type::And, "and".to_string(), None, 1),
-            right: Box::new(Expr::Literal(Literal::Bool(false))),
+            right: Box::new(Expr::Literal(Literal::Bool(false).into())),
         });
 
         let _literal = Literal::Bool(true);
@@ -116,8 +208,8 @@ mod test {
             assert_eq!(data.operator.r#type, Type::And);
             assert_eq!(data.operator.lexeme, "and");
             assert_eq!(data.operator.line, 1);
-            assert!(matches!(*data.left, Expr::Literal(Literal::Bool(_literal))));
-            assert!(matches!(*data.right, Expr::Literal(Literal::Bool(_literal2))));
+            assert!(matches!(*data.left, Expr::Literal(LiteralData { value: Literal::Bool(_literal), .. })));
+            assert!(matches!(*data.right, Expr::Literal(LiteralData { value: Literal::Bool(_literal2), .. })));
         } else {
             panic!("Expected logical expression");
         }
@@ -127,7 +219,7 @@ mod test {
     fn create_unary() {
         let expr = Expr::Unary(UnaryData {
             operator: Token::new(Type::Minus, String::from("-"), None, 1),
-            expr: Box::new(Expr::Literal(Literal::Number(12.0))),
+            expr: Box::new(Expr::Literal(Literal::Number(12.0).into())),
         });
 
         let _literal = Literal::Number(12.0);
@@ -136,7 +228,7 @@ mod test {
             assert_eq!(data.operator.r#type, Type::Minus);
             assert_eq!(data.operator.lexeme, "-");
             assert_eq!(data.operator.line, 1);
-            assert!(matches!(*data.expr, Expr::Literal(Literal::Number(_literal))));
+            assert!(matches!(*data.expr, Expr::Literal(LiteralData { value: Literal::Number(_literal), .. })));
         } else {
             panic!("Expected unary expression");
         }
@@ -145,9 +237,9 @@ mod test {
     #[test]
     fn create_binary() {
         let expr = Expr::Binary(BinaryData {
-            left: Box::new(Expr::Literal(Literal::Number(12.0))),
+            left: Box::new(Expr::Literal(Literal::Number(12.0).into())),
             operator: Token::new(Type::Minus, String::from("-"), None, 1),
-            right: Box::new(Expr::Literal(Literal::Number(12.0))),
+            right: Box::new(Expr::Literal(Literal::Number(12.0).into())),
         });
 
         let _literal = Literal::Number(12.0);
@@ -156,8 +248,8 @@ mod test {
             assert_eq!(data.operator.r#type, Type::Minus);
             assert_eq!(data.operator.lexeme, "-");
             assert_eq!(data.operator.line, 1);
-            assert!(matches!(*data.left, Expr::Literal(Literal::Number(_literal))));
-            assert!(matches!(*data.right, Expr::Literal(Literal::Number(_literal))));
+            assert!(matches!(*data.left, Expr::Literal(LiteralData { value: Literal::Number(_literal), .. })));
+            assert!(matches!(*data.right, Expr::Literal(LiteralData { value: Literal::Number(_literal), .. })));
         } else {
             panic!("Expected binary expression");
         }
@@ -166,13 +258,14 @@ mod test {
     #[test]
     fn create_grouping() {
         let expr = Expr::Grouping(GroupingData {
-            expr: Box::new(Expr::Literal(Literal::Number(12.0))),
+            paren: Token::new(Type::LeftParen, String::from("("), None, 1),
+            expr: Box::new(Expr::Literal(Literal::Number(12.0).into())),
         });
 
         let _literal = Literal::Number(12.0);
 
         if let Expr::Grouping(data) = expr {
-            assert!(matches!(*data.expr, Expr::Literal(Literal::Number(_literal))));
+            assert!(matches!(*data.expr, Expr::Literal(LiteralData { value: Literal::Number(_literal), .. })));
         } else {
             panic!("Expected grouping expression");
         }
@@ -180,8 +273,8 @@ mod test {
 
     #[test]
     fn accept_literal() {
-        let expr_num = Expr::Literal(Literal::Number(12.4));
-        let expr_str = Expr::Literal(Literal::String(String::from("hello")));
+        let expr_num = Expr::Literal(Literal::Number(12.4).into());
+        let expr_str = Expr::Literal(Literal::String(String::from("hello")).into());
 
         let mut ast = ASTPrinter {};
 
@@ -192,9 +285,9 @@ mod test {
     #[test]
     fn accept_logical() {
         let expr = Expr::Logical(LogicalData {
-            left: Box::new(Expr::Literal(Literal::Bool(true))),
+            left: Box::new(Expr::Literal(Literal::Bool(true).into())),
             operator: Token::new(Type::Or, String::from("or"), None, 1),
-            right: Box::new(Expr::Literal(Literal::Bool(false))),
+            right: Box::new(Expr::Literal(Literal::Bool(false).into())),
         });
 
         let mut ast = ASTPrinter {};
@@ -206,7 +299,7 @@ mod test {
     fn accept_unary() {
         let expr = Expr::Unary(UnaryData {
             operator: Token::new(Type::Bang, String::from("!"), None, 1),
-            expr: Box::new(Expr::Literal(Literal::Bool(false))),
+            expr: Box::new(Expr::Literal(Literal::Bool(false).into())),
         });
 
         let mut ast = ASTPrinter {};
@@ -217,9 +310,9 @@ mod test {
     #[test]
     fn accept_binary() {
         let expr = Expr::Binary(BinaryData {
-            left: Box::new(Expr::Literal(Literal::Number(53.6))),
+            left: Box::new(Expr::Literal(Literal::Number(53.6).into())),
             operator: Token::new(Type::Minus, String::from("-"), None, 1),
-            right: Box::new(Expr::Literal(Literal::Number(23.3))),
+            right: Box::new(Expr::Literal(Literal::Number(23.3).into())),
         });
 
         let mut ast = ASTPrinter {};
@@ -230,7 +323,8 @@ mod test {
     #[test]
     fn accept_grouping() {
         let expr = Expr::Grouping(GroupingData {
-            expr: Box::new(Expr::Literal(Literal::Null)),
+            paren: Token::new(Type::LeftParen, String::from("("), None, 1),
+            expr: Box::new(Expr::Literal(Literal::Null.into())),
         });
 
         let mut ast = ASTPrinter {};
@@ -243,10 +337,10 @@ mod test {
         let expr = Expr::Binary(BinaryData {
             left: Box::new(Expr::Unary(UnaryData {
                 operator: Token::new(Type::Bang, String::from("!"), None, 1),
-                expr: Box::new(Expr::Literal(Literal::Bool(false))),
+                expr: Box::new(Expr::Literal(Literal::Bool(false).into())),
             })),
             operator: Token::new(Type::Minus, String::from("-"), None, 1),
-            right: Box::new(Expr::Literal(Literal::Number(23.3))),
+            right: Box::new(Expr::Literal(Literal::Number(23.3).into())),
         });
 
         let mut ast = ASTPrinter {};
@@ -258,10 +352,11 @@ mod test {
     fn accept_nested_grouping() {
         let expr = Expr::Binary(BinaryData {
             left: Box::new(Expr::Grouping(GroupingData {
-                expr: Box::new(Expr::Literal(Literal::Number(53.6))),
+                paren: Token::new(Type::LeftParen, String::from("("), None, 1),
+                expr: Box::new(Expr::Literal(Literal::Number(53.6).into())),
             })),
             operator: Token::new(Type::Minus, String::from("-"), None, 1),
-            right: Box::new(Expr::Literal(Literal::Number(23.3))),
+            right: Box::new(Expr::Literal(Literal::Number(23.3).into())),
         });
 
         let mut ast = ASTPrinter {};
@@ -273,11 +368,13 @@ mod test {
     fn accept_nested_grouping2() {
         let expr = Expr::Binary(BinaryData {
             left: Box::new(Expr::Grouping(GroupingData {
-                expr: Box::new(Expr::Literal(Literal::Number(53.6))),
+                paren: Token::new(Type::LeftParen, String::from("("), None, 1),
+                expr: Box::new(Expr::Literal(Literal::Number(53.6).into())),
             })),
             operator: Token::new(Type::Minus, String::from("-"), None, 1),
             right: Box::new(Expr::Grouping(GroupingData {
-                expr: Box::new(Expr::Literal(Literal::Number(23.3))),
+                paren: Token::new(Type::LeftParen, String::from("("), None, 1),
+                expr: Box::new(Expr::Literal(Literal::Number(23.3).into())),
             })),
         });
 
@@ -290,15 +387,17 @@ mod test {
     fn accept_nested_grouping3() {
         let expr = Expr::Binary(BinaryData {
             left: Box::new(Expr::Grouping(GroupingData {
+                paren: Token::new(Type::LeftParen, String::from("("), None, 1),
                 expr: Box::new(Expr::Binary(BinaryData {
-                    left: Box::new(Expr::Literal(Literal::Number(53.6))),
+                    left: Box::new(Expr::Literal(Literal::Number(53.6).into())),
                     operator: Token::new(Type::Minus, String::from("-"), None, 1),
-                    right: Box::new(Expr::Literal(Literal::Number(23.3))),
+                    right: Box::new(Expr::Literal(Literal::Number(23.3).into())),
                 })),
             })),
             operator: Token::new(Type::Minus, String::from("-"), None, 1),
             right: Box::new(Expr::Grouping(GroupingData {
-                expr: Box::new(Expr::Literal(Literal::Number(23.3))),
+                paren: Token::new(Type::LeftParen, String::from("("), None, 1),
+                expr: Box::new(Expr::Literal(Literal::Number(23.3).into())),
             })),
         });
 
@@ -311,18 +410,20 @@ mod test {
     fn accept_nested_grouping4() {
         let expr = Expr::Binary(BinaryData {
             left: Box::new(Expr::Grouping(GroupingData {
+                paren: Token::new(Type::LeftParen, String::from("("), None, 1),
                 expr: Box::new(Expr::Binary(BinaryData {
-                    left: Box::new(Expr::Literal(Literal::Number(53.6))),
+                    left: Box::new(Expr::Literal(Literal::Number(53.6).into())),
                     operator: Token::new(Type::Minus, String::from("-"), None, 1),
-                    right: Box::new(Expr::Literal(Literal::Number(23.3))),
+                    right: Box::new(Expr::Literal(Literal::Number(23.3).into())),
                 })),
             })),
             operator: Token::new(Type::Minus, String::from("-"), None, 1),
             right: Box::new(Expr::Grouping(GroupingData {
+                paren: Token::new(Type::LeftParen, String::from("("), None, 1),
                 expr: Box::new(Expr::Binary(BinaryData {
-                    left: Box::new(Expr::Literal(Literal::Number(53.6))),
+                    left: Box::new(Expr::Literal(Literal::Number(53.6).into())),
                     operator: Token::new(Type::Minus, String::from("-"), None, 1),
-                    right: Box::new(Expr::Literal(Literal::Number(23.3))),
+                    right: Box::new(Expr::Literal(Literal::Number(23.3).into())),
                 })),
             })),
         });
@@ -350,7 +451,7 @@ mod test {
     fn accept_assign() {
         let expr = Expr::Assign(AssignData {
             name: Token::new(Type::Identifier, String::from("a"), None, 1),
-            value: Box::new(Expr::Literal(Literal::Number(23.3))),
+            value: Box::new(Expr::Literal(Literal::Number(23.3).into())),
         });
 
         let mut ast = ASTPrinter {};
@@ -366,6 +467,7 @@ mod test {
             })),
             paren: Token::new(Type::RightParen, String::from(")"), None, 1),
             arguments: vec![],
+            optional: None,
         });
 
         let mut ast = ASTPrinter {};
@@ -380,7 +482,8 @@ mod test {
                 name: Token::new(Type::Identifier, String::from("a"), None, 1),
             })),
             paren: Token::new(Type::RightParen, String::from(")"), None, 1),
-            arguments: vec![Expr::Literal(Literal::Number(23.3))],
+            arguments: vec![Expr::Literal(Literal::Number(23.3).into())],
+            optional: None,
         });
 
         let mut ast = ASTPrinter {};
@@ -0,0 +1,623 @@
+use std::collections::VecDeque;
+
+use crate::expr::{Expr, LiteralData, ExprVisitor, UnaryData, BinaryData, GroupingData, LogicalData, ConditionalData, AssignData, UpdateData, VariableData, CallData, GetData, InterpolationData};
+use crate::literal::Literal;
+use crate::parser::Parser;
+use crate::scanner::Scanner;
+use crate::stmt::{StmtVisitor, Stmt, WhileData};
+use crate::token::{Token, Type};
+
+const INDENT: &str = "    ";
+
+/// Formats the source code, returns `None` if it could not be scanned or parsed.
+pub fn format(source: &str) -> Option<String> {
+    let mut scanner = Scanner::with_comments(source.to_string());
+    let tokens = scanner.scan_tokens();
+
+    if !scanner.errors.is_empty() {
+        return None;
+    }
+
+    let (comments, tokens): (Vec<Token>, Vec<Token>) = tokens
+        .into_iter()
+        .partition(|token| matches!(token.r#type, Type::Comment | Type::DocComment));
+
+    let mut parser = Parser::new(tokens);
+    let statements = parser.parse();
+
+    if !parser.errors.is_empty() {
+        return None;
+    }
+
+    Some(Formatter::new(comments).format(&statements))
+}
+
+/// Returns the text of a string literal, escaping what cannot be written as is.
+///
+/// Only used for the parts of interpolated strings, string literals are printed as written.
+fn escape(string: &str) -> String {
+    let mut escaped = String::new();
+    let mut chars = string.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => escaped += "\\\\",
+            '"' => escaped += "\\\"",
+            '\n' => escaped += "\\n",
+            '\t' => escaped += "\\t",
+            '\r' => escaped += "\\r",
+            '\0' => escaped += "\\0",
+            '$' if chars.peek() == Some(&'{') => escaped += "\\$",
+            c if c.is_control() => escaped += &format!("\\u{{{:X}}}", c as u32),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+/// Pretty prints statements in the canonical style.
+///
+/// Comments are not part of the AST, they are placed back using the lines of the tokens around
+/// them. A comment on the same line as the end of a statement stays at the end of that line, any
+/// other comment is printed on its own line before the statement that follows it.
+pub struct Formatter {
+    output: String,
+    indent: usize,
+    comments: VecDeque<Token>,
+    // Last source line that has been printed.
+    line: usize,
+    // If nothing has been printed yet in the current block.
+    fresh: bool,
+}
+
+impl Formatter {
+    pub fn new(comments: Vec<Token>) -> Self {
+        Formatter {
+            output: String::new(),
+            indent: 0,
+            comments: VecDeque::from(comments),
+            line: 0,
+            fresh: true,
+        }
+    }
+
+    /// Formats the statements of a program.
+    pub fn format(mut self, statements: &[Stmt]) -> String {
+        for statement in statements {
+            self.statement(statement);
+        }
+
+        self.remaining_comments(usize::MAX);
+
+        self.output
+    }
+
+    /// Records that the token has been printed, up to its last line.
+    fn mark(&mut self, token: &Token) {
+        self.line = self.line.max(token.line + token.lexeme.matches('\n').count());
+    }
+
+    fn write_indent(&mut self) {
+        self.output += &INDENT.repeat(self.indent);
+    }
+
+    /// Keeps a single blank line if the source had one before the given line.
+    fn blank_line(&mut self, line: usize) {
+        if !self.fresh && line > self.line + 1 {
+            self.output.push('\n');
+        }
+    }
+
+    /// Prints the comments before the given line on their own lines.
+    fn remaining_comments(&mut self, line: usize) {
+        while self.comments.front().is_some_and(|comment| comment.line < line) {
+            let comment = self.comments.pop_front().unwrap();
+
+            self.blank_line(comment.line);
+            self.write_indent();
+            self.output += &comment.lexeme;
+            self.output.push('\n');
+
+            self.mark(&comment);
+            self.fresh = false;
+        }
+    }
+
+    /// Prints the comment on the last printed line at the end of the output.
+    fn trailing_comment(&mut self) {
+        if self.comments.front().is_some_and(|comment| comment.line == self.line) {
+            let comment = self.comments.pop_front().unwrap();
+            self.output += " ";
+            self.output += &comment.lexeme;
+            self.mark(&comment);
+        }
+    }
+
+    /// Prints a statement of a block on its own line.
+    fn statement(&mut self, stmt: &Stmt) {
+        // The comments inside a block are printed by its statements, after the opening brace.
+        let line = match stmt {
+            Stmt::Block(data) => Some(data.start.line),
+            _ => stmt.line(),
+        };
+
+        if let Some(line) = line {
+            self.remaining_comments(line);
+            self.blank_line(line);
+        }
+
+        self.write_indent();
+        stmt.accept(self);
+        self.trailing_comment();
+        self.output.push('\n');
+
+        self.fresh = false;
+    }
+
+    /// Prints the body of a control flow statement after its header.
+    fn body(&mut self, stmt: &Stmt) {
+        if let Stmt::Block(data) = stmt {
+            if !Self::is_for(&data.statements) {
+                self.output += " ";
+                self.mark(&data.start);
+                self.block(&data.statements, &data.end);
+                return;
+            }
+        }
+
+        self.output.push('\n');
+        self.indent += 1;
+
+        if let Some(line) = stmt.line() {
+            self.fresh = true;
+            self.remaining_comments(line);
+        }
+
+        self.write_indent();
+        stmt.accept(self);
+        self.trailing_comment();
+
+        self.indent -= 1;
+    }
+
+    /// Prints a block, including the comments before its closing brace.
+    fn block(&mut self, statements: &[Stmt], end: &Token) {
+        let has_comments = self.comments.front().is_some_and(|comment| comment.line < end.line);
+
+        if statements.is_empty() && !has_comments {
+            self.output += "{}";
+            self.mark(end);
+            return;
+        }
+
+        self.output += "{";
+        self.trailing_comment();
+        self.output.push('\n');
+
+        self.indent += 1;
+        self.fresh = true;
+
+        for statement in statements {
+            self.statement(statement);
+        }
+
+        self.remaining_comments(end.line);
+        self.indent -= 1;
+
+        self.write_indent();
+        self.output += "}";
+        self.mark(end);
+    }
+
+    /// Returns if the statements are a for loop desugared by the parser.
+    fn is_for(statements: &[Stmt]) -> bool {
+        matches!(statements.last(), Some(Stmt::While(data)) if data.keyword.r#type == Type::For)
+    }
+
+    /// Prints a desugared for loop in its original form.
+    fn for_loop(&mut self, initializer: Option<&Stmt>, data: &WhileData) {
+        self.mark(&data.keyword);
+        self.output += "for (";
+
+        match initializer {
+            Some(initializer) => initializer.accept(self),
+            None => self.output += ";",
+        }
+
+        // The parser adds a `true` condition without a token when there is none.
+        match &data.condition {
+            Expr::Literal(LiteralData { token: None, .. }) => self.output += ";",
+            condition => {
+                let condition = condition.accept(self);
+                self.output += &format!(" {condition};");
+            },
+        }
+
+        let Stmt::Block(body) = data.body.as_ref() else { unreachable!() };
+
+        if let Some(Stmt::Expression(increment)) = body.statements.get(1) {
+            let increment = increment.expr.accept(self);
+            self.output += &format!(" {increment}");
+        }
+
+        self.output += ")";
+
+        self.body(&body.statements[0]);
+    }
+}
+
+impl ExprVisitor<String> for Formatter {
+    fn visit_literal_expr(&mut self, literal: &LiteralData) -> String {
+        match (&literal.value, &literal.token) {
+            // Numbers and strings are kept as written, such as in another base or as a raw string.
+            (Literal::Number(_) | Literal::String(_), Some(token)) => {
+                self.mark(token);
+                token.lexeme.clone()
+            },
+            (Literal::String(string), None) => format!("\"{}\"", escape(string)),
+            (value, _) => value.to_string(),
+        }
+    }
+
+    fn visit_logical_expr(&mut self, logical: &LogicalData) -> String {
+        let left = logical.left.accept(self);
+        self.mark(&logical.operator);
+        let right = logical.right.accept(self);
+
+        format!("{left} {} {right}", logical.operator.lexeme)
+    }
+
+    fn visit_conditional_expr(&mut self, conditional: &ConditionalData) -> String {
+        let condition = conditional.condition.accept(self);
+        self.mark(&conditional.question);
+        let then_branch = conditional.then_branch.accept(self);
+        let else_branch = conditional.else_branch.accept(self);
+
+        format!("{condition} ? {then_branch} : {else_branch}")
+    }
+
+    fn visit_unary_expr(&mut self, unary: &UnaryData) -> String {
+        self.mark(&unary.operator);
+        let expr = unary.expr.accept(self);
+
+        // `- -a` would otherwise be scanned back as a decrement.
+        match unary.operator.r#type == Type::Minus && expr.starts_with('-') {
+            true => format!("{} {expr}", unary.operator.lexeme),
+            false => format!("{}{expr}", unary.operator.lexeme),
+        }
+    }
+
+    fn visit_binary_expr(&mut self, binary: &BinaryData) -> String {
+        let left = binary.left.accept(self);
+        self.mark(&binary.operator);
+        let right = binary.right.accept(self);
+
+        match binary.operator.r#type {
+            Type::Comma => format!("{left}, {right}"),
+            _ => format!("{left} {} {right}", binary.operator.lexeme),
+        }
+    }
+
+    fn visit_grouping_expr(&mut self, grouping: &GroupingData) -> String {
+        format!("({})", grouping.expr.accept(self))
+    }
+
+    fn visit_variable_expr(&mut self, variable: &VariableData) -> String {
+        self.mark(&variable.name);
+        variable.name.lexeme.clone()
+    }
+
+    fn visit_assign_expr(&mut self, assign: &AssignData) -> String {
+        self.mark(&assign.name);
+        format!("{} = {}", assign.name.lexeme, assign.value.accept(self))
+    }
+
+    fn visit_update_expr(&mut self, update: &UpdateData) -> String {
+        let (name, operator) = (&update.name.lexeme, &update.operator.lexeme);
+
+        match (&update.value, update.prefix) {
+            (Some(value), _) => {
+                self.mark(&update.name);
+                format!("{name} {operator} {}", value.accept(self))
+            },
+            (None, true) => {
+                self.mark(&update.operator);
+                format!("{operator}{name}")
+            },
+            (None, false) => {
+                self.mark(&update.name);
+                format!("{name}{operator}")
+            },
+        }
+    }
+
+    fn visit_call_expr(&mut self, call: &CallData) -> String {
+        let callee = call.callee.accept(self);
+        let arguments = call.arguments
+            .iter()
+            .map(|argument| argument.accept(self))
+            .collect::<Vec<String>>()
+            .join(", ");
+        self.mark(&call.paren);
+
+        match call.optional {
+            Some(_) => format!("{callee}?.({arguments})"),
+            None => format!("{callee}({arguments})"),
+        }
+    }
+
+    fn visit_get_expr(&mut self, get: &GetData) -> String {
+        let object = get.object.accept(self);
+        self.mark(&get.name);
+
+        format!("{object}.{}", get.name.lexeme)
+    }
+
+    fn visit_interpolation_expr(&mut self, interpolation: &InterpolationData) -> String {
+        self.mark(&interpolation.token);
+        let mut string = String::from("\"");
+
+        for (index, part) in interpolation.strings.iter().enumerate() {
+            string += &escape(part);
+            if let Some(expr) = interpolation.expressions.get(index) {
+                string += &format!("${{{}}}", expr.accept(self));
+            }
+        }
+
+        string.push('"');
+        string
+    }
+}
+
+impl StmtVisitor<()> for Formatter {
+    fn visit_expression_stmt(&mut self, stmt: &Stmt) {
+        let Stmt::Expression(data) = stmt else { unreachable!() };
+        let expr = data.expr.accept(self);
+        self.output += &format!("{expr};");
+    }
+
+    fn visit_function_stmt(&mut self, stmt: &Stmt) {
+        let Stmt::Function(data) = stmt else { unreachable!() };
+        self.mark(&data.name);
+
+        let params = data.params
+            .iter()
+            .map(|param| param.lexeme.clone())
+            .collect::<Vec<String>>()
+            .join(", ");
+
+        self.output += &format!("fun {}({params}) ", data.name.lexeme);
+        self.block(&data.body, &data.end);
+    }
+
+    fn visit_if_stmt(&mut self, stmt: &Stmt) {
+        let Stmt::If(data) = stmt else { unreachable!() };
+        self.mark(&data.keyword);
+
+        let condition = data.condition.accept(self);
+        self.output += &format!("if ({condition})");
+        self.body(&data.then_branch);
+
+        let Some(else_branch) = &data.else_branch else { return };
+
+        if matches!(data.then_branch.as_ref(), Stmt::Block(_)) {
+            self.output += " else";
+        } else {
+            self.output.push('\n');
+            self.write_indent();
+            self.output += "else";
+        }
+
+        // Keep else if chains flat.
+        if let Stmt::If(_) = else_branch.as_ref() {
+            self.output += " ";
+            else_branch.accept(self);
+        } else {
+            self.body(else_branch);
+        }
+    }
+
+    fn visit_print_stmt(&mut self, stmt: &Stmt) {
+        let Stmt::Print(data) = stmt else { unreachable!() };
+        self.mark(&data.keyword);
+        let expr = data.expr.accept(self);
+        self.output += &format!("print {expr};");
+    }
+
+    fn visit_return_stmt(&mut self, stmt: &Stmt) {
+        let Stmt::Return(data) = stmt else { unreachable!() };
+        self.mark(&data.keyword);
+
+        match &data.value {
+            Some(value) => {
+                let value = value.accept(self);
+                self.output += &format!("return {value};");
+            },
+            None => self.output += "return;",
+        }
+    }
+
+    fn visit_var_stmt(&mut self, stmt: &Stmt) {
+        let Stmt::Var(data) = stmt else { unreachable!() };
+        self.mark(&data.name);
+
+        match &data.initializer {
+            Some(value) => {
+                let value = value.accept(self);
+                self.output += &format!("var {} = {value};", data.name.lexeme);
+            },
+            None => self.output += &format!("var {};", data.name.lexeme),
+        }
+    }
+
+    fn visit_while_stmt(&mut self, stmt: &Stmt) {
+        let Stmt::While(data) = stmt else { unreachable!() };
+
+        if data.keyword.r#type == Type::For {
+            return self.for_loop(None, data);
+        }
+
+        self.mark(&data.keyword);
+        let condition = data.condition.accept(self);
+        self.output += &format!("while ({condition})");
+        self.body(&data.body);
+    }
+
+    fn visit_block_stmt(&mut self, stmt: &Stmt) {
+        let Stmt::Block(data) = stmt else { unreachable!() };
+
+        if Self::is_for(&data.statements) {
+            let Some(Stmt::While(loop_data)) = data.statements.last() else { unreachable!() };
+            return self.for_loop(data.statements.first().filter(|_| data.statements.len() == 2), loop_data);
+        }
+
+        self.mark(&data.start);
+        self.block(&data.statements, &data.end);
+    }
+
+    fn visit_import_stmt(&mut self, stmt: &Stmt) {
+        let Stmt::Import(data) = stmt else { unreachable!() };
+        self.mark(&data.name);
+        self.output += &format!("import {} as {};", data.path.lexeme, data.name.lexeme);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn assert_formats(source: &str, expected: &str) {
+        let formatted = format(source).expect("source to be valid");
+        assert_eq!(formatted, expected);
+        assert_eq!(format(&formatted).unwrap(), expected, "formatting is not idempotent");
+    }
+
+    #[test]
+    fn format_statements() {
+        assert_formats(
+            "var   a=1;print a+ 2*(3-a) ;a=-a;",
+            "var a = 1;\nprint a + 2 * (3 - a);\na = -a;\n",
+        );
+    }
+
+    #[test]
+    fn format_function() {
+        assert_formats(
+            "fun add(a,b){return a+b;}\nfun nothing() {}\nprint add(1,\n2);",
+            "fun add(a, b) {\n    return a + b;\n}\nfun nothing() {}\nprint add(1, 2);\n",
+        );
+    }
+
+    #[test]
+    fn format_import() {
+        assert_formats(
+            "import   \"lib/math.lox\"as math;print math . square(2);",
+            "import \"lib/math.lox\" as math;\nprint math.square(2);\n",
+        );
+    }
+
+    #[test]
+    fn format_interpolation() {
+        assert_formats(
+            "print \"a ${ b+1 } ${\"c ${d}\"}\";",
+            "print \"a ${b + 1} ${\"c ${d}\"}\";\n",
+        );
+    }
+
+    #[test]
+    fn format_conditional_and_comma() {
+        assert_formats(
+            "print a?b:c?d:e;f(a,(b,c));for(i=0,j=1;i<j;i++,j--)print i;",
+            "print a ? b : c ? d : e;\nf(a, (b, c));\nfor (i = 0, j = 1; i < j; i++, j--)\n    print i;\n",
+        );
+    }
+
+    #[test]
+    fn format_null_operators() {
+        assert_formats("print a??b;f ?. (1,2);", "print a ?? b;\nf?.(1, 2);\n");
+    }
+
+    #[test]
+    fn format_update() {
+        assert_formats(
+            "a+=1;b --;++ c;print - -d;print -(-d);print - --d;for(var i=0;i<3;i++)print i;",
+            "a += 1;\nb--;\n++c;\nprint - -d;\nprint -(-d);\nprint - --d;\nfor (var i = 0; i < 3; i++)\n    print i;\n",
+        );
+    }
+
+    #[test]
+    fn format_comment_inside_block() {
+        assert_formats(
+            "print 0;\n{ // first\n    // inside\n    print 1;\n}\n",
+            "print 0;\n{ // first\n    // inside\n    print 1;\n}\n",
+        );
+        assert_formats("{\n    // inside\n    print 1;\n}\n", "{\n    // inside\n    print 1;\n}\n");
+    }
+
+    #[test]
+    fn format_for_without_condition() {
+        assert_formats("for(;;)print 1;", "for (;;)\n    print 1;\n");
+        assert_formats("for (var i = 0;; i++) {}", "for (var i = 0;; i++) {}\n");
+        assert_formats("for (; true;) {}", "for (; true;) {}\n");
+    }
+
+    #[test]
+    fn format_number_literals() {
+        assert_formats(
+            "print 0xFF_FF+0b1010*0o17;print 1_000.5e-3-6.02E23;",
+            "print 0xFF_FF + 0b1010 * 0o17;\nprint 1_000.5e-3 - 6.02E23;\n",
+        );
+    }
+
+    #[test]
+    fn format_escapes() {
+        assert_formats("print \"a\\\"b\\u{41}\";", "print \"a\\\"b\\u{41}\";\n");
+        assert_formats("print \"${x}\\t\\${y}\";", "print \"${x}\\t\\${y}\";\n");
+    }
+
+    #[test]
+    fn format_raw_and_multiline_strings() {
+        assert_formats(
+            "print r\"a\\b\"; print \"\"\"\n    \"x\"\n    \\${y}\n    \"\"\";\nprint r\"\"\"\n  \\n\n  \"\"\"; // raw\nprint 1;",
+            "print r\"a\\b\";\nprint \"\"\"\n    \"x\"\n    \\${y}\n    \"\"\";\nprint r\"\"\"\n  \\n\n  \"\"\"; // raw\nprint 1;\n",
+        );
+    }
+
+    #[test]
+    fn format_if_else() {
+        assert_formats(
+            "if (a) { print 1; } else if (b) print 2; else { print 3; }",
+            "if (a) {\n    print 1;\n} else if (b)\n    print 2;\nelse {\n    print 3;\n}\n",
+        );
+    }
+
+    #[test]
+    fn format_for() {
+        assert_formats(
+            "for (var i=0;i<10;i=i+1) print i;\nfor(;;){}\nfor (i = 0; i < 2;) { print i; }",
+            "for (var i = 0; i < 10; i = i + 1)\n    print i;\nfor (;;) {}\nfor (i = 0; i < 2;) {\n    print i;\n}\n",
+        );
+    }
+
+    #[test]
+    fn format_comments() {
+        assert_formats(
+            "// Header\n\n\n\nvar a = 1; // One\nfun f() { // Start\n  // Body\n  print a;\n\n  // End\n}\n// Footer",
+            "// Header\n\nvar a = 1; // One\nfun f() { // Start\n    // Body\n    print a;\n\n    // End\n}\n// Footer\n",
+        );
+    }
+
+    #[test]
+    fn format_invalid() {
+        assert!(format("var = ;").is_none());
+    }
+
+    #[test]
+    fn format_doc_and_block_comments() {
+        assert_formats(
+            "/// Doubles.\nfun   double(x) { /* twice */ return x*2; }\n/* a\n   b */\nvar a;",
+            "/// Doubles.\nfun double(x) { /* twice */\n    return x * 2;\n}\n/* a\n   b */\nvar a;\n",
+        );
+    }
+}
@@ -6,7 +6,7 @@ use std::rc::Rc;
 use crate::environment::Environment;
 use crate::interpreter::Interpreter;
 use crate::object::{Object, Callable};
-use crate::error::RuntimeError;
+use crate::error::{RuntimeError, Unwind};
 use crate::stmt::Stmt;
 use crate::token::{Token, Type};
 use crate::literal::Literal;
@@ -17,16 +17,21 @@ pub struct Function {
     pub params: Vec<Token>,
     pub body: Vec<Stmt>,
     pub closure: Rc<RefCell<Environment>>,
+    pub doc: Option<String>,
+    /// Name of the module the function was declared in, `None` for the script itself.
+    pub module: Option<Rc<String>>,
 }
 
 impl Function {
-    pub fn new(stmt: Stmt, closure: Rc<RefCell<Environment>>) -> Self {
+    pub fn new(stmt: Stmt, closure: Rc<RefCell<Environment>>, module: Option<Rc<String>>) -> Self {
         match stmt {
             Stmt::Function(data) => Function {
                 name: data.name,
                 params: data.params,
                 body: data.body,
                 closure,
+                doc: data.doc,
+                module,
             },
             _ => panic!("Expected function statement"),
         }
@@ -45,7 +50,8 @@ impl Callable for Function {
 
         match interpreter.execute_block(&self.body, environment) {
             Ok(_) => Ok(Object::from(Literal::Null)),
-            Err(err) => Ok(err.value),
+            Err(Unwind::Return(ret)) => Ok(ret.value),
+            Err(Unwind::Error(error)) => Err(error),
         }
     }
 
@@ -63,6 +69,7 @@ impl Display for Function {
 #[derive(Clone)]
 pub struct NativeFunction {
     pub name: Token,
+    pub arity: usize,
     pub function: fn(&mut Interpreter, Vec<Object>) -> Result<Object, RuntimeError>,
 }
 
@@ -72,7 +79,7 @@ impl Callable for NativeFunction {
     }
 
     fn arity(&self) -> usize {
-        0
+        self.arity
     }
 }
 
@@ -81,16 +88,18 @@ impl NativeFunction {
         vec![
             NativeFunction {
                 name: Token::new(Type::Identifier, "clock".to_owned(), None, 0),
+                arity: 0,
                 function: |_, _| {
                     let now = std::time::SystemTime::now()
                         .duration_since(std::time::UNIX_EPOCH)
                         .unwrap()
                         .as_millis();
-                    Ok(Object::from(now as f32))
+                    Ok(Object::from(now as f64))
                 },
             },
             NativeFunction {
                 name: Token::new(Type::Identifier, "input".to_owned(), None, 0),
+                arity: 0,
                 function: |_, _| {
                     let mut input = String::new();
                     std::io::stdin().read_line(&mut input).unwrap();
@@ -98,6 +107,20 @@ impl NativeFunction {
                     Ok(Object::from(input))
                 },
             },
+            NativeFunction {
+                name: Token::new(Type::Identifier, "doc".to_owned(), None, 0),
+                arity: 1,
+                // The doc comment of a function, null for anything else.
+                function: |_, arguments| {
+                    match &arguments[0] {
+                        Object::Function(function) => match &function.doc {
+                            Some(doc) => Ok(Object::from(doc.clone())),
+                            None => Ok(Object::from(Literal::Null)),
+                        },
+                        _ => Ok(Object::from(Literal::Null)),
+                    }
+                },
+            },
         ]
     }
 }
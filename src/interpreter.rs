@@ -1,19 +1,126 @@
 use std::rc::Rc;
 use std::cell::RefCell;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::iter;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 
 use crate::environment::Environment;
-use crate::error::{rloxError, RuntimeError, self, ReturnError};
+use crate::error::{RuntimeError, ReturnError, Unwind, Frame};
 use crate::expr::{self, Expr, ExprVisitor};
 use crate::function::{NativeFunction, Function};
-use crate::object::{Object, Callable};
+use crate::object::{Object, Callable, Module};
+use crate::parser::Parser;
+use crate::scanner::Scanner;
 use crate::stmt::{Stmt, StmtVisitor};
-use crate::token::Type;
+use crate::token::{Token, Type};
 use crate::literal::Literal;
 
+/// Default maximum number of nested calls before raising a stack overflow.
+pub const MAX_CALL_DEPTH: usize = 256;
+
+/// Limits the amount of work a single call to `Interpreter::interpret` may do.
+///
+/// Every loop iteration and every function call consumes one unit of fuel.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Limits {
+    pub fuel: Option<u64>,
+    pub timeout: Option<Duration>,
+}
+
+/// Cancels the evaluation of an interpreter, possibly from another thread.
+#[derive(Debug, Default, Clone)]
+pub struct CancelHandle {
+    cancelled: Arc<AtomicBool>,
+    running: Arc<AtomicBool>,
+}
+
+impl CancelHandle {
+    /// Requests the running evaluation to stop with an "Interrupted" error.
+    ///
+    /// Returns false if the interpreter was idle, there is nothing to cancel then.
+    pub fn cancel(&self) -> bool {
+        let running = self.running.load(Ordering::SeqCst);
+        if running {
+            self.cancelled.store(true, Ordering::SeqCst);
+        }
+        running
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    fn reset(&self) {
+        self.cancelled.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Observes the execution of an interpreter.
+///
+/// Hooks are taken out of the interpreter while they run, so they can evaluate code with it
+/// without being called again.
+pub trait Hook {
+    /// Called before every statement is executed, an error stops the execution.
+    fn statement(&mut self, _interpreter: &mut Interpreter, _stmt: &Stmt) -> Result<(), RuntimeError> {
+        Ok(())
+    }
+
+    /// Called after a frame is pushed for a function call, before its body runs.
+    fn enter(&mut self, _interpreter: &Interpreter, _function: &str, _arguments: &[Object]) {}
+
+    /// Called after a function call returns or fails, before its frame is popped.
+    ///
+    /// The value is missing when the call failed.
+    fn leave(&mut self, _interpreter: &Interpreter, _function: &str, _value: Option<&Object>) {}
+
+    /// Called after a variable or function is declared in the current environment.
+    fn define(&mut self, _interpreter: &Interpreter, _name: &Token, _value: &Object) {}
+
+    /// Called after a value is assigned to an existing variable.
+    fn assign(&mut self, _interpreter: &Interpreter, _name: &Token, _value: &Object) {}
+
+    /// Called when a condition decides the way of an `if`, `while`, `and` or `or`, with its keyword.
+    ///
+    /// The branch is taken when the body or the right operand runs.
+    fn branch(&mut self, _interpreter: &Interpreter, _keyword: &Token, _taken: bool) {}
+
+    /// Called before an imported module runs for the first time, with its name, source and statements.
+    fn import(&mut self, _interpreter: &Interpreter, _module: &str, _source: &str, _statements: &[Stmt]) {}
+
+    /// Called when a call to `Interpreter::interpret` is done, even if it failed.
+    fn finish(&mut self, _interpreter: &Interpreter) {}
+}
+
 pub struct Interpreter {
     // Interior mutability with multiple owners
     environment: Rc<RefCell<Environment>>,
     globals: Rc<RefCell<Environment>>,
+    // Active calls, outermost first. Each frame holds the callee and the line it was called from.
+    frames: Vec<Frame>,
+    // The environment each active call was made from, parallel to `frames`.
+    callers: Vec<Rc<RefCell<Environment>>>,
+    max_call_depth: usize,
+    limits: Limits,
+    // Remaining budget of the current run, armed from `limits` by `interpret`.
+    fuel: Option<u64>,
+    deadline: Option<Instant>,
+    cancel: CancelHandle,
+    hooks: Vec<Box<dyn Hook>>,
+    output: Box<dyn Write>,
+    // Directories searched for modules that are not found next to the importing script.
+    search_path: Vec<PathBuf>,
+    // Modules that have been run, by canonical path.
+    modules: HashMap<PathBuf, Object>,
+    // Scripts being run, outermost first, by canonical path and name.
+    importing: Vec<(PathBuf, String)>,
+    // Name of the module whose code is running, `None` while running the script itself.
+    module: Option<Rc<String>>,
 }
 
 impl Interpreter {
@@ -24,41 +131,368 @@ impl Interpreter {
             globals.borrow_mut().define(&native.name.lexeme, Object::from(native.clone()));
         });
 
-        Interpreter { environment: Rc::clone(&globals), globals: Rc::clone(&globals) }
+        Interpreter {
+            environment: Rc::clone(&globals),
+            globals: Rc::clone(&globals),
+            frames: vec![],
+            callers: vec![],
+            max_call_depth: MAX_CALL_DEPTH,
+            limits: Limits::default(),
+            fuel: None,
+            deadline: None,
+            cancel: CancelHandle::default(),
+            hooks: vec![],
+            output: Box::new(io::stdout()),
+            search_path: vec![],
+            modules: HashMap::new(),
+            importing: vec![],
+            module: None,
+        }
+    }
+
+    /// Returns a handle that can cancel the running evaluation.
+    ///
+    /// Cancellations requested while nothing is being evaluated are discarded.
+    pub fn cancel_handle(&self) -> CancelHandle {
+        self.cancel.clone()
+    }
+
+    /// Sets the execution limits applied to every following call to `interpret`.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    /// Sets the maximum number of nested calls before raising a stack overflow.
+    pub fn set_max_call_depth(&mut self, depth: usize) {
+        self.max_call_depth = depth;
+    }
+
+    /// Sets where `print` statements write to, standard output by default.
+    pub fn set_output(&mut self, output: Box<dyn Write>) {
+        self.output = output;
     }
 
-    pub fn interpret(&mut self, statements: &Vec<Stmt>) {
+    /// Sets the path of the script being run, which its imports are resolved relative to.
+    ///
+    /// Without it, imports are resolved relative to the working directory.
+    pub fn set_script(&mut self, path: &Path) {
+        let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        let name = canonical.file_name().map_or_else(|| path.display().to_string(), |name| name.to_string_lossy().to_string());
+        self.importing = vec![(canonical, name)];
+    }
+
+    /// Sets the directories searched for modules not found next to the importing script.
+    pub fn set_search_path(&mut self, search_path: Vec<PathBuf>) {
+        self.search_path = search_path;
+    }
+
+    /// Adds a hook that is called during every following evaluation.
+    pub fn add_hook(&mut self, hook: Box<dyn Hook>) {
+        self.hooks.push(hook);
+    }
+
+    /// Executes the statements, stopping at the first uncaught runtime error.
+    pub fn interpret(&mut self, statements: &[Stmt]) -> Result<(), RuntimeError> {
+        self.fuel = self.limits.fuel;
+        self.deadline = self.limits.timeout.map(|timeout| Instant::now() + timeout);
+        self.cancel.reset();
+
+        self.cancel.running.store(true, Ordering::SeqCst);
+        let result = self.run(statements);
+        self.cancel.running.store(false, Ordering::SeqCst);
+        self.notify(|hook, interpreter| hook.finish(interpreter));
+
+        result
+    }
+
+    fn run(&mut self, statements: &[Stmt]) -> Result<(), RuntimeError> {
         for statement in statements {
-            self.execute(statement).unwrap_or_else(|err| {
-                dbg!(err);
+            let result = self.execute(statement).map_err(|unwind| match unwind {
+                // The parser rejects these, but statements can be built without it.
+                Unwind::Return(ret) => {
+                    RuntimeError::new(ret.keyword, "Can't return from top-level code.".to_string())
+                },
+                Unwind::Error(error) => error,
             });
+
+            match result {
+                Ok(_) => (),
+                Err(mut error) => {
+                    if error.trace.is_empty() {
+                        error.trace = self.backtrace(error.token.line);
+                    }
+
+                    self.frames.clear();
+                    self.callers.clear();
+                    self.module = None;
+                    self.environment = Rc::clone(&self.globals);
+
+                    return Err(error);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the environment each active call is executing in, innermost first.
+    ///
+    /// The first environment is the innermost scope, the last one is the scope of the script.
+    pub fn frame_environments(&self) -> Vec<Rc<RefCell<Environment>>> {
+        let mut environments = vec![Rc::clone(&self.environment)];
+        environments.extend(self.callers.iter().rev().cloned());
+        environments
+    }
+
+    /// Returns the active calls, outermost first.
+    pub fn call_stack(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    /// Returns the name of the imported module running, or `None` while the script itself runs.
+    pub fn module(&self) -> Option<&str> {
+        self.module.as_deref().map(String::as_str)
+    }
+
+    /// Returns the stack trace for an error raised at the given line, innermost frame first.
+    pub fn backtrace(&self, line: usize) -> Vec<Frame> {
+        let mut trace = vec![];
+        let mut line = line;
+        let mut module = self.module.clone();
+
+        for frame in self.frames.iter().rev() {
+            // Modules are run in a frame named `<module name>`, which can't be a function name.
+            let function = match frame.function.starts_with('<') {
+                true => frame.function.clone(),
+                false => format!("{}()", frame.function),
+            };
+
+            trace.push(Frame { function, line, module });
+            line = frame.line;
+            module = frame.module.clone();
+        }
+
+        trace.push(Frame { function: "script".to_string(), line, module });
+
+        trace
+    }
+
+    /// Consumes a unit of fuel and checks the deadline and the cancel handle.
+    fn tick(&mut self, token: &Token) -> Result<(), RuntimeError> {
+        if self.cancel.is_cancelled() {
+            return Err(RuntimeError::interrupted(token.clone()));
+        }
+
+        if let Some(fuel) = self.fuel.as_mut() {
+            if *fuel == 0 {
+                return Err(RuntimeError::limit_exceeded(token.clone(), "Step limit exceeded".to_string()));
+            }
+            *fuel -= 1;
+        }
+
+        if let Some(deadline) = self.deadline {
+            if Instant::now() >= deadline {
+                return Err(RuntimeError::limit_exceeded(token.clone(), "Time limit exceeded".to_string()));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the canonical path of the module, looking next to the importing script first.
+    fn resolve(&self, name: &str) -> Option<PathBuf> {
+        let base = self.importing
+            .last()
+            .and_then(|(path, _)| path.parent())
+            .map_or_else(PathBuf::new, Path::to_path_buf);
+
+        iter::once(base)
+            .chain(self.search_path.iter().cloned())
+            .map(|directory| directory.join(name))
+            .find(|path| path.is_file())
+            .and_then(|path| path.canonicalize().ok())
+    }
+
+    /// Returns the name of the module, its path relative to the directory of the script.
+    ///
+    /// Without a script, the path is relative to the working directory.
+    fn module_name(&self, canonical: &Path) -> String {
+        let base = match self.importing.first() {
+            Some((script, _)) => script.parent().map(Path::to_path_buf),
+            None => env::current_dir().and_then(|directory| directory.canonicalize()).ok(),
+        };
+
+        let Some(base) = base else { return canonical.display().to_string() };
+
+        // Modules outside of the directory are reached by going up from it.
+        let common = canonical.components().zip(base.components()).take_while(|(a, b)| a == b).count();
+        if common == 0 {
+            return canonical.display().to_string();
+        }
+
+        let mut relative: PathBuf = base.components().skip(common).map(|_| Component::ParentDir).collect();
+        relative.extend(canonical.components().skip(common));
+        relative.display().to_string()
+    }
+
+    /// Runs the module the first time it is imported and returns it.
+    fn import(&mut self, path: &Token) -> Result<Object, Unwind> {
+        let Some(Literal::String(written)) = &path.literal else { unreachable!() };
+
+        let Some(canonical) = self.resolve(written) else {
+            return Err(RuntimeError::new(path.clone(), format!("Could not find module '{written}'")).into());
+        };
+
+        if let Some(module) = self.modules.get(&canonical) {
+            return Ok(module.clone());
+        }
+
+        let name = self.module_name(&canonical);
+
+        if let Some(start) = self.importing.iter().position(|(importing, _)| *importing == canonical) {
+            let mut chain: Vec<&str> = self.importing[start..].iter().map(|(_, name)| name.as_str()).collect();
+            chain.push(&name);
+
+            return Err(RuntimeError::new(path.clone(), format!("Import cycle: {}", chain.join(" -> "))).into());
+        }
+
+        let source = fs::read_to_string(&canonical).map_err(|error| {
+            RuntimeError::new(path.clone(), format!("Could not read module '{name}': {error}"))
+        })?;
+
+        let mut scanner = Scanner::new(source.clone()).quiet();
+        let tokens = scanner.scan_tokens();
+        let mut parser = Parser::new(tokens).quiet();
+        let statements = parser.parse();
+
+        if !scanner.errors.is_empty() || !parser.errors.is_empty() {
+            let errors: Vec<String> = scanner.errors
+                .iter()
+                .map(|error| format!("\n  {error}"))
+                .chain(parser.errors.iter().map(|error| format!("\n  {error}")))
+                .collect();
+
+            return Err(RuntimeError::new(path.clone(), format!("Could not compile module '{name}':{}", errors.concat())).into());
+        }
+
+        // Modules see the globals but not the scope of the importing script.
+        let environment = Rc::new(RefCell::new(Environment::new(Some(Rc::clone(&self.globals)))));
+
+        self.importing.push((canonical.clone(), name.clone()));
+        self.frames.push(Frame { function: format!("<module {name}>"), line: path.line, module: self.module.clone() });
+        self.callers.push(Rc::clone(&self.environment));
+        let previous = self.module.replace(Rc::new(name.clone()));
+
+        self.notify(|hook, interpreter| hook.import(interpreter, &name, &source, &statements));
+
+        let result = self.execute_block(&statements, Rc::clone(&environment)).map_err(|mut unwind| {
+            // Capture the trace while the frame of the module is active, like calls do.
+            if let Unwind::Error(error) = &mut unwind {
+                if error.trace.is_empty() {
+                    error.trace = self.backtrace(error.token.line);
+                }
+            }
+            unwind
+        });
+
+        self.module = previous;
+        self.callers.pop();
+        self.frames.pop();
+        self.importing.pop();
+
+        result?;
+
+        let module = Object::Module(Rc::new(Module { name, environment }));
+        self.modules.insert(canonical, module.clone());
+
+        Ok(module)
+    }
+
+    /// Calls every hook, taking them out of the interpreter while they run.
+    fn notify(&mut self, mut call: impl FnMut(&mut dyn Hook, &Interpreter)) {
+        if !self.hooks.is_empty() {
+            let mut hooks = std::mem::take(&mut self.hooks);
+            hooks.iter_mut().for_each(|hook| call(hook.as_mut(), self));
+            self.hooks = hooks;
         }
     }
 
-    fn execute(&mut self, stmt: &Stmt) -> Result<(), ReturnError> {
+    fn execute(&mut self, stmt: &Stmt) -> Result<(), Unwind> {
+        if !self.hooks.is_empty() {
+            let mut hooks = std::mem::take(&mut self.hooks);
+            let result = hooks.iter_mut().try_for_each(|hook| hook.statement(self, stmt));
+            self.hooks = hooks;
+            result?;
+        }
+
         stmt.accept(self)
     }
 
     pub fn execute_block(
         &mut self,
-        statements: &Vec<Stmt>,
+        statements: &[Stmt],
         environment: Rc<RefCell<Environment>>
-    ) -> Result<(), ReturnError> {
+    ) -> Result<(), Unwind> {
         let previous = self.environment.clone();
         self.environment = environment;
 
-        for statement in statements {
-            self.execute(statement)?;
-        }
+        // Restore the environment even if one of the statements unwinds.
+        let result = statements.iter().try_for_each(|statement| self.execute(statement));
 
         self.environment = previous;
 
-        Ok(())
+        result
     }
 
-    fn evaluate(&mut self, expr: &Expr) -> Object {
+    pub fn evaluate(&mut self, expr: &Expr) -> Result<Object, RuntimeError> {
         expr.accept(self)
     }
+
+    /// Calls the callable while keeping track of it in the call stack.
+    fn call(
+        &mut self,
+        callee: &impl Callable,
+        name: &str,
+        module: Option<Rc<String>>,
+        call: &expr::CallData,
+        arguments: Vec<Object>,
+    ) -> Result<Object, RuntimeError> {
+        if arguments.len() != callee.arity() {
+            let message = format!("Expected {} arguments but got {}", callee.arity(), arguments.len());
+            return Err(RuntimeError::new(call.paren.clone(), message));
+        }
+
+        self.tick(&call.paren)?;
+
+        // Raise a Lox error before the native stack runs out.
+        if self.frames.len() >= self.max_call_depth {
+            return Err(RuntimeError::new(call.paren.clone(), "Stack overflow".to_string()));
+        }
+
+        self.frames.push(Frame { function: name.to_string(), line: call.paren.line, module: self.module.clone() });
+        self.callers.push(Rc::clone(&self.environment));
+
+        self.notify(|hook, interpreter| hook.enter(interpreter, name, &arguments));
+
+        let previous = std::mem::replace(&mut self.module, module);
+
+        let result = callee.call(self, arguments).map_err(|mut error| {
+            // Capture the trace at the innermost call, before any frames are popped.
+            if error.trace.is_empty() {
+                error.trace = self.backtrace(error.token.line);
+            }
+            error
+        });
+
+        self.module = previous;
+
+        self.notify(|hook, interpreter| hook.leave(interpreter, name, result.as_ref().ok()));
+
+        self.frames.pop();
+        self.callers.pop();
+
+        result
+    }
 }
 
 impl Default for Interpreter {
@@ -67,154 +501,316 @@ impl Default for Interpreter {
     }
 }
 
-impl ExprVisitor<Object> for Interpreter {
-    fn visit_literal_expr(&mut self, literal: &Literal) -> Object {
-        Object::Literal(literal.clone())
+/// Returns the value as a number, or a type error at the operator.
+fn number(operator: &Token, value: &Object, message: &str) -> Result<f64, RuntimeError> {
+    match value {
+        Object::Literal(Literal::Number(number)) => Ok(*number),
+        _ => Err(RuntimeError::new(operator.clone(), message.to_string())),
+    }
+}
+
+/// Returns the value as an integer, or a type error at the operator.
+fn integer(operator: &Token, value: &Object, message: &str) -> Result<i64, RuntimeError> {
+    match number(operator, value, message)? {
+        number if number.fract() == 0.0 && number.abs() < i64::MAX as f64 => Ok(number as i64),
+        _ => Err(RuntimeError::new(operator.clone(), message.to_string())),
+    }
+}
+
+/// Applies an arithmetic operator that is only defined on numbers.
+fn arithmetic(operator: &Token, left: &Object, right: &Object) -> Result<Object, RuntimeError> {
+    let message = "Operands must be numbers";
+    let (l, r) = (number(operator, left, message)?, number(operator, right, message)?);
+
+    let value = match operator.r#type {
+        // The remainder takes the sign of the divisor, to match the floor division.
+        Type::Percent => match l % r {
+            remainder if remainder != 0.0 && (remainder < 0.0) != (r < 0.0) => remainder + r,
+            remainder => remainder,
+        },
+        Type::TildeSlash => (l / r).floor(),
+        Type::StarStar => l.powf(r),
+        _ => unreachable!(),
+    };
+
+    Ok(Object::from(value))
+}
+
+/// Applies a bitwise operator, defined on numbers without a fractional part.
+fn bitwise(operator: &Token, left: &Object, right: &Object) -> Result<Object, RuntimeError> {
+    let message = "Operands must be integers";
+    let (l, r) = (integer(operator, left, message)?, integer(operator, right, message)?);
+
+    if matches!(operator.r#type, Type::LessLess | Type::GreaterGreater) && !(0..64).contains(&r) {
+        return Err(RuntimeError::new(operator.clone(), "Shift amount must be between 0 and 63".to_string()));
+    }
+
+    let value = match operator.r#type {
+        Type::Ampersand      => l & r,
+        Type::Pipe           => l | r,
+        Type::Caret          => l ^ r,
+        Type::LessLess       => l << r,
+        Type::GreaterGreater => l >> r,
+        _ => unreachable!(),
+    };
+
+    Ok(Object::from(value as f64))
+}
+
+/// Applies a comparison or arithmetic operator to operands converted to numbers.
+fn numeric(operator: &Token, left: &Object, right: &Object) -> Result<Object, RuntimeError> {
+    let message = "Operands must be numbers";
+    let (l, r) = (left.as_number(operator, message)?, right.as_number(operator, message)?);
+
+    let value = match operator.r#type {
+        Type::Greater       => Object::from(l > r),
+        Type::GreaterEqual  => Object::from(l >= r),
+        Type::Less          => Object::from(l < r),
+        Type::LessEqual     => Object::from(l <= r),
+        Type::EqualEqual    => Object::from(l == r),
+        Type::BangEqual     => Object::from(l != r),
+        Type::Slash         => Object::from(l / r),
+        Type::Star          => Object::from(l * r),
+        Type::Minus         => Object::from(l - r),
+        _ => unreachable!(),
+    };
+
+    Ok(value)
+}
+
+/// Applies a binary operator to the already evaluated operands.
+fn binary_operation(operator: &Token, left: Object, right: Object) -> Result<Object, RuntimeError> {
+    let value = match operator.r#type {
+        Type::Greater | Type::GreaterEqual | Type::Less | Type::LessEqual | Type::EqualEqual | Type::BangEqual
+        | Type::Slash | Type::Star | Type::Minus => numeric(operator, &left, &right)?,
+        Type::Comma         => right,
+        Type::Percent | Type::TildeSlash | Type::StarStar => arithmetic(operator, &left, &right)?,
+        Type::Ampersand | Type::Pipe | Type::Caret | Type::LessLess | Type::GreaterGreater => {
+            bitwise(operator, &left, &right)?
+        },
+        Type::Plus          => match (left, right) {
+            (Object::Literal(Literal::Number(l)), Object::Literal(Literal::Number(r))) => Object::from(l + r),
+            (Object::Literal(Literal::String(l)), Object::Literal(Literal::String(r))) => Object::from(l + &r),
+            _ => {
+                return Err(RuntimeError::new(
+                    operator.clone(),
+                    "Tried to add two unsupported types".to_string(),
+                ));
+            }
+        },
+        _ => unreachable!(),
+    };
+
+    Ok(value)
+}
+
+impl ExprVisitor<Result<Object, RuntimeError>> for Interpreter {
+    fn visit_literal_expr(&mut self, literal: &expr::LiteralData) -> Result<Object, RuntimeError> {
+        Ok(Object::Literal(literal.value.clone()))
     }
 
-    fn visit_logical_expr(&mut self, logical: &expr::LogicalData) -> Object {
-        let left = self.evaluate(&logical.left);
+    fn visit_logical_expr(&mut self, logical: &expr::LogicalData) -> Result<Object, RuntimeError> {
+        let left = self.evaluate(&logical.left)?;
 
-        match logical.operator.r#type {
-            Type::Or => if left.as_bool() { return left },
-            Type::And => if !left.as_bool() { return left },
+        let taken = match logical.operator.r#type {
+            Type::Or => !left.as_bool(),
+            Type::And => left.as_bool(),
+            Type::QuestionQuestion => left == Object::Literal(Literal::Null),
             _ => unreachable!(),
         };
 
+        self.notify(|hook, interpreter| hook.branch(interpreter, &logical.operator, taken));
+
+        if !taken {
+            return Ok(left);
+        }
+
         self.evaluate(&logical.right)
     }
 
-    fn visit_unary_expr(&mut self, unary: &expr::UnaryData) -> Object {
-        let right = self.evaluate(&unary.expr);
+    fn visit_conditional_expr(&mut self, conditional: &expr::ConditionalData) -> Result<Object, RuntimeError> {
+        let taken = self.evaluate(&conditional.condition)?.as_bool();
 
-        match unary.operator.r#type {
-            Type::Minus => Object::Literal(Literal::Number(-right.as_number())),
-            Type::Bang => Object::Literal(Literal::Bool(!right.as_bool())),
-            _ => unreachable!(),
+        self.notify(|hook, interpreter| hook.branch(interpreter, &conditional.question, taken));
+
+        match taken {
+            true => self.evaluate(&conditional.then_branch),
+            false => self.evaluate(&conditional.else_branch),
         }
     }
 
-    fn visit_binary_expr(&mut self, binary: &expr::BinaryData) -> Object {
-        let left = self.evaluate(&binary.left);
-        let right = self.evaluate(&binary.right);
-
-        match binary.operator.r#type {
-            Type::Greater       => Object::from(left.as_number() > right.as_number()),
-            Type::GreaterEqual  => Object::from(left.as_number() >= right.as_number()),
-            Type::Less          => Object::from(left.as_number() < right.as_number()),
-            Type::LessEqual     => Object::from(left.as_number() <= right.as_number()),
-            Type::EqualEqual    => Object::from(left.as_number() == right.as_number()),
-            Type::BangEqual     => Object::from(left.as_number() != right.as_number()),
-            Type::Slash         => Object::from(left.as_number() / right.as_number()),
-            Type::Star          => Object::from(left.as_number() * right.as_number()),
-            Type::Minus         => Object::from(left.as_number() - right.as_number()),
-            Type::Plus          => match (left, right) {
-                (Object::Literal(Literal::Number(l)), Object::Literal(Literal::Number(r))) => Object::from(l + r),
-                (Object::Literal(Literal::String(l)), Object::Literal(Literal::String(r))) => Object::from(l + &r),
-                _ => {
-                    RuntimeError {
-                        token: binary.operator.clone(),
-                        message: "Tried to add two unsupported types".to_string(),
-                    }.throw();
-                    Object::from(Literal::Null)
-                }
+    fn visit_unary_expr(&mut self, unary: &expr::UnaryData) -> Result<Object, RuntimeError> {
+        let right = self.evaluate(&unary.expr)?;
+
+        match unary.operator.r#type {
+            Type::Minus => {
+                let value = right.as_number(&unary.operator, "Operand must be a number")?;
+                Ok(Object::Literal(Literal::Number(-value)))
+            },
+            Type::Bang => Ok(Object::Literal(Literal::Bool(!right.as_bool()))),
+            Type::Tilde => {
+                let value = integer(&unary.operator, &right, "Operand must be an integer")?;
+                Ok(Object::from(!value as f64))
             },
             _ => unreachable!(),
         }
     }
 
-    fn visit_call_expr(&mut self, call: &expr::CallData) -> Object {
-        let callee = self.evaluate(call.callee.as_ref());
+    fn visit_binary_expr(&mut self, binary: &expr::BinaryData) -> Result<Object, RuntimeError> {
+        let left = self.evaluate(&binary.left)?;
+        let right = self.evaluate(&binary.right)?;
 
-        // TODO: Try to avoid clone here
-        let arguments: Vec<Object> = call.arguments
+        binary_operation(&binary.operator, left, right)
+    }
+
+    fn visit_call_expr(&mut self, call: &expr::CallData) -> Result<Object, RuntimeError> {
+        let callee = self.evaluate(call.callee.as_ref())?;
+
+        // An optional call short-circuits on a null callee, before its arguments are evaluated.
+        if let Some(question) = &call.optional {
+            let taken = callee != Object::Literal(Literal::Null);
+            self.notify(|hook, interpreter| hook.branch(interpreter, question, taken));
+
+            if !taken {
+                return Ok(callee);
+            }
+        }
+
+        let arguments = call.arguments
             .iter()
             .map(|expr| self.evaluate(expr))
-            .collect();
+            .collect::<Result<Vec<Object>, RuntimeError>>()?;
 
         match callee {
             Object::Function(function) => {
-                if arguments.len() != function.arity() {
-                    RuntimeError {
-                        token: call.paren.clone(),
-                        message: format!("Expected {} arguments but got {}", function.arity(), arguments.len()),
-                    }.throw();
-                    return Object::from(Literal::Null);
-                }
-
-                function.call(self, arguments).unwrap_or_else(|mut error| {
-                    error.token = call.paren.clone();
-                    error.throw();
-                    Object::from(Literal::Null)
-                })
+                let name = function.name.lexeme.clone();
+                self.call(function.as_ref(), &name, function.module.clone(), call, arguments)
             },
             Object::NativeFunction(function) => {
-                if arguments.len() != function.arity() {
-                    RuntimeError {
-                        token: call.paren.clone(),
-                        message: format!("Expected {} arguments but got {}", function.arity(), arguments.len()),
-                    }.throw();
-                    return Object::from(Literal::Null);
-                }
-
-                function.call(self, arguments).unwrap_or_else(|mut error| {
+                let name = function.name.lexeme.clone();
+                // Native functions have no source location of their own, blame the call site.
+                let module = self.module.clone();
+                self.call(&function, &name, module, call, arguments).map_err(|mut error| {
                     error.token = call.paren.clone();
-                    error.throw();
-                    Object::from(Literal::Null)
+                    error
                 })
             },
-            _ => {
-                RuntimeError {
-                    token: call.paren.clone(),
-                    message: "Can only call functions and classes".to_string(),
-                }.throw();
-                Object::from(Literal::Null)
-            }
+            _ => Err(RuntimeError::new(
+                call.paren.clone(),
+                "Can only call functions and classes".to_string(),
+            )),
         }
     }
 
-    fn visit_grouping_expr(&mut self, grouping: &expr::GroupingData) -> Object {
+    fn visit_grouping_expr(&mut self, grouping: &expr::GroupingData) -> Result<Object, RuntimeError> {
         self.evaluate(&grouping.expr)
     }
 
-    fn visit_variable_expr(&mut self, variable: &expr::VariableData) -> Object {
-        self.environment
-            .borrow()
-            .get(&variable.name)
-            .unwrap_or_else(|error| {
-                error.throw();
-                Object::from(Literal::Null)
-            })
+    fn visit_variable_expr(&mut self, variable: &expr::VariableData) -> Result<Object, RuntimeError> {
+        self.environment.borrow().get(&variable.name)
+    }
+
+    fn visit_assign_expr(&mut self, assign: &expr::AssignData) -> Result<Object, RuntimeError> {
+        let value = self.evaluate(&assign.value)?;
+        self.environment.borrow_mut().assign(&assign.name, value.to_owned())?;
+        self.notify(|hook, interpreter| hook.assign(interpreter, &assign.name, &value));
+        Ok(value)
+    }
+
+    fn visit_update_expr(&mut self, update: &expr::UpdateData) -> Result<Object, RuntimeError> {
+        // The target is read once, before the value is evaluated.
+        let current = self.environment.borrow().get(&update.name)?;
+
+        let operand = match &update.value {
+            Some(value) => self.evaluate(value)?,
+            None => {
+                number(&update.operator, &current, "Operand must be a number")?;
+                Object::from(1.0)
+            },
+        };
+
+        let r#type = match update.operator.r#type {
+            Type::PlusEqual | Type::PlusPlus => Type::Plus,
+            Type::MinusEqual | Type::MinusMinus => Type::Minus,
+            Type::StarEqual => Type::Star,
+            Type::SlashEqual => Type::Slash,
+            Type::PercentEqual => Type::Percent,
+            _ => unreachable!(),
+        };
+
+        // Unlike their binary forms, these don't coerce their operands.
+        if matches!(r#type, Type::Minus | Type::Star | Type::Slash) {
+            let message = "Operands must be numbers";
+            number(&update.operator, &current, message)?;
+            number(&update.operator, &operand, message)?;
+        }
+
+        let operator = Token { r#type, ..update.operator.clone() };
+        let value = binary_operation(&operator, current.clone(), operand)?;
+
+        self.environment.borrow_mut().assign(&update.name, value.to_owned())?;
+        self.notify(|hook, interpreter| hook.assign(interpreter, &update.name, &value));
+
+        Ok(if update.prefix { value } else { current })
+    }
+
+    fn visit_interpolation_expr(&mut self, interpolation: &expr::InterpolationData) -> Result<Object, RuntimeError> {
+        let mut string = String::new();
+
+        for (index, part) in interpolation.strings.iter().enumerate() {
+            string += part;
+
+            // Rendered the same way `print` does.
+            if let Some(expr) = interpolation.expressions.get(index) {
+                string += &self.evaluate(expr)?.to_string();
+            }
+        }
+
+        Ok(Object::from(string))
     }
 
-    fn visit_assign_expr(&mut self, assign: &expr::AssignData) -> Object {
-        let value = self.evaluate(&assign.value);
-        self.environment.borrow_mut().assign(&assign.name, value.to_owned());
-        value
+    fn visit_get_expr(&mut self, get: &expr::GetData) -> Result<Object, RuntimeError> {
+        let Object::Module(module) = self.evaluate(&get.object)? else {
+            return Err(RuntimeError::new(get.name.clone(), "Only modules have properties".to_string()));
+        };
+
+        // Only the bindings of the module itself, not the globals it sees.
+        let value = module.environment.borrow().variables.get(&get.name.lexeme).cloned();
+
+        value.ok_or_else(|| {
+            let message = format!("Undefined property '{}' in module '{}'", get.name.lexeme, module.name);
+            RuntimeError::new(get.name.clone(), message)
+        })
     }
 }
 
-impl StmtVisitor<Result<(), ReturnError>> for Interpreter {
-    fn visit_expression_stmt(&mut self, stmt: &Stmt) -> Result<(), ReturnError> {
+impl StmtVisitor<Result<(), Unwind>> for Interpreter {
+    fn visit_expression_stmt(&mut self, stmt: &Stmt) -> Result<(), Unwind> {
         let Stmt::Expression(data) = stmt else { unreachable!() };
-        self.evaluate(&data.expr);
+        self.evaluate(&data.expr)?;
 
         Ok(())
     }
 
-    fn visit_function_stmt(&mut self, stmt: &Stmt) -> Result<(), ReturnError> {
+    fn visit_function_stmt(&mut self, stmt: &Stmt) -> Result<(), Unwind> {
         let Stmt::Function(_) = stmt else { unreachable!() };
 
-        let function = Function::new(stmt.to_owned(), Rc::clone(&self.environment));
+        let function = Function::new(stmt.to_owned(), Rc::clone(&self.environment), self.module.clone());
+        let name = function.name.clone();
+        let value = Object::from(function);
 
-        self.environment.borrow_mut().define(&function.name.lexeme.clone(), Object::from(function));
+        self.environment.borrow_mut().define(&name.lexeme, value.clone());
+        self.notify(|hook, interpreter| hook.define(interpreter, &name, &value));
 
         Ok(())
     }
 
-    fn visit_if_stmt(&mut self, stmt: &Stmt) -> Result<(), ReturnError> {
+    fn visit_if_stmt(&mut self, stmt: &Stmt) -> Result<(), Unwind> {
         let Stmt::If(data) = stmt else { unreachable!() };
-        if self.evaluate(&data.condition).as_bool() {
+        let taken = self.evaluate(&data.condition)?.as_bool();
+        self.notify(|hook, interpreter| hook.branch(interpreter, &data.keyword, taken));
+
+        if taken {
             self.execute(&data.then_branch)
         } else if let Some(else_branch) = &data.else_branch {
             self.execute(else_branch)
@@ -223,109 +819,129 @@ impl StmtVisitor<Result<(), ReturnError>> for Interpreter {
         }
     }
 
-    fn visit_print_stmt(&mut self, stmt: &Stmt) -> Result<(), ReturnError> {
+    fn visit_print_stmt(&mut self, stmt: &Stmt) -> Result<(), Unwind> {
         let Stmt::Print(data) = stmt else { unreachable!() };
-        let value = self.evaluate(&data.expr);
-
-        // Make sure evaluate didn't throw an error
-        if error::did_error() {
-            return Ok(());
-        }
+        let value = self.evaluate(&data.expr)?;
 
-        println!("{value}");
+        writeln!(self.output, "{value}").map_err(|error| {
+            RuntimeError::new(data.keyword.clone(), format!("Could not write output: {error}"))
+        })?;
 
         Ok(())
     }
 
-    fn visit_return_stmt(&mut self, stmt: &Stmt) -> Result<(), ReturnError> {
+    fn visit_return_stmt(&mut self, stmt: &Stmt) -> Result<(), Unwind> {
         let Stmt::Return(data) = stmt else { unreachable!() };
 
         let value = if let Some(expr) = &data.value {
-            self.evaluate(&expr)
+            self.evaluate(expr)?
         } else {
             Object::from(Literal::Null)
         };
 
-        Err(ReturnError { value })
+        Err(Unwind::Return(Box::new(ReturnError { keyword: data.keyword.clone(), value })))
     }
 
-    fn visit_var_stmt(&mut self, stmt: &Stmt) -> Result<(), ReturnError> {
+    fn visit_var_stmt(&mut self, stmt: &Stmt) -> Result<(), Unwind> {
         let Stmt::Var(data) = stmt else { unreachable!() };
         let value = match &data.initializer {
-            Some(value) => self.evaluate(value),
+            Some(value) => self.evaluate(value)?,
             None => Object::from(Literal::Null),
         };
 
-        self.environment.borrow_mut().define(&data.name.lexeme, value);
+        self.environment.borrow_mut().define(&data.name.lexeme, value.clone());
+        self.notify(|hook, interpreter| hook.define(interpreter, &data.name, &value));
 
         Ok(())
     }
 
-    fn visit_while_stmt(&mut self, stmt: &Stmt) -> Result<(), ReturnError> {
+    fn visit_while_stmt(&mut self, stmt: &Stmt) -> Result<(), Unwind> {
         let Stmt::While(data) = stmt else { unreachable!() };
-        while self.evaluate(&data.condition).as_bool() {
+        loop {
+            let taken = self.evaluate(&data.condition)?.as_bool();
+            self.notify(|hook, interpreter| hook.branch(interpreter, &data.keyword, taken));
+
+            if !taken {
+                break;
+            }
+
+            self.tick(&data.keyword)?;
             self.execute(&data.body)?;
         }
 
         Ok(())
     }
 
-    fn visit_block_stmt(&mut self, stmt: &Stmt) -> Result<(), ReturnError> {
+    fn visit_block_stmt(&mut self, stmt: &Stmt) -> Result<(), Unwind> {
         let Stmt::Block(data) = stmt else { unreachable!() };
         self.execute_block(
             &data.statements,
             Rc::new(RefCell::new(Environment::new(Some(Rc::clone(&self.environment)))))
         )
     }
+
+    fn visit_import_stmt(&mut self, stmt: &Stmt) -> Result<(), Unwind> {
+        let Stmt::Import(data) = stmt else { unreachable!() };
+        let module = self.import(&data.path)?;
+
+        self.environment.borrow_mut().define(&data.name.lexeme, module.clone());
+        self.notify(|hook, interpreter| hook.define(interpreter, &data.name, &module));
+
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::token::Token;
+    use crate::error::RuntimeErrorKind;
+    use crate::scanner::Scanner;
+    use crate::parser::Parser;
+    use crate::stmt::{PrintData, ReturnData};
+    use crate::testing::files;
 
     #[test]
     fn evaluate_literal() {
         let mut interpreter = Interpreter::new();
-        let expr = Expr::Literal(Literal::Number(12.0));
-        assert_eq!(interpreter.evaluate(&expr), Object::from(12.0));
+        let expr = Expr::Literal(Literal::Number(12.0).into());
+        assert_eq!(interpreter.evaluate(&expr).unwrap(), Object::from(12.0));
     }
 
     #[test]
     fn evaluate_logical() {
         let mut interpreter = Interpreter::new();
         let expr = Expr::Logical(expr::LogicalData {
-            left: Box::new(Expr::Literal(Literal::Bool(true))),
+            left: Box::new(Expr::Literal(Literal::Bool(true).into())),
             operator: Token::new(Type::And, String::from("and"), None, 1),
-            right: Box::new(Expr::Literal(Literal::Bool(true))),
+            right: Box::new(Expr::Literal(Literal::Bool(true).into())),
         });
-        assert_eq!(interpreter.evaluate(&expr), Object::from(true));
+        assert_eq!(interpreter.evaluate(&expr).unwrap(), Object::from(true));
     }
 
     #[test]
     fn evaluate_logical_short_circuit() {
         let mut interpreter = Interpreter::new();
         let expr = Expr::Logical(expr::LogicalData {
-            left: Box::new(Expr::Literal(Literal::Bool(false))),
+            left: Box::new(Expr::Literal(Literal::Bool(false).into())),
             operator: Token::new(Type::And, String::from("and"), None, 1),
-            right: Box::new(Expr::Literal(Literal::Bool(true))),
+            right: Box::new(Expr::Literal(Literal::Bool(true).into())),
         });
-        assert_eq!(interpreter.evaluate(&expr), Object::from(false));
+        assert_eq!(interpreter.evaluate(&expr).unwrap(), Object::from(false));
     }
 
     #[test]
     fn evaluate_logical_nested() {
         let mut interpreter = Interpreter::new();
         let expr = Expr::Logical(expr::LogicalData {
-            left: Box::new(Expr::Literal(Literal::Bool(true))),
+            left: Box::new(Expr::Literal(Literal::Bool(true).into())),
             operator: Token::new(Type::Or, String::from("or"), None, 1),
             right: Box::new(Expr::Logical(expr::LogicalData {
-                left: Box::new(Expr::Literal(Literal::Bool(true))),
+                left: Box::new(Expr::Literal(Literal::Bool(true).into())),
                 operator: Token::new(Type::And, String::from("and"), None, 1),
-                right: Box::new(Expr::Literal(Literal::Bool(true))),
+                right: Box::new(Expr::Literal(Literal::Bool(true).into())),
             })),
         });
-        assert_eq!(interpreter.evaluate(&expr), Object::from(true));
+        assert_eq!(interpreter.evaluate(&expr).unwrap(), Object::from(true));
     }
 
     #[test]
@@ -333,140 +949,140 @@ mod test {
         let mut interpreter = Interpreter::new();
         let expr = Expr::Unary(expr::UnaryData {
             operator: Token::new(Type::Minus, String::from("-"), None, 1),
-            expr: Box::new(Expr::Literal(Literal::Number(12.0))),
+            expr: Box::new(Expr::Literal(Literal::Number(12.0).into())),
         });
-        assert_eq!(interpreter.evaluate(&expr), Object::from(-12.0));
+        assert_eq!(interpreter.evaluate(&expr).unwrap(), Object::from(-12.0));
     }
 
     #[test]
     fn evaluate_binary() {
         let mut interpreter = Interpreter::new();
         let expr = Expr::Binary(expr::BinaryData {
-            left: Box::new(Expr::Literal(Literal::Number(12.0))),
+            left: Box::new(Expr::Literal(Literal::Number(12.0).into())),
             operator: Token::new(Type::Minus, String::from("-"), None, 1),
-            right: Box::new(Expr::Literal(Literal::Number(12.0))),
+            right: Box::new(Expr::Literal(Literal::Number(12.0).into())),
         });
-        assert_eq!(interpreter.evaluate(&expr), Object::from(0.0));
+        assert_eq!(interpreter.evaluate(&expr).unwrap(), Object::from(0.0));
     }
 
     #[test]
     fn evaluate_grouping() {
         let mut interpreter = Interpreter::new();
         let expr = Expr::Grouping(expr::GroupingData {
-            expr: Box::new(Expr::Literal(Literal::Number(12.0))),
+            paren: Token::new(Type::LeftParen, String::from("("), None, 1),
+            expr: Box::new(Expr::Literal(Literal::Number(12.0).into())),
         });
-        assert_eq!(interpreter.evaluate(&expr), Object::from(12.0));
+        assert_eq!(interpreter.evaluate(&expr).unwrap(), Object::from(12.0));
     }
 
     #[test]
     fn evaluate_complex() {
         let mut interpreter = Interpreter::new();
         let expr = Expr::Binary(expr::BinaryData {
-            left: Box::new(Expr::Literal(Literal::Number(6.0))),
+            left: Box::new(Expr::Literal(Literal::Number(6.0).into())),
             operator: Token::new(Type::Minus, String::from("-"), None, 1),
             right: Box::new(Expr::Binary(expr::BinaryData {
-                left: Box::new(Expr::Literal(Literal::Number(12.0))),
+                left: Box::new(Expr::Literal(Literal::Number(12.0).into())),
                 operator: Token::new(Type::Minus, String::from("-"), None, 1),
-                right: Box::new(Expr::Literal(Literal::Number(24.0))),
+                right: Box::new(Expr::Literal(Literal::Number(24.0).into())),
             })),
         });
-        assert_eq!(interpreter.evaluate(&expr), Object::from(18.0));
+        assert_eq!(interpreter.evaluate(&expr).unwrap(), Object::from(18.0));
     }
 
     #[test]
     fn evaluate_string() {
         let mut interpreter = Interpreter::new();
         let expr = Expr::Binary(expr::BinaryData {
-            left: Box::new(Expr::Literal(Literal::String(String::from("Hello")))),
+            left: Box::new(Expr::Literal(Literal::String(String::from("Hello")).into())),
             operator: Token::new(Type::Plus, String::from("+"), None, 1),
-            right: Box::new(Expr::Literal(Literal::String(String::from("World")))),
+            right: Box::new(Expr::Literal(Literal::String(String::from("World")).into())),
         });
-        assert_eq!(interpreter.evaluate(&expr), Object::from("HelloWorld"));
+        assert_eq!(interpreter.evaluate(&expr).unwrap(), Object::from("HelloWorld"));
     }
 
     #[test]
     fn evaluate_string_and_number() {
         let mut interpreter = Interpreter::new();
         let expr = Expr::Binary(expr::BinaryData {
-            left: Box::new(Expr::Literal(Literal::String(String::from("Hello")))),
+            left: Box::new(Expr::Literal(Literal::String(String::from("Hello")).into())),
             operator: Token::new(Type::Plus, String::from("+"), None, 1),
-            right: Box::new(Expr::Literal(Literal::Number(12.0))),
+            right: Box::new(Expr::Literal(Literal::Number(12.0).into())),
         });
-        assert_eq!(interpreter.evaluate(&expr), Object::from(Literal::Null));
-        assert!(error::did_error());
+        assert!(interpreter.evaluate(&expr).is_err());
     }
 
     #[test]
     fn evaluate_greater() {
         let mut interpreter = Interpreter::new();
         let expr = Expr::Binary(expr::BinaryData {
-            left: Box::new(Expr::Literal(Literal::Number(12.0))),
+            left: Box::new(Expr::Literal(Literal::Number(12.0).into())),
             operator: Token::new(Type::Greater, String::from(">"), None, 1),
-            right: Box::new(Expr::Literal(Literal::Number(12.0))),
+            right: Box::new(Expr::Literal(Literal::Number(12.0).into())),
         });
-        assert_eq!(interpreter.evaluate(&expr), Object::from(false));
+        assert_eq!(interpreter.evaluate(&expr).unwrap(), Object::from(false));
     }
 
     #[test]
     fn evaluate_greater_equal() {
         let mut interpreter = Interpreter::new();
         let expr = Expr::Binary(expr::BinaryData {
-            left: Box::new(Expr::Literal(Literal::Number(12.0))),
+            left: Box::new(Expr::Literal(Literal::Number(12.0).into())),
             operator: Token::new(Type::GreaterEqual, String::from(">="), None, 1),
-            right: Box::new(Expr::Literal(Literal::Number(12.0))),
+            right: Box::new(Expr::Literal(Literal::Number(12.0).into())),
         });
-        assert_eq!(interpreter.evaluate(&expr), Object::from(true));
+        assert_eq!(interpreter.evaluate(&expr).unwrap(), Object::from(true));
     }
 
     #[test]
     fn evaluate_less() {
         let mut interpreter = Interpreter::new();
         let expr = Expr::Binary(expr::BinaryData {
-            left: Box::new(Expr::Literal(Literal::Number(12.0))),
+            left: Box::new(Expr::Literal(Literal::Number(12.0).into())),
             operator: Token::new(Type::Less, String::from("<"), None, 1),
-            right: Box::new(Expr::Literal(Literal::Number(12.0))),
+            right: Box::new(Expr::Literal(Literal::Number(12.0).into())),
         });
-        assert_eq!(interpreter.evaluate(&expr), Object::from(false));
+        assert_eq!(interpreter.evaluate(&expr).unwrap(), Object::from(false));
     }
 
     #[test]
     fn evaluate_less_equal() {
         let mut interpreter = Interpreter::new();
         let expr = Expr::Binary(expr::BinaryData {
-            left: Box::new(Expr::Literal(Literal::Number(12.0))),
+            left: Box::new(Expr::Literal(Literal::Number(12.0).into())),
             operator: Token::new(Type::LessEqual, String::from("<="), None, 1),
-            right: Box::new(Expr::Literal(Literal::Number(12.0))),
+            right: Box::new(Expr::Literal(Literal::Number(12.0).into())),
         });
-        assert_eq!(interpreter.evaluate(&expr), Object::from(true));
+        assert_eq!(interpreter.evaluate(&expr).unwrap(), Object::from(true));
     }
 
     #[test]
     fn evaluate_equal() {
         let mut interpreter = Interpreter::new();
         let expr_true = Expr::Binary(expr::BinaryData {
-            left: Box::new(Expr::Literal(Literal::Number(12.0))),
+            left: Box::new(Expr::Literal(Literal::Number(12.0).into())),
             operator: Token::new(Type::EqualEqual, String::from("=="), None, 1),
-            right: Box::new(Expr::Literal(Literal::Number(12.0))),
+            right: Box::new(Expr::Literal(Literal::Number(12.0).into())),
         });
-        assert_eq!(interpreter.evaluate(&expr_true), Object::from(true));
+        assert_eq!(interpreter.evaluate(&expr_true).unwrap(), Object::from(true));
 
         let expr_false = Expr::Binary(expr::BinaryData {
-            left: Box::new(Expr::Literal(Literal::Number(12.0))),
+            left: Box::new(Expr::Literal(Literal::Number(12.0).into())),
             operator: Token::new(Type::EqualEqual, String::from("=="), None, 1),
-            right: Box::new(Expr::Literal(Literal::Number(13.0))),
+            right: Box::new(Expr::Literal(Literal::Number(13.0).into())),
         });
-        assert_eq!(interpreter.evaluate(&expr_false), Object::from(false));
+        assert_eq!(interpreter.evaluate(&expr_false).unwrap(), Object::from(false));
     }
 
     #[test]
     fn evaluate_not_equal() {
         let mut interpreter = Interpreter::new();
         let expr = Expr::Binary(expr::BinaryData {
-            left: Box::new(Expr::Literal(Literal::Number(12.0))),
+            left: Box::new(Expr::Literal(Literal::Number(12.0).into())),
             operator: Token::new(Type::BangEqual, String::from("!="), None, 1),
-            right: Box::new(Expr::Literal(Literal::Number(12.0))),
+            right: Box::new(Expr::Literal(Literal::Number(12.0).into())),
         });
-        assert_eq!(interpreter.evaluate(&expr), Object::from(false));
+        assert_eq!(interpreter.evaluate(&expr).unwrap(), Object::from(false));
     }
 
     #[test]
@@ -475,13 +1091,435 @@ mod test {
         interpreter.environment.borrow_mut().define("a", Object::from(0.0));
         let expr = Expr::Assign(expr::AssignData {
             name: Token::new(Type::Identifier, String::from("a"), None, 1),
-            value: Box::new(Expr::Literal(Literal::Number(12.0))),
+            value: Box::new(Expr::Literal(Literal::Number(12.0).into())),
         });
-        assert_eq!(interpreter.evaluate(&expr), Object::from(12.0));
+        assert_eq!(interpreter.evaluate(&expr).unwrap(), Object::from(12.0));
         assert_eq!(
             interpreter.environment.borrow().get(&Token::new(Type::Identifier, String::from("a"), None, 1)).unwrap(),
             Object::from(12.0)
         );
     }
-}
 
+    /// Interprets the expression and returns its value.
+    fn value(expression: &str) -> Result<Object, RuntimeError> {
+        let tokens = Scanner::new(format!("var result = {expression};")).scan_tokens();
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(&Parser::new(tokens).parse())?;
+
+        interpreter.evaluate(&Expr::Variable(expr::VariableData {
+            name: Token::new(Type::Identifier, "result".to_string(), None, 1),
+        }))
+    }
+
+    #[test]
+    fn evaluate_arithmetic_operators() {
+        let values: Vec<Object> = [
+            "7 % 3", "-7 % 3", "7 % -3", "5.5 % 2", "7 ~/ 2", "-7 ~/ 2", "2 ** 10", "2 ** 3 ** 2",
+            "-2 ** 2", "2 ** -1", "1 + 2 * 3 ** 2 % 5",
+        ].iter().map(|expression| value(expression).unwrap()).collect();
+
+        assert_eq!(values, [1.0, 2.0, -2.0, 1.5, 3.0, -4.0, 1024.0, 512.0, -4.0, 0.5, 4.0].map(Object::from));
+    }
+
+    #[test]
+    fn evaluate_bitwise_operators() {
+        let values: Vec<Object> = [
+            "6 & 3", "6 | 3", "6 ^ 3", "~5", "1 << 4", "-16 >> 2", "1 | 2 ^ 3 & 4 << 1", "1 << 2 + 1",
+        ].iter().map(|expression| value(expression).unwrap()).collect();
+
+        assert_eq!(values, [2.0, 7.0, 5.0, -6.0, 16.0, -4.0, 3.0, 8.0].map(Object::from));
+        assert_eq!(value("(6 & 3) == 2").unwrap(), Object::from(true));
+    }
+
+    #[test]
+    fn operator_type_errors() {
+        let message = |expression| value(expression).unwrap_err().message;
+
+        assert_eq!(message("\"a\" % 2"), "Operands must be numbers");
+        assert_eq!(message("2 ** null"), "Operands must be numbers");
+        assert_eq!(message("1.5 & 1"), "Operands must be integers");
+        assert_eq!(message("true | 1"), "Operands must be integers");
+        assert_eq!(message("~\"a\""), "Operand must be an integer");
+        assert_eq!(message("1 << 64"), "Shift amount must be between 0 and 63");
+    }
+
+    #[test]
+    fn evaluate_conditional_and_comma() {
+        let values: Vec<Object> = [
+            "true ? 1 : 2", "null ? 1 : 2", "false ? 1 : true ? 2 : 3", "1 > 2 ? missing : null ? 2 : 3 + 1 ? 4 : 5",
+            "true ? 1 : missing", "1, 2", "(3, 4) + 1",
+        ].iter().map(|expression| value(expression).unwrap()).collect();
+
+        assert_eq!(values, [1.0, 2.0, 2.0, 4.0, 1.0, 2.0, 5.0].map(Object::from));
+        assert_eq!(value("missing, 1").unwrap_err().message, "Undefined variable 'missing'");
+    }
+
+    #[test]
+    fn callable_truthiness() {
+        let directory = files("truthiness", &[
+            ("main.lox", "import \"lib.lox\" as lib;\nfun f() {}\nvar a = f ? 1 : 2;\nvar b = !clock;\n\
+                var c = lib and 3;\nif (f) a = a + 1;\n"),
+            ("lib.lox", ""),
+        ]);
+
+        let path = directory.join("main.lox");
+        let tokens = Scanner::new(std::fs::read_to_string(&path).unwrap()).scan_tokens();
+
+        let mut interpreter = Interpreter::new();
+        interpreter.set_script(&path);
+        interpreter.interpret(&Parser::new(tokens).parse()).unwrap();
+
+        let values: Vec<Object> = ["a", "b", "c"]
+            .iter()
+            .map(|name| interpreter.evaluate(&Expr::Variable(expr::VariableData {
+                name: Token::new(Type::Identifier, name.to_string(), None, 1),
+            })).unwrap())
+            .collect();
+
+        assert_eq!(values, vec![Object::from(2.0), Object::from(false), Object::from(3.0)]);
+
+        // Functions have no number value.
+        assert_eq!(value("-clock").unwrap_err().message, "Operand must be a number");
+        assert_eq!(value("clock > 1").unwrap_err().message, "Operands must be numbers");
+        assert_eq!(value("1 == clock").unwrap_err().message, "Operands must be numbers");
+    }
+
+    #[test]
+    fn evaluate_null_operators() {
+        let values: Vec<Object> = [
+            "null ?? 1", "false ?? 1", "0 ?? missing", "null ?? null ?? 2", "null?.(missing)", "clock?.() > 0",
+        ].iter().map(|expression| value(expression).unwrap()).collect();
+
+        assert_eq!(values, [
+            Object::from(1.0), Object::from(false), Object::from(0.0), Object::from(2.0),
+            Object::Literal(Literal::Null), Object::from(true),
+        ]);
+        assert_eq!(value("1?.()").unwrap_err().message, "Can only call functions and classes");
+    }
+
+    #[test]
+    fn evaluate_update() {
+        let source = "var a = 5; var b = a++; var c = ++a; var d = a--; var e = --a;\n\
+            var f = 1; f += 9; f -= 2; f *= 3; f /= 4; f %= 4;\n\
+            var g = \"a\"; g += \"b\";\n\
+            var h = 1; fun bump() { h = 10; return 1; } var i = h += bump();\n";
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(&Parser::new(Scanner::new(source.to_string()).scan_tokens()).parse()).unwrap();
+
+        let mut global = |name: &str| interpreter.evaluate(&Expr::Variable(expr::VariableData {
+            name: Token::new(Type::Identifier, name.to_string(), None, 1),
+        })).unwrap();
+
+        let values: Vec<Object> = ["a", "b", "c", "d", "e", "f"].iter().map(|name| global(name)).collect();
+        assert_eq!(values, [5.0, 5.0, 7.0, 7.0, 5.0, 2.0].map(Object::from));
+        assert_eq!(global("g"), Object::from("ab"));
+
+        // The target is read before the value is evaluated.
+        assert_eq!(global("h"), Object::from(2.0));
+        assert_eq!(global("i"), Object::from(2.0));
+    }
+
+    #[test]
+    fn update_errors() {
+        let message = |expression| value(expression).unwrap_err().message;
+
+        assert_eq!(message("clock++"), "Operand must be a number");
+        assert_eq!(message("clock += 1"), "Tried to add two unsupported types");
+        assert_eq!(message("missing -= 1"), "Undefined variable 'missing'");
+        assert_eq!(message("clock -= 1"), "Operands must be numbers");
+        assert_eq!(message("clock *= 2"), "Operands must be numbers");
+    }
+
+    #[test]
+    fn update_string_operand() {
+        let source = "var s = \"a\"; s -= 1;";
+        let mut interpreter = Interpreter::new();
+        let error = interpreter.interpret(&Parser::new(Scanner::new(source.to_string()).scan_tokens()).parse()).unwrap_err();
+        assert_eq!(error.message, "Operands must be numbers");
+
+        let source = "var n = 1; n /= \"2\";";
+        let error = interpreter.interpret(&Parser::new(Scanner::new(source.to_string()).scan_tokens()).parse()).unwrap_err();
+        assert_eq!(error.message, "Operands must be numbers");
+    }
+
+    #[test]
+    fn top_level_return() {
+        // Built by hand, the parser would already reject it.
+        let keyword = Token::new(Type::Return, "return".to_string(), None, 2);
+        let statements = vec![
+            Stmt::Print(PrintData {
+                keyword: Token::new(Type::Print, "print".to_string(), None, 1),
+                expr: Expr::Literal(Literal::Number(1.0).into()),
+            }),
+            Stmt::Return(ReturnData { keyword: keyword.clone(), value: None }),
+        ];
+
+        let mut interpreter = Interpreter::new();
+        let error = interpreter.interpret(&statements).unwrap_err();
+
+        assert_eq!(error.token, keyword);
+        assert_eq!(error.message, "Can't return from top-level code.");
+    }
+
+    #[test]
+    fn runtime_error_trace() {
+        let source = "fun inner() {\n  return missing;\n}\nfun outer() {\n  inner();\n}\nouter();\n";
+        let tokens = Scanner::new(source.to_string()).scan_tokens();
+        let statements = Parser::new(tokens).parse();
+
+        let mut interpreter = Interpreter::new();
+        let error = interpreter.interpret(&statements).unwrap_err();
+
+        assert_eq!(error.token.line, 2);
+        assert_eq!(error.trace, vec![
+            Frame { function: "inner()".to_string(), line: 2, module: None },
+            Frame { function: "outer()".to_string(), line: 5, module: None },
+            Frame { function: "script".to_string(), line: 7, module: None },
+        ]);
+        assert!(interpreter.call_stack().is_empty());
+    }
+
+    #[test]
+    fn stack_overflow() {
+        let source = "fun f() {\n  f();\n}\nf();\n";
+        let tokens = Scanner::new(source.to_string()).scan_tokens();
+        let statements = Parser::new(tokens).parse();
+
+        let mut interpreter = Interpreter::new();
+        interpreter.set_max_call_depth(32);
+        let error = interpreter.interpret(&statements).unwrap_err();
+
+        assert_eq!(error.message, "Stack overflow");
+        assert_eq!(error.trace.len(), 33);
+        assert_eq!(error.trace[0], Frame { function: "f()".to_string(), line: 2, module: None });
+        assert_eq!(error.trace[32], Frame { function: "script".to_string(), line: 4, module: None });
+
+        // The interpreter is still usable after the overflow.
+        let tokens = Scanner::new("var a = 1;\n".to_string()).scan_tokens();
+        assert!(interpreter.interpret(&Parser::new(tokens).parse()).is_ok());
+    }
+
+    #[test]
+    fn fuel_limit() {
+        let source = "var i = 0;\nwhile (true) {\n  i = i + 1;\n}\n";
+        let tokens = Scanner::new(source.to_string()).scan_tokens();
+        let statements = Parser::new(tokens).parse();
+
+        let mut interpreter = Interpreter::new();
+        interpreter.set_limits(Limits { fuel: Some(100), timeout: None });
+        let error = interpreter.interpret(&statements).unwrap_err();
+
+        assert_eq!(error.kind, RuntimeErrorKind::LimitExceeded);
+        assert_eq!(error.token.line, 2);
+        assert_eq!(
+            interpreter.globals.borrow().get(&Token::new(Type::Identifier, "i".to_string(), None, 1)).unwrap(),
+            Object::from(100.0)
+        );
+
+        // Each run gets a fresh budget.
+        let tokens = Scanner::new("i = 0;\n".to_string()).scan_tokens();
+        assert!(interpreter.interpret(&Parser::new(tokens).parse()).is_ok());
+    }
+
+    #[test]
+    fn time_limit() {
+        let source = "fun spin() {\n  while (true) {}\n}\nspin();\n";
+        let tokens = Scanner::new(source.to_string()).scan_tokens();
+        let statements = Parser::new(tokens).parse();
+
+        let mut interpreter = Interpreter::new();
+        interpreter.set_limits(Limits { fuel: None, timeout: Some(Duration::from_millis(10)) });
+        let error = interpreter.interpret(&statements).unwrap_err();
+
+        assert_eq!(error.kind, RuntimeErrorKind::LimitExceeded);
+        assert_eq!(error.trace[1], Frame { function: "script".to_string(), line: 4, module: None });
+        assert!(interpreter.call_stack().is_empty());
+    }
+
+    #[test]
+    fn cancel_from_another_thread() {
+        let source = "var i = 0;\nwhile (true) {\n  i = i + 1;\n}\n";
+        let tokens = Scanner::new(source.to_string()).scan_tokens();
+        let statements = Parser::new(tokens).parse();
+
+        let mut interpreter = Interpreter::new();
+        let handle = interpreter.cancel_handle();
+
+        // Nothing is running yet, the request is dropped.
+        assert!(!handle.cancel());
+
+        // Signals once the loop is running, so the cancel can't come before it.
+        struct Started(Option<std::sync::mpsc::Sender<()>>);
+
+        impl Hook for Started {
+            fn statement(&mut self, _interpreter: &mut Interpreter, _stmt: &Stmt) -> Result<(), RuntimeError> {
+                if let Some(sender) = self.0.take() {
+                    sender.send(()).unwrap();
+                }
+                Ok(())
+            }
+        }
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        interpreter.add_hook(Box::new(Started(Some(sender))));
+
+        let canceller = std::thread::spawn(move || {
+            receiver.recv().unwrap();
+            assert!(handle.cancel());
+        });
+
+        let error = interpreter.interpret(&statements).unwrap_err();
+        canceller.join().unwrap();
+
+        assert_eq!(error.kind, RuntimeErrorKind::Interrupted);
+        assert_eq!(error.message, "Interrupted");
+
+        // State survives the interruption.
+        let tokens = Scanner::new("i = i + 1;\n".to_string()).scan_tokens();
+        assert!(interpreter.interpret(&Parser::new(tokens).parse()).is_ok());
+    }
+
+    #[test]
+    fn doc_native() {
+        let source = "/// Says hi.\nfun hi() {}\nfun bye() {}\nvar a = doc(hi);\nvar b = doc(bye);\nvar c = doc(clock);\n\
+            /// The answer.\nvar answer = 42;\nvar d = doc(answer);\n/// Greets.\nvar greet = hi;\nvar e = doc(greet);\n";
+        let tokens = Scanner::new(source.to_string()).scan_tokens();
+
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(&Parser::new(tokens).parse()).unwrap();
+
+        let docs: Vec<Object> = ["a", "b", "c", "d", "e"]
+            .iter()
+            .map(|name| interpreter.evaluate(&Expr::Variable(expr::VariableData {
+                name: Token::new(Type::Identifier, name.to_string(), None, 1),
+            })).unwrap())
+            .collect();
+
+        assert_eq!(docs, vec![
+            Object::from("Says hi."), Object::from(Literal::Null), Object::from(Literal::Null),
+            Object::from(Literal::Null), Object::from("Says hi."),
+        ]);
+    }
+
+    #[test]
+    fn evaluate_interpolation() {
+        let source = "var a = 1;\nvar s = \"total: ${a + 2} ${\"items\"}, ${null} ${a == 1}${clock}\";\n";
+        let tokens = Scanner::new(source.to_string()).scan_tokens();
+
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(&Parser::new(tokens).parse()).unwrap();
+
+        let s = interpreter.evaluate(&Expr::Variable(expr::VariableData {
+            name: Token::new(Type::Identifier, "s".to_string(), None, 1),
+        }));
+        assert_eq!(s.unwrap(), Object::from("total: 3 items, null true<native fn clock>"));
+    }
+
+    fn run_script(path: &Path) -> Result<(), RuntimeError> {
+        let tokens = Scanner::new(fs::read_to_string(path).unwrap()).scan_tokens();
+        let statements = Parser::new(tokens).parse();
+
+        let mut interpreter = Interpreter::new();
+        interpreter.set_script(path);
+        interpreter.interpret(&statements)
+    }
+
+    #[test]
+    fn import_modules() {
+        let directory = files("import", &[
+            ("main.lox", "import \"lib/counter.lox\" as a;\nimport \"lib/counter.lox\" as b;\nvar total = a.count + b.count;\n"),
+            ("lib/counter.lox", "import \"base.lox\" as base;\nvar count = base.start + 1;\n"),
+            ("lib/base.lox", "var start = 1;\n"),
+        ]);
+
+        let tokens = Scanner::new(fs::read_to_string(directory.join("main.lox")).unwrap()).scan_tokens();
+        let statements = Parser::new(tokens).parse();
+
+        let mut interpreter = Interpreter::new();
+        interpreter.set_script(&directory.join("main.lox"));
+        interpreter.interpret(&statements).unwrap();
+
+        // The module is run once and both names share it.
+        let total = interpreter.evaluate(&Expr::Variable(expr::VariableData {
+            name: Token::new(Type::Identifier, "total".to_string(), None, 1),
+        }));
+        assert_eq!(total.unwrap(), Object::from(4.0));
+        assert_eq!(interpreter.modules.len(), 2);
+    }
+
+    #[test]
+    fn import_search_path() {
+        let directory = files("search", &[
+            ("main.lox", "import \"shared.lox\" as shared;\n"),
+            ("path/shared.lox", "var x = 1;\n"),
+        ]);
+
+        let error = run_script(&directory.join("main.lox")).unwrap_err();
+        assert_eq!(error.message, "Could not find module 'shared.lox'");
+
+        let tokens = Scanner::new("import \"shared.lox\" as shared;\n".to_string()).scan_tokens();
+        let mut interpreter = Interpreter::new();
+        interpreter.set_script(&directory.join("main.lox"));
+        interpreter.set_search_path(vec![directory.join("path")]);
+        assert!(interpreter.interpret(&Parser::new(tokens).parse()).is_ok());
+    }
+
+    #[test]
+    fn import_errors() {
+        let directory = files("errors", &[
+            ("cycle.lox", "import \"a.lox\" as a;\n"),
+            ("a.lox", "import \"b.lox\" as b;\n"),
+            ("b.lox", "import \"a.lox\" as a;\n"),
+            ("syntax.lox", "import \"bad.lox\" as bad;\n"),
+            ("bad.lox", "var = 1;\n"),
+            ("runtime.lox", "import \"fails.lox\" as fails;\n"),
+            ("fails.lox", "var x = 1;\nprint fails.x;\n"),
+            ("property.lox", "import \"a.lox\" as a;\nprint a.missing;\n"),
+            ("nested.lox", "import \"lib/calls.lox\" as calls;\ncalls.f();\n"),
+            ("script.lox", "import \"lib/calls.lox\" as calls;\nprint calls.missing;\n"),
+            ("lib/calls.lox", "fun f() {\n  return missing;\n}\n"),
+        ]);
+
+        let error = run_script(&directory.join("cycle.lox")).unwrap_err();
+        assert_eq!(error.message, "Import cycle: a.lox -> b.lox -> a.lox");
+        assert_eq!(error.trace[0], Frame { function: "<module b.lox>".to_string(), line: 1, module: Some(Rc::new("b.lox".to_string())) });
+
+        let error = run_script(&directory.join("syntax.lox")).unwrap_err();
+        assert_eq!(error.message, "Could not compile module 'bad.lox':\n  [line 1] Error at 'var': Expect variable name");
+
+        let error = run_script(&directory.join("runtime.lox")).unwrap_err();
+        assert_eq!(error.message, "Undefined variable 'fails'");
+        assert_eq!(error.trace, vec![
+            Frame { function: "<module fails.lox>".to_string(), line: 2, module: Some(Rc::new("fails.lox".to_string())) },
+            Frame { function: "script".to_string(), line: 1, module: None },
+        ]);
+        assert!(error.to_string().starts_with("[fails.lox, line 2] Error at 'fails'"));
+
+        // Functions report the module they were declared in, even when called from the script.
+        let error = run_script(&directory.join("nested.lox")).unwrap_err();
+        assert_eq!(error.to_string(), "[lib/calls.lox, line 2] Error at 'missing': Undefined variable 'missing'\
+            \n  [lib/calls.lox, line 2] in f()\
+            \n  [line 2] in script");
+
+        // Modules are named by their path from the directory of the script, wherever they were imported from.
+        let names = files("names", &[
+            ("main.lox", "import \"lib/a.lox\" as a;\n"),
+            ("lib/a.lox", "import \"b.lox\" as b;\n"),
+            ("lib/b.lox", "import \"a.lox\" as a;\n"),
+            ("app/main.lox", "import \"../shared/x.lox\" as x;\n"),
+            ("shared/x.lox", "print missing;\n"),
+        ]);
+
+        let error = run_script(&names.join("main.lox")).unwrap_err();
+        assert_eq!(error.message, "Import cycle: lib/a.lox -> lib/b.lox -> lib/a.lox");
+        assert!(error.to_string().starts_with("[lib/b.lox, line 1] Error at '\"a.lox\"'"));
+        assert_eq!(error.trace[0].function, "<module lib/b.lox>");
+
+        let error = run_script(&names.join("app/main.lox")).unwrap_err();
+        assert!(error.to_string().starts_with("[../shared/x.lox, line 1] Error at 'missing'"));
+
+        // Errors in the script itself keep the plain location.
+        let error = run_script(&directory.join("script.lox")).unwrap_err();
+        assert!(error.to_string().starts_with("[line 2] Error at 'missing'"));
+    }
+}
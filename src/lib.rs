@@ -1,5 +1,6 @@
-use std::{fs, process};
+use std::{env, fs::File, process};
 use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 
 pub mod error;
 pub mod token;
@@ -13,10 +14,39 @@ pub mod interpreter;
 pub mod literal;
 pub mod object;
 pub mod function;
-
+pub mod formatter;
+pub mod linter;
+pub mod lsp;
+pub mod debugger;
+pub mod dap;
+pub mod profiler;
+pub mod coverage;
+pub mod tracer;
+pub mod tester;
+#[cfg(test)]
+mod testing;
+
+use error::rloxError;
 use parser::Parser;
 use scanner::Scanner;
 
+/// Environment variable listing the directories searched for imported modules.
+pub const SEARCH_PATH_VAR: &str = "RLOX_PATH";
+
+/// Returns the directories listed in `RLOX_PATH`.
+pub fn search_path() -> Vec<PathBuf> {
+    env::var_os(SEARCH_PATH_VAR).map_or_else(Vec::new, |paths| env::split_paths(&paths).collect())
+}
+
+/// Returns an interpreter for the script, resolving its imports next to it and in `RLOX_PATH`.
+pub fn script_interpreter(path: &Path) -> interpreter::Interpreter {
+    let mut interpreter = interpreter::Interpreter::new();
+    interpreter.set_script(path);
+    interpreter.set_search_path(search_path());
+
+    interpreter
+}
+
 static mut HAD_ERROR: bool = false;
 static mut HAD_RUNTIME_ERROR: bool = false;
 
@@ -27,16 +57,25 @@ pub struct rlox {
 
 impl rlox {
     pub fn new() -> Self {
-        rlox {
-            interpreter: interpreter::Interpreter::new(),
-        }
+        let mut interpreter = interpreter::Interpreter::new();
+        interpreter.set_search_path(search_path());
+
+        rlox { interpreter }
     }
 
     pub fn run_file(&mut self, path: String) {
-        let contents = fs::read_to_string(path)
+        let file = File::open(&path)
             .expect("Should have been able to read the file");
 
-        self.run(contents);
+        self.interpreter.set_script(Path::new(&path));
+
+        // The file is parsed as it is read, without holding all of its source or tokens.
+        let scanner = Scanner::from_reader(file);
+        let statements = Parser::new(scanner.filter_map(Result::ok)).parse();
+
+        if !error::did_error() {
+            self.execute(&statements);
+        }
 
         unsafe {
             if HAD_ERROR {
@@ -49,18 +88,31 @@ impl rlox {
     }
 
     pub fn run_prompt(&mut self) {
+        // Ctrl-C interrupts the running line instead of killing the session, at the prompt it
+        // drops the line being typed and starts a new one.
+        let handle = self.interpreter.cancel_handle();
+        ctrlc::set_handler(move || {
+            if !handle.cancel() {
+                let mut stdout = io::stdout();
+                let _ = stdout.write_all(b"\n> ").and_then(|_| stdout.flush());
+            }
+        })
+        .expect("Should have been able to set the Ctrl-C handler");
+
         loop {
             let mut input = String::new();
             io::stdout().write_all(b"> ").unwrap();
             io::stdout().flush().unwrap();
-            io::stdin().read_line(&mut input).expect("acceptable expression");
+
+            // Exit on end of input (Ctrl-D).
+            if io::stdin().read_line(&mut input).expect("acceptable expression") == 0 {
+                println!();
+                break;
+            }
 
             self.run(input);
 
-            unsafe {
-                HAD_ERROR = false;
-                HAD_RUNTIME_ERROR = false;
-            }
+            error::reset();
         }
     }
 
@@ -79,7 +131,13 @@ impl rlox {
             return;
         }
 
-        self.interpreter.interpret(&statements);
+        self.execute(&statements);
+    }
+
+    fn execute(&mut self, statements: &[stmt::Stmt]) {
+        if let Err(error) = self.interpreter.interpret(statements) {
+            error.throw();
+        }
     }
 }
 
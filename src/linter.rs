@@ -0,0 +1,536 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::error::ParseError;
+use crate::expr::{LiteralData, Expr, ExprVisitor, UnaryData, BinaryData, GroupingData, LogicalData, ConditionalData, AssignData, UpdateData, VariableData, CallData, GetData, InterpolationData};
+use crate::function::NativeFunction;
+use crate::object::Callable;
+use crate::stmt::{Stmt, StmtVisitor};
+use crate::token::{Token, Type};
+
+/// Represents a lint rule that can be enabled or disabled individually.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Rule {
+    UnusedVariable,
+    UnusedParameter,
+    UnreachableCode,
+    ShadowedVariable,
+    SelfAssignment,
+    ConstantCondition,
+    ArityMismatch,
+}
+
+impl Rule {
+    pub const ALL: [Rule; 7] = [
+        Rule::UnusedVariable,
+        Rule::UnusedParameter,
+        Rule::UnreachableCode,
+        Rule::ShadowedVariable,
+        Rule::SelfAssignment,
+        Rule::ConstantCondition,
+        Rule::ArityMismatch,
+    ];
+
+    /// Returns the name of the rule used on the command line and in messages.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Rule::UnusedVariable    => "unused-variable",
+            Rule::UnusedParameter   => "unused-parameter",
+            Rule::UnreachableCode   => "unreachable-code",
+            Rule::ShadowedVariable  => "shadowed-variable",
+            Rule::SelfAssignment    => "self-assignment",
+            Rule::ConstantCondition => "constant-condition",
+            Rule::ArityMismatch     => "arity-mismatch",
+        }
+    }
+
+    /// Returns the rule with the given name.
+    pub fn from_name(name: &str) -> Option<Rule> {
+        Rule::ALL.into_iter().find(|rule| rule.name() == name)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Kind {
+    Variable,
+    Parameter,
+    Function(usize),
+}
+
+#[derive(Debug)]
+struct Binding {
+    token: Token,
+    kind: Kind,
+    used: bool,
+}
+
+/// Finds suspicious code in parsed statements.
+///
+/// Bindings at the top level are not reported as unused since they are visible to other runs,
+/// such as the following lines of the REPL. Names starting with an underscore are never reported
+/// as unused.
+pub struct Linter {
+    rules: HashSet<Rule>,
+    scopes: Vec<HashMap<String, Binding>>,
+    // Names that are assigned anywhere, calls to them can't be checked against a fixed arity.
+    assigned: HashSet<String>,
+    warnings: Vec<ParseError>,
+}
+
+impl Linter {
+    /// Creates a new linter with every rule enabled.
+    pub fn new() -> Self {
+        Linter {
+            rules: HashSet::from(Rule::ALL),
+            scopes: vec![],
+            assigned: HashSet::new(),
+            warnings: vec![],
+        }
+    }
+
+    pub fn enable(&mut self, rule: Rule) {
+        self.rules.insert(rule);
+    }
+
+    pub fn disable(&mut self, rule: Rule) {
+        self.rules.remove(&rule);
+    }
+
+    /// Lints the statements of a program and returns the warnings ordered by line.
+    pub fn lint(&mut self, statements: &[Stmt]) -> Vec<ParseError> {
+        self.assigned.clear();
+        statements.iter().for_each(|stmt| collect_assigned(stmt, &mut self.assigned));
+
+        let mut globals = HashMap::new();
+
+        for native in NativeFunction::get_globals() {
+            let binding = Binding { token: native.name.clone(), kind: Kind::Function(native.arity()), used: true };
+            globals.insert(native.name.lexeme.clone(), binding);
+        }
+
+        // Globals are late bound, functions can call the ones declared after them.
+        for stmt in statements {
+            if let Stmt::Function(data) = stmt {
+                let binding = Binding { token: data.name.clone(), kind: Kind::Function(data.params.len()), used: true };
+                globals.insert(data.name.lexeme.clone(), binding);
+            }
+        }
+
+        self.scopes = vec![globals];
+        self.statements(statements);
+        self.scopes.clear();
+
+        let mut warnings = std::mem::take(&mut self.warnings);
+        warnings.sort_by_key(|warning| warning.token.line);
+        warnings
+    }
+
+    fn warn(&mut self, rule: Rule, token: &Token, message: String) {
+        if self.rules.contains(&rule) {
+            self.warnings.push(ParseError {
+                token: token.clone(),
+                message: format!("{message} ({})", rule.name()),
+            });
+        }
+    }
+
+    fn statements(&mut self, statements: &[Stmt]) {
+        let mut returned: Option<&Token> = None;
+        let mut reported = false;
+
+        for stmt in statements {
+            if let (Some(keyword), false) = (returned, reported) {
+                let token = stmt.token().unwrap_or(keyword).clone();
+                self.warn(Rule::UnreachableCode, &token, "Unreachable code after return".to_string());
+                reported = true;
+            }
+
+            stmt.accept(self);
+
+            if let Stmt::Return(data) = stmt {
+                returned = returned.or(Some(&data.keyword));
+            }
+        }
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        let scope = self.scopes.pop().expect("scope to be open");
+
+        let mut unused: Vec<Binding> = scope
+            .into_values()
+            .filter(|binding| !binding.used && !binding.token.lexeme.starts_with('_'))
+            .collect();
+        unused.sort_by_key(|binding| binding.token.line);
+
+        for binding in unused {
+            match binding.kind {
+                Kind::Parameter => {
+                    let message = format!("Unused parameter '{}'", binding.token.lexeme);
+                    self.warn(Rule::UnusedParameter, &binding.token, message);
+                },
+                _ => {
+                    let message = format!("Unused variable '{}'", binding.token.lexeme);
+                    self.warn(Rule::UnusedVariable, &binding.token, message);
+                },
+            }
+        }
+    }
+
+    fn declare(&mut self, name: &Token, kind: Kind) {
+        // Top level declarations can't shadow anything.
+        if self.scopes.len() > 1 {
+            let enclosing = &self.scopes[..self.scopes.len() - 1];
+
+            if enclosing.iter().any(|scope| scope.contains_key(&name.lexeme)) {
+                let message = format!("'{}' shadows a declaration in an enclosing scope", name.lexeme);
+                self.warn(Rule::ShadowedVariable, name, message);
+            }
+        }
+
+        // Redeclaring a global keeps the hoisted function marked as used.
+        let used = self.scopes.len() == 1;
+        let scope = self.scopes.last_mut().expect("scope to be open");
+        scope.insert(name.lexeme.clone(), Binding { token: name.clone(), kind, used });
+    }
+
+    /// Finds the closest binding with the given name.
+    fn resolve(&mut self, name: &str) -> Option<&mut Binding> {
+        self.scopes.iter_mut().rev().find_map(|scope| scope.get_mut(name))
+    }
+
+    fn condition(&mut self, keyword: &Token, condition: &Expr) {
+        // `while (true)` is the idiomatic infinite loop, so is a for loop without a condition.
+        let is_loop = matches!(keyword.r#type, Type::While | Type::For);
+
+        match constant(condition) {
+            Some(true) if is_loop => (),
+            Some(value) => {
+                let message = format!("Condition is always {value}");
+                self.warn(Rule::ConstantCondition, keyword, message);
+            },
+            None => (),
+        }
+    }
+}
+
+impl Default for Linter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns the truthiness of the expression if it doesn't depend on anything.
+fn constant(expr: &Expr) -> Option<bool> {
+    match expr {
+        Expr::Literal(literal) => Some(literal.value.as_bool()),
+        Expr::Grouping(data) => constant(&data.expr),
+        Expr::Unary(data) if data.operator.r#type == Type::Bang => constant(&data.expr).map(|value| !value),
+        _ => None,
+    }
+}
+
+/// Collects the names of every assigned variable.
+fn collect_assigned(stmt: &Stmt, assigned: &mut HashSet<String>) {
+    fn walk(expr: &Expr, assigned: &mut HashSet<String>) {
+        match expr {
+            Expr::Literal(_) | Expr::Variable(_) => (),
+            Expr::Logical(data) => { walk(&data.left, assigned); walk(&data.right, assigned) },
+            Expr::Conditional(data) => {
+                walk(&data.condition, assigned);
+                walk(&data.then_branch, assigned);
+                walk(&data.else_branch, assigned);
+            },
+            Expr::Unary(data) => walk(&data.expr, assigned),
+            Expr::Binary(data) => { walk(&data.left, assigned); walk(&data.right, assigned) },
+            Expr::Grouping(data) => walk(&data.expr, assigned),
+            Expr::Assign(data) => {
+                assigned.insert(data.name.lexeme.clone());
+                walk(&data.value, assigned);
+            },
+            Expr::Update(data) => {
+                assigned.insert(data.name.lexeme.clone());
+                data.value.iter().for_each(|value| walk(value, assigned));
+            },
+            Expr::Call(data) => {
+                walk(&data.callee, assigned);
+                data.arguments.iter().for_each(|argument| walk(argument, assigned));
+            },
+            Expr::Get(data) => walk(&data.object, assigned),
+            Expr::Interpolation(data) => data.expressions.iter().for_each(|expr| walk(expr, assigned)),
+        }
+    }
+
+    match stmt {
+        Stmt::Expression(data) => walk(&data.expr, assigned),
+        Stmt::Function(data) => data.body.iter().for_each(|stmt| collect_assigned(stmt, assigned)),
+        Stmt::If(data) => {
+            walk(&data.condition, assigned);
+            collect_assigned(&data.then_branch, assigned);
+            if let Some(else_branch) = &data.else_branch {
+                collect_assigned(else_branch, assigned);
+            }
+        },
+        Stmt::Print(data) => walk(&data.expr, assigned),
+        Stmt::Return(data) => if let Some(value) = &data.value { walk(value, assigned) },
+        Stmt::Var(data) => if let Some(value) = &data.initializer { walk(value, assigned) },
+        Stmt::While(data) => {
+            walk(&data.condition, assigned);
+            collect_assigned(&data.body, assigned);
+        },
+        Stmt::Block(data) => data.statements.iter().for_each(|stmt| collect_assigned(stmt, assigned)),
+        Stmt::Import(_) => (),
+    }
+}
+
+impl ExprVisitor<()> for Linter {
+    fn visit_literal_expr(&mut self, _literal: &LiteralData) {}
+
+    fn visit_logical_expr(&mut self, logical: &LogicalData) {
+        logical.left.accept(self);
+        logical.right.accept(self);
+    }
+
+    fn visit_conditional_expr(&mut self, conditional: &ConditionalData) {
+        self.condition(&conditional.question, &conditional.condition);
+        conditional.condition.accept(self);
+        conditional.then_branch.accept(self);
+        conditional.else_branch.accept(self);
+    }
+
+    fn visit_unary_expr(&mut self, unary: &UnaryData) {
+        unary.expr.accept(self);
+    }
+
+    fn visit_binary_expr(&mut self, binary: &BinaryData) {
+        binary.left.accept(self);
+        binary.right.accept(self);
+    }
+
+    fn visit_grouping_expr(&mut self, grouping: &GroupingData) {
+        grouping.expr.accept(self);
+    }
+
+    fn visit_variable_expr(&mut self, variable: &VariableData) {
+        if let Some(binding) = self.resolve(&variable.name.lexeme) {
+            binding.used = true;
+        }
+    }
+
+    fn visit_assign_expr(&mut self, assign: &AssignData) {
+        if let Expr::Variable(value) = assign.value.as_ref() {
+            if value.name.lexeme == assign.name.lexeme {
+                let message = format!("'{}' is assigned to itself", assign.name.lexeme);
+                self.warn(Rule::SelfAssignment, &assign.name, message);
+            }
+        }
+
+        assign.value.accept(self);
+    }
+
+    fn visit_update_expr(&mut self, update: &UpdateData) {
+        // The variable is read as well as written, like in `i = i + 1`.
+        if let Some(binding) = self.resolve(&update.name.lexeme) {
+            binding.used = true;
+        }
+
+        if let Some(value) = &update.value {
+            value.accept(self);
+        }
+    }
+
+    fn visit_call_expr(&mut self, call: &CallData) {
+        call.callee.accept(self);
+        call.arguments.iter().for_each(|argument| argument.accept(self));
+
+        let Expr::Variable(callee) = call.callee.as_ref() else { return };
+
+        if self.assigned.contains(&callee.name.lexeme) {
+            return;
+        }
+
+        let Some(Binding { kind: Kind::Function(arity), .. }) = self.resolve(&callee.name.lexeme) else { return };
+        let arity = *arity;
+
+        if arity != call.arguments.len() {
+            let message = format!(
+                "'{}' expects {arity} arguments but is called with {}",
+                callee.name.lexeme,
+                call.arguments.len()
+            );
+            self.warn(Rule::ArityMismatch, &call.paren, message);
+        }
+    }
+
+    fn visit_get_expr(&mut self, get: &GetData) {
+        get.object.accept(self);
+    }
+
+    fn visit_interpolation_expr(&mut self, interpolation: &InterpolationData) {
+        interpolation.expressions.iter().for_each(|expr| expr.accept(self));
+    }
+}
+
+impl StmtVisitor<()> for Linter {
+    fn visit_expression_stmt(&mut self, stmt: &Stmt) {
+        let Stmt::Expression(data) = stmt else { unreachable!() };
+        data.expr.accept(self);
+    }
+
+    fn visit_function_stmt(&mut self, stmt: &Stmt) {
+        let Stmt::Function(data) = stmt else { unreachable!() };
+
+        // Declared before the body to allow recursion.
+        self.declare(&data.name, Kind::Function(data.params.len()));
+
+        self.begin_scope();
+        data.params.iter().for_each(|param| self.declare(param, Kind::Parameter));
+        self.statements(&data.body);
+        self.end_scope();
+    }
+
+    fn visit_if_stmt(&mut self, stmt: &Stmt) {
+        let Stmt::If(data) = stmt else { unreachable!() };
+
+        self.condition(&data.keyword, &data.condition);
+        data.condition.accept(self);
+        data.then_branch.accept(self);
+
+        if let Some(else_branch) = &data.else_branch {
+            else_branch.accept(self);
+        }
+    }
+
+    fn visit_print_stmt(&mut self, stmt: &Stmt) {
+        let Stmt::Print(data) = stmt else { unreachable!() };
+        data.expr.accept(self);
+    }
+
+    fn visit_return_stmt(&mut self, stmt: &Stmt) {
+        let Stmt::Return(data) = stmt else { unreachable!() };
+
+        if let Some(value) = &data.value {
+            value.accept(self);
+        }
+    }
+
+    fn visit_var_stmt(&mut self, stmt: &Stmt) {
+        let Stmt::Var(data) = stmt else { unreachable!() };
+
+        if let Some(initializer) = &data.initializer {
+            initializer.accept(self);
+        }
+
+        self.declare(&data.name, Kind::Variable);
+    }
+
+    fn visit_while_stmt(&mut self, stmt: &Stmt) {
+        let Stmt::While(data) = stmt else { unreachable!() };
+
+        self.condition(&data.keyword, &data.condition);
+        data.condition.accept(self);
+        data.body.accept(self);
+    }
+
+    fn visit_block_stmt(&mut self, stmt: &Stmt) {
+        let Stmt::Block(data) = stmt else { unreachable!() };
+
+        self.begin_scope();
+        self.statements(&data.statements);
+        self.end_scope();
+    }
+
+    fn visit_import_stmt(&mut self, stmt: &Stmt) {
+        let Stmt::Import(data) = stmt else { unreachable!() };
+        self.declare(&data.name, Kind::Variable);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::scanner::Scanner;
+    use crate::parser::Parser;
+
+    fn lint(source: &str) -> Vec<String> {
+        let tokens = Scanner::new(source.to_string()).scan_tokens();
+        let statements = Parser::new(tokens).parse();
+
+        Linter::new()
+            .lint(&statements)
+            .into_iter()
+            .map(|warning| format!("{}: {}", warning.token.line, warning.message))
+            .collect()
+    }
+
+    #[test]
+    fn unused() {
+        assert_eq!(lint("var global;\nfun f(a, b, _c) {\n  var x = a;\n}\n"), vec![
+            "2: Unused parameter 'b' (unused-parameter)",
+            "3: Unused variable 'x' (unused-variable)",
+        ]);
+    }
+
+    #[test]
+    fn unreachable() {
+        assert_eq!(lint("fun f() {\n  return 1;\n  print 2;\n  print 3;\n}\n"), vec![
+            "3: Unreachable code after return (unreachable-code)",
+        ]);
+    }
+
+    #[test]
+    fn shadowed() {
+        assert_eq!(lint("var a = 1;\n{\n  var a = 2;\n  print a;\n}\n"), vec![
+            "3: 'a' shadows a declaration in an enclosing scope (shadowed-variable)",
+        ]);
+    }
+
+    #[test]
+    fn self_assignment() {
+        assert_eq!(lint("var a = 1;\na = a;\n"), vec![
+            "2: 'a' is assigned to itself (self-assignment)",
+        ]);
+    }
+
+    #[test]
+    fn constant_condition() {
+        assert_eq!(lint("if (!null) print 1;\nwhile (true) {}\nwhile (false) {}\nfor (;;) {}\nprint true ? 1 : 2;\n"), vec![
+            "1: Condition is always true (constant-condition)",
+            "3: Condition is always false (constant-condition)",
+            "5: Condition is always true (constant-condition)",
+        ]);
+    }
+
+    #[test]
+    fn arity_mismatch() {
+        assert_eq!(lint("fun f() { g(1); }\nfun g(a, b) { print a + b; }\nclock(1);\ng(1, 2);\n"), vec![
+            "1: 'g' expects 2 arguments but is called with 1 (arity-mismatch)",
+            "3: 'clock' expects 0 arguments but is called with 1 (arity-mismatch)",
+        ]);
+    }
+
+    #[test]
+    fn arity_of_reassigned_function() {
+        assert!(lint("fun f(a) { print a; }\nf = clock;\nf();\n").is_empty());
+    }
+
+    #[test]
+    fn disabled_rule() {
+        let tokens = Scanner::new("var a = 1;\na = a;\n".to_string()).scan_tokens();
+        let statements = Parser::new(tokens).parse();
+
+        let mut linter = Linter::new();
+        linter.disable(Rule::SelfAssignment);
+
+        assert!(linter.lint(&statements).is_empty());
+    }
+
+    #[test]
+    fn rule_names() {
+        for rule in Rule::ALL {
+            assert_eq!(Rule::from_name(rule.name()), Some(rule));
+        }
+    }
+}
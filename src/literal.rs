@@ -4,19 +4,19 @@ use std::fmt;
 #[derive(Debug, PartialEq, Clone)]
 pub enum Literal {
     String(String),
-    Number(f32),
+    Number(f64),
     Bool(bool),
     Null,
 }
 
 impl Literal {
     /// Returns the literal as a number.
-    pub fn as_number(&self) -> f32 {
+    pub fn as_number(&self) -> f64 {
         match self {
             Literal::Number(n) => *n,
             Literal::Null => 0.0,
             Literal::Bool(b) => if *b { 1.0 } else { 0.0 },
-            Literal::String(s) => s.parse::<f32>().unwrap_or(0.0),
+            Literal::String(s) => s.parse::<f64>().unwrap_or(0.0),
         }
     }
 
@@ -42,8 +42,8 @@ impl From<String> for Literal {
     }
 }
 
-impl From<f32> for Literal {
-    fn from(n: f32) -> Self {
+impl From<f64> for Literal {
+    fn from(n: f64) -> Self {
         Literal::Number(n)
     }
 }
@@ -0,0 +1,938 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+use serde_json::{json, Value};
+
+use crate::error::{ParseError, ScanError};
+use crate::expr::{LiteralData, ExprVisitor, UnaryData, BinaryData, GroupingData, LogicalData, ConditionalData, AssignData, UpdateData, VariableData, CallData, GetData, InterpolationData};
+use crate::function::NativeFunction;
+use crate::parser::Parser;
+use crate::scanner::Scanner;
+use crate::stmt::{Stmt, StmtVisitor, FunctionData};
+use crate::token::{Token, Type};
+
+const KEYWORDS: [&str; 18] = [
+    "and", "as", "class", "else", "false", "for", "fun", "if", "import",
+    "null", "or", "print", "return", "super", "this", "true", "var", "while",
+];
+
+// Kinds from the specification.
+const SEVERITY_ERROR: u8 = 1;
+const COMPLETION_FUNCTION: u8 = 3;
+const COMPLETION_VARIABLE: u8 = 6;
+const COMPLETION_KEYWORD: u8 = 14;
+const SYMBOL_FUNCTION: u8 = 12;
+const PARSE_ERROR: i32 = -32700;
+const METHOD_NOT_FOUND: i32 = -32601;
+
+#[derive(Debug, PartialEq, Clone)]
+enum Kind {
+    Variable,
+    Parameter,
+    Function(Vec<String>),
+}
+
+/// Represents a declared name.
+#[derive(Debug)]
+struct Symbol {
+    name: Token,
+    kind: Kind,
+    doc: Option<String>,
+}
+
+impl Symbol {
+    /// Returns the declaration as it would be written in the source.
+    fn signature(&self) -> String {
+        match &self.kind {
+            Kind::Variable => format!("var {}", self.name.lexeme),
+            Kind::Parameter => format!("(parameter) {}", self.name.lexeme),
+            Kind::Function(params) => format!("fun {}({})", self.name.lexeme, params.join(", ")),
+        }
+    }
+}
+
+/// Resolves every name in a document to its declaration.
+#[derive(Debug, Default)]
+struct Index {
+    symbols: Vec<Symbol>,
+    // Every declaration and reference with the symbol it resolves to.
+    occurrences: Vec<(Token, usize)>,
+    // References to names that are not declared in the document, such as natives.
+    unresolved: Vec<Token>,
+    // The first symbols are the top level declarations.
+    globals: usize,
+    scopes: Vec<HashMap<String, usize>>,
+}
+
+impl Index {
+    fn new(statements: &[Stmt]) -> Self {
+        let mut index = Index::default();
+
+        // Globals are late bound, so they are visible before their declaration.
+        let mut globals = HashMap::new();
+
+        for stmt in statements {
+            let (name, kind, doc) = match stmt {
+                Stmt::Var(data) => (&data.name, Kind::Variable, &data.doc),
+                Stmt::Function(data) => (&data.name, Kind::Function(params(data)), &data.doc),
+                _ => continue,
+            };
+
+            if !globals.contains_key(&name.lexeme) {
+                globals.insert(name.lexeme.clone(), index.symbols.len());
+                index.symbols.push(Symbol { name: name.clone(), kind, doc: doc.clone() });
+            }
+        }
+
+        index.globals = index.symbols.len();
+        index.scopes.push(globals);
+        statements.iter().for_each(|stmt| stmt.accept(&mut index));
+        index.scopes.clear();
+
+        index
+    }
+
+    fn declare(&mut self, name: &Token, kind: Kind, doc: &Option<String>) {
+        let global = self.scopes.len() == 1;
+        let scope = self.scopes.last_mut().expect("scope to be open");
+
+        // Redeclaring a global refers to the same variable.
+        let symbol = match scope.get(&name.lexeme) {
+            Some(&symbol) if global => symbol,
+            _ => {
+                self.symbols.push(Symbol { name: name.clone(), kind, doc: doc.clone() });
+                scope.insert(name.lexeme.clone(), self.symbols.len() - 1);
+                self.symbols.len() - 1
+            },
+        };
+
+        self.occurrences.push((name.clone(), symbol));
+    }
+
+    fn reference(&mut self, name: &Token) {
+        let symbol = self.scopes.iter().rev().find_map(|scope| scope.get(&name.lexeme));
+
+        match symbol {
+            Some(&symbol) => self.occurrences.push((name.clone(), symbol)),
+            None => self.unresolved.push(name.clone()),
+        }
+    }
+
+    /// Returns the symbol of the name at the given position.
+    fn find(&self, line: usize, character: usize) -> Option<usize> {
+        self.occurrences
+            .iter()
+            .find(|(token, _)| contains(token, line, character))
+            .map(|(_, symbol)| *symbol)
+    }
+
+    /// Returns the top level declarations.
+    fn globals(&self) -> &[Symbol] {
+        &self.symbols[..self.globals]
+    }
+}
+
+/// Returns the declaration of the native, its parameters are only known by their number.
+fn native_signature(native: &NativeFunction) -> String {
+    let params: Vec<String> = (1..=native.arity).map(|index| format!("arg{index}")).collect();
+    format!("fun {}({})", native.name.lexeme, params.join(", "))
+}
+
+fn params(function: &FunctionData) -> Vec<String> {
+    function.params.iter().map(|param| param.lexeme.clone()).collect()
+}
+
+/// Returns if the token spans the given zero-based position.
+fn contains(token: &Token, line: usize, character: usize) -> bool {
+    let column = token.column as usize;
+
+    token.line == line + 1
+        && column <= character
+        && character <= column + token.lexeme.chars().count()
+}
+
+impl ExprVisitor<()> for Index {
+    fn visit_literal_expr(&mut self, _literal: &LiteralData) {}
+
+    fn visit_logical_expr(&mut self, logical: &LogicalData) {
+        logical.left.accept(self);
+        logical.right.accept(self);
+    }
+
+    fn visit_conditional_expr(&mut self, conditional: &ConditionalData) {
+        conditional.condition.accept(self);
+        conditional.then_branch.accept(self);
+        conditional.else_branch.accept(self);
+    }
+
+    fn visit_unary_expr(&mut self, unary: &UnaryData) {
+        unary.expr.accept(self);
+    }
+
+    fn visit_binary_expr(&mut self, binary: &BinaryData) {
+        binary.left.accept(self);
+        binary.right.accept(self);
+    }
+
+    fn visit_grouping_expr(&mut self, grouping: &GroupingData) {
+        grouping.expr.accept(self);
+    }
+
+    fn visit_variable_expr(&mut self, variable: &VariableData) {
+        self.reference(&variable.name);
+    }
+
+    fn visit_assign_expr(&mut self, assign: &AssignData) {
+        assign.value.accept(self);
+        self.reference(&assign.name);
+    }
+
+    fn visit_update_expr(&mut self, update: &UpdateData) {
+        if let Some(value) = &update.value {
+            value.accept(self);
+        }
+        self.reference(&update.name);
+    }
+
+    fn visit_call_expr(&mut self, call: &CallData) {
+        call.callee.accept(self);
+        call.arguments.iter().for_each(|argument| argument.accept(self));
+    }
+
+    fn visit_get_expr(&mut self, get: &GetData) {
+        get.object.accept(self);
+    }
+
+    fn visit_interpolation_expr(&mut self, interpolation: &InterpolationData) {
+        interpolation.expressions.iter().for_each(|expr| expr.accept(self));
+    }
+}
+
+impl StmtVisitor<()> for Index {
+    fn visit_expression_stmt(&mut self, stmt: &Stmt) {
+        let Stmt::Expression(data) = stmt else { unreachable!() };
+        data.expr.accept(self);
+    }
+
+    fn visit_function_stmt(&mut self, stmt: &Stmt) {
+        let Stmt::Function(data) = stmt else { unreachable!() };
+
+        self.declare(&data.name, Kind::Function(params(data)), &data.doc);
+
+        self.scopes.push(HashMap::new());
+        data.params.iter().for_each(|param| self.declare(param, Kind::Parameter, &None));
+        data.body.iter().for_each(|stmt| stmt.accept(self));
+        self.scopes.pop();
+    }
+
+    fn visit_if_stmt(&mut self, stmt: &Stmt) {
+        let Stmt::If(data) = stmt else { unreachable!() };
+
+        data.condition.accept(self);
+        data.then_branch.accept(self);
+
+        if let Some(else_branch) = &data.else_branch {
+            else_branch.accept(self);
+        }
+    }
+
+    fn visit_print_stmt(&mut self, stmt: &Stmt) {
+        let Stmt::Print(data) = stmt else { unreachable!() };
+        data.expr.accept(self);
+    }
+
+    fn visit_return_stmt(&mut self, stmt: &Stmt) {
+        let Stmt::Return(data) = stmt else { unreachable!() };
+
+        if let Some(value) = &data.value {
+            value.accept(self);
+        }
+    }
+
+    fn visit_var_stmt(&mut self, stmt: &Stmt) {
+        let Stmt::Var(data) = stmt else { unreachable!() };
+
+        if let Some(initializer) = &data.initializer {
+            initializer.accept(self);
+        }
+
+        self.declare(&data.name, Kind::Variable, &data.doc);
+    }
+
+    fn visit_while_stmt(&mut self, stmt: &Stmt) {
+        let Stmt::While(data) = stmt else { unreachable!() };
+
+        data.condition.accept(self);
+        data.body.accept(self);
+    }
+
+    fn visit_block_stmt(&mut self, stmt: &Stmt) {
+        let Stmt::Block(data) = stmt else { unreachable!() };
+
+        self.scopes.push(HashMap::new());
+        data.statements.iter().for_each(|stmt| stmt.accept(self));
+        self.scopes.pop();
+    }
+
+    fn visit_import_stmt(&mut self, stmt: &Stmt) {
+        let Stmt::Import(data) = stmt else { unreachable!() };
+        self.declare(&data.name, Kind::Variable, &None);
+    }
+}
+
+/// Represents an open document and what is known about it.
+struct Document {
+    statements: Vec<Stmt>,
+    index: Index,
+    diagnostics: Vec<Value>,
+    // Tokens count columns in characters, the client may count them in UTF-16 code units.
+    lines: Vec<String>,
+    utf16: bool,
+}
+
+impl Document {
+    fn new(text: &str, utf16: bool) -> Self {
+        let mut scanner = Scanner::new(text.to_string()).quiet();
+        let tokens = scanner.scan_tokens();
+        let mut parser = Parser::new(tokens).quiet();
+        let statements = parser.parse();
+
+        let mut document = Document {
+            index: Index::new(&statements),
+            statements,
+            diagnostics: vec![],
+            lines: text.split('\n').map(String::from).collect(),
+            utf16,
+        };
+
+        let mut diagnostics: Vec<Value> = scanner.errors
+            .iter()
+            .map(|error| document.scan_diagnostic(text, error))
+            .collect();
+        diagnostics.extend(parser.errors.iter().map(|error| document.parse_diagnostic(error)));
+        document.diagnostics = diagnostics;
+
+        document
+    }
+
+    /// Converts a column in characters on the one-based line to the encoding of the client.
+    fn encode(&self, line: usize, character: usize) -> usize {
+        let Some(text) = self.lines.get(line.saturating_sub(1)).filter(|_| self.utf16) else { return character };
+
+        // Columns past the end of the line, such as the end of a multi-line string, are kept as they are.
+        let units: usize = text.chars().take(character).map(char::len_utf16).sum();
+        units + character.saturating_sub(text.chars().count())
+    }
+
+    /// Converts a column in the encoding of the client on the zero-based line to characters.
+    fn decode(&self, line: usize, column: usize) -> usize {
+        let Some(text) = self.lines.get(line).filter(|_| self.utf16) else { return column };
+
+        let mut units = 0;
+        for (character, c) in text.chars().enumerate() {
+            if units >= column {
+                return character;
+            }
+            units += c.len_utf16();
+        }
+
+        text.chars().count() + column.saturating_sub(units)
+    }
+
+    fn position(&self, line: usize, character: usize) -> Value {
+        json!({ "line": line.saturating_sub(1), "character": self.encode(line, character) })
+    }
+
+    fn range(&self, token: &Token) -> Value {
+        let column = token.column as usize;
+
+        json!({
+            "start": self.position(token.line, column),
+            "end": self.position(token.line, column + token.lexeme.chars().count()),
+        })
+    }
+
+    fn scan_diagnostic(&self, text: &str, error: &ScanError) -> Value {
+        // The location is a byte offset in the whole source, the column is in characters on its line.
+        let before = &text[..error.location.min(text.len())];
+        let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+        let column = before[line_start..].chars().count();
+
+        json!({
+            "range": {
+                "start": self.position(error.line, column),
+                "end": self.position(error.line, column + 1),
+            },
+            "severity": SEVERITY_ERROR,
+            "source": "rlox",
+            "message": error.message,
+        })
+    }
+
+    fn parse_diagnostic(&self, error: &ParseError) -> Value {
+        let message = match error.token.r#type {
+            Type::EOF => format!("Error at end: {}", error.message),
+            _ => format!("Error at '{}': {}", error.token.lexeme, error.message),
+        };
+
+        json!({
+            "range": self.range(&error.token),
+            "severity": SEVERITY_ERROR,
+            "source": "rlox",
+            "message": message,
+        })
+    }
+
+    /// Returns the function declarations with the ones nested in them.
+    fn document_symbols(&self, statements: &[Stmt]) -> Vec<Value> {
+        let mut symbols = vec![];
+
+        for stmt in statements {
+            match stmt {
+                Stmt::Function(data) => symbols.push(json!({
+                    "name": data.name.lexeme,
+                    "detail": format!("fun {}({})", data.name.lexeme, params(data).join(", ")),
+                    "kind": SYMBOL_FUNCTION,
+                    "range": {
+                        "start": self.position(data.name.line, data.name.column as usize),
+                        "end": self.position(data.end.line, data.end.column as usize + 1),
+                    },
+                    "selectionRange": self.range(&data.name),
+                    "children": self.document_symbols(&data.body),
+                })),
+                Stmt::If(data) => {
+                    symbols.extend(self.document_symbols(std::slice::from_ref(&data.then_branch)));
+
+                    if let Some(else_branch) = &data.else_branch {
+                        symbols.extend(self.document_symbols(std::slice::from_ref(else_branch)));
+                    }
+                },
+                Stmt::While(data) => symbols.extend(self.document_symbols(std::slice::from_ref(&data.body))),
+                Stmt::Block(data) => symbols.extend(self.document_symbols(&data.statements)),
+                _ => (),
+            }
+        }
+
+        symbols
+    }
+}
+
+/// Reads the next message framed with a `Content-Length` header, or `None` at the end of the stream.
+///
+/// The debug adapter protocol uses the same framing.
+pub(crate) fn receive(reader: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut length = None;
+
+    loop {
+        let mut header = String::new();
+
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+
+        let header = header.trim_end();
+
+        if header.is_empty() {
+            break;
+        }
+
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+
+    let Some(length) = length else {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "missing Content-Length"));
+    };
+
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+
+    // A body that is not JSON is malformed, even when it ends early.
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+}
+
+/// Writes a message framed with a `Content-Length` header.
+pub(crate) fn send(writer: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+    writer.flush()
+}
+
+/// Language server speaking JSON-RPC over a pair of streams, usually stdin and stdout.
+///
+/// Documents are synchronized in full on every change.
+pub struct Server<R: BufRead, W: Write> {
+    reader: R,
+    writer: W,
+    documents: HashMap<String, Document>,
+    shutdown: bool,
+    // Positions are in UTF-16 code units unless the client accepts UTF-32.
+    utf16: bool,
+}
+
+impl<R: BufRead, W: Write> Server<R, W> {
+    pub fn new(reader: R, writer: W) -> Self {
+        Server { reader, writer, documents: HashMap::new(), shutdown: false, utf16: true }
+    }
+
+    /// Serves requests until the client exits and returns the exit code.
+    pub fn run(&mut self) -> i32 {
+        loop {
+            let message = match self.receive() {
+                Ok(Some(message)) => message,
+                // A malformed message is answered and skipped, the session goes on.
+                Err(error) if error.kind() == io::ErrorKind::InvalidData => {
+                    let response = json!({
+                        "jsonrpc": "2.0",
+                        "id": null,
+                        "error": { "code": PARSE_ERROR, "message": format!("Parse error: {error}") },
+                    });
+
+                    match self.send(response) {
+                        Ok(()) => continue,
+                        Err(_) => return 1,
+                    }
+                },
+                Ok(None) | Err(_) => return 1,
+            };
+
+            let method = message["method"].as_str().unwrap_or_default().to_string();
+
+            if method == "exit" {
+                return if self.shutdown { 0 } else { 1 };
+            }
+
+            let result = match message.get("id") {
+                Some(id) => {
+                    let response = match self.request(&method, &message["params"]) {
+                        Some(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                        None => json!({
+                            "jsonrpc": "2.0",
+                            "id": id,
+                            "error": { "code": METHOD_NOT_FOUND, "message": format!("Unknown method {method}") },
+                        }),
+                    };
+
+                    self.send(response)
+                },
+                None => self.notification(&method, &message["params"]),
+            };
+
+            if result.is_err() {
+                return 1;
+            }
+        }
+    }
+
+    fn receive(&mut self) -> io::Result<Option<Value>> {
+        receive(&mut self.reader)
+    }
+
+    fn send(&mut self, message: Value) -> io::Result<()> {
+        send(&mut self.writer, &message)
+    }
+
+    /// Handles a request and returns its result, or `None` if the method is not supported.
+    fn request(&mut self, method: &str, params: &Value) -> Option<Value> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let line = params["position"]["line"].as_u64().unwrap_or_default() as usize;
+        let character = params["position"]["character"].as_u64().unwrap_or_default() as usize;
+
+        let result = match method {
+            "initialize" => {
+                let encodings = params["capabilities"]["general"]["positionEncodings"].as_array();
+                self.utf16 = !encodings.is_some_and(|encodings| encodings.iter().any(|encoding| encoding == "utf-32"));
+
+                json!({
+                    "capabilities": {
+                        "positionEncoding": if self.utf16 { "utf-16" } else { "utf-32" },
+                        "textDocumentSync": 1,
+                        "definitionProvider": true,
+                        "referencesProvider": true,
+                        "hoverProvider": true,
+                        "completionProvider": {},
+                        "documentSymbolProvider": true,
+                    },
+                    "serverInfo": { "name": "rlox", "version": env!("CARGO_PKG_VERSION") },
+                })
+            },
+            "shutdown" => {
+                self.shutdown = true;
+                Value::Null
+            },
+            "textDocument/definition" => self.definition(uri, line, character),
+            "textDocument/references" => {
+                let declaration = params["context"]["includeDeclaration"].as_bool().unwrap_or(true);
+                self.references(uri, line, character, declaration)
+            },
+            "textDocument/hover" => self.hover(uri, line, character),
+            "textDocument/completion" => self.completion(uri),
+            "textDocument/documentSymbol" => match self.documents.get(uri) {
+                Some(document) => Value::from(document.document_symbols(&document.statements)),
+                None => Value::Null,
+            },
+            _ => return None,
+        };
+
+        Some(result)
+    }
+
+    fn notification(&mut self, method: &str, params: &Value) -> io::Result<()> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default().to_string();
+
+        match method {
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                self.documents.insert(uri.clone(), Document::new(text, self.utf16));
+                self.publish(&uri)
+            },
+            "textDocument/didChange" => {
+                let changes = params["contentChanges"].as_array();
+                let Some(text) = changes.and_then(|changes| changes.last()?["text"].as_str()) else {
+                    return Ok(());
+                };
+
+                self.documents.insert(uri.clone(), Document::new(text, self.utf16));
+                self.publish(&uri)
+            },
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                self.publish(&uri)
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /// Sends the diagnostics of the document, closed documents have none.
+    fn publish(&mut self, uri: &str) -> io::Result<()> {
+        let diagnostics = self.documents
+            .get(uri)
+            .map_or(vec![], |document| document.diagnostics.clone());
+
+        self.send(json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": { "uri": uri, "diagnostics": diagnostics },
+        }))
+    }
+
+    fn definition(&self, uri: &str, line: usize, character: usize) -> Value {
+        let Some(document) = self.documents.get(uri) else { return Value::Null };
+        let character = document.decode(line, character);
+        let Some(symbol) = document.index.find(line, character) else { return Value::Null };
+
+        json!({ "uri": uri, "range": document.range(&document.index.symbols[symbol].name) })
+    }
+
+    fn references(&self, uri: &str, line: usize, character: usize, declaration: bool) -> Value {
+        let Some(document) = self.documents.get(uri) else { return Value::Null };
+        let character = document.decode(line, character);
+        let Some(symbol) = document.index.find(line, character) else { return json!([]) };
+        let name = &document.index.symbols[symbol].name;
+
+        let locations: Vec<Value> = document.index.occurrences
+            .iter()
+            .filter(|(token, other)| *other == symbol && (declaration || token != name))
+            .map(|(token, _)| json!({ "uri": uri, "range": document.range(token) }))
+            .collect();
+
+        Value::from(locations)
+    }
+
+    fn hover(&self, uri: &str, line: usize, character: usize) -> Value {
+        let Some(document) = self.documents.get(uri) else { return Value::Null };
+        let character = document.decode(line, character);
+
+        let (signature, doc, token) = match document.index.find(line, character) {
+            Some(symbol) => {
+                let symbol = &document.index.symbols[symbol];
+                let token = document.index.occurrences
+                    .iter()
+                    .map(|(token, _)| token)
+                    .find(|token| contains(token, line, character))
+                    .expect("occurrence to exist");
+
+                (symbol.signature(), symbol.doc.clone(), token.clone())
+            },
+            None => {
+                // Natives are not declared in the document.
+                let natives = NativeFunction::get_globals();
+                let Some((token, native)) = document.index.unresolved.iter().find_map(|token| {
+                    let native = natives.iter().find(|native| native.name.lexeme == token.lexeme)?;
+                    contains(token, line, character).then_some((token, native))
+                }) else {
+                    return Value::Null;
+                };
+
+                (native_signature(native), None, token.clone())
+            },
+        };
+
+        json!({
+            "contents": { "kind": "markdown", "value": match doc {
+                Some(doc) => format!("```lox\n{signature}\n```\n\n{doc}"),
+                None => format!("```lox\n{signature}\n```"),
+            } },
+            "range": document.range(&token),
+        })
+    }
+
+    fn completion(&self, uri: &str) -> Value {
+        let mut items = vec![];
+
+        for native in NativeFunction::get_globals() {
+            items.push(json!({
+                "label": native.name.lexeme,
+                "kind": COMPLETION_FUNCTION,
+                "detail": native_signature(&native),
+            }));
+        }
+
+        if let Some(document) = self.documents.get(uri) {
+            for symbol in document.index.globals() {
+                let kind = match symbol.kind {
+                    Kind::Function(_) => COMPLETION_FUNCTION,
+                    _ => COMPLETION_VARIABLE,
+                };
+
+                items.push(json!({ "label": symbol.name.lexeme, "kind": kind, "detail": symbol.signature() }));
+            }
+        }
+
+        for keyword in KEYWORDS {
+            items.push(json!({ "label": keyword, "kind": COMPLETION_KEYWORD }));
+        }
+
+        Value::from(items)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const URI: &str = "file:///test.lox";
+
+    /// Runs the server over the messages and returns its exit code and everything it sent.
+    fn serve(messages: Vec<Value>) -> (i32, Vec<Value>) {
+        let input: String = messages
+            .iter()
+            .map(|message| {
+                let body = message.to_string();
+                format!("Content-Length: {}\r\n\r\n{body}", body.len())
+            })
+            .collect();
+
+        let mut output = vec![];
+        let code = Server::new(input.as_bytes(), &mut output).run();
+
+        let mut reader = output.as_slice();
+        let mut sent = vec![];
+
+        while let Ok(Some(message)) = receive(&mut reader) {
+            sent.push(message);
+        }
+
+        (code, sent)
+    }
+
+    fn open(text: &str) -> Value {
+        json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didOpen",
+            "params": { "textDocument": { "uri": URI, "languageId": "lox", "version": 1, "text": text } },
+        })
+    }
+
+    fn request(id: u64, method: &str, line: usize, character: usize) -> Value {
+        json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": {
+                "textDocument": { "uri": URI },
+                "position": { "line": line, "character": character },
+                "context": { "includeDeclaration": true },
+            },
+        })
+    }
+
+    fn result(sent: &[Value], id: u64) -> Value {
+        sent.iter().find(|message| message["id"] == id).expect("response to be sent")["result"].clone()
+    }
+
+    #[test]
+    fn lifecycle() {
+        let (code, sent) = serve(vec![
+            json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {} }),
+            json!({ "jsonrpc": "2.0", "method": "initialized", "params": {} }),
+            json!({ "jsonrpc": "2.0", "id": 2, "method": "unknown", "params": {} }),
+            json!({ "jsonrpc": "2.0", "id": 3, "method": "shutdown" }),
+            json!({ "jsonrpc": "2.0", "method": "exit" }),
+        ]);
+
+        assert_eq!(code, 0);
+        assert_eq!(result(&sent, 1)["capabilities"]["definitionProvider"], true);
+        assert_eq!(result(&sent, 1)["capabilities"]["positionEncoding"], "utf-16");
+        assert_eq!(sent[1]["error"]["code"], METHOD_NOT_FOUND);
+        assert_eq!(result(&sent, 3), Value::Null);
+    }
+
+    #[test]
+    fn malformed_messages() {
+        let shutdown = json!({ "jsonrpc": "2.0", "id": 1, "method": "shutdown" }).to_string();
+        let exit = json!({ "jsonrpc": "2.0", "method": "exit" }).to_string();
+        let input = format!(
+            "Content-Type: text\r\n\r\nContent-Length: 5\r\n\r\n{{oopsContent-Length: {}\r\n\r\n{shutdown}Content-Length: {}\r\n\r\n{exit}",
+            shutdown.len(),
+            exit.len(),
+        );
+
+        let mut output = vec![];
+        let code = Server::new(input.as_bytes(), &mut output).run();
+
+        let mut reader = output.as_slice();
+        let mut sent = vec![];
+        while let Ok(Some(message)) = receive(&mut reader) {
+            sent.push(message);
+        }
+
+        // Both bad frames are answered and the server keeps going until it is told to exit.
+        assert_eq!(code, 0);
+        assert_eq!(sent.len(), 3);
+        assert_eq!(sent[0]["error"]["code"], PARSE_ERROR);
+        assert_eq!(sent[0]["id"], Value::Null);
+        assert_eq!(sent[1]["error"]["code"], PARSE_ERROR);
+        assert_eq!(result(&sent, 1), Value::Null);
+    }
+
+    #[test]
+    fn exit_without_shutdown() {
+        let (code, _) = serve(vec![json!({ "jsonrpc": "2.0", "method": "exit" })]);
+        assert_eq!(code, 1);
+    }
+
+    #[test]
+    fn diagnostics() {
+        let (_, sent) = serve(vec![open("var a = 1;\nprint a +;\n\"open")]);
+
+        let diagnostics = &sent[0]["params"]["diagnostics"];
+        assert_eq!(sent[0]["method"], "textDocument/publishDiagnostics");
+        assert_eq!(diagnostics[0]["message"], "Unterminated string");
+        assert_eq!(diagnostics[0]["range"]["start"], json!({ "line": 2, "character": 0 }));
+        assert_eq!(diagnostics[1]["message"], "Error at ';': Expected expression");
+        assert_eq!(diagnostics[1]["range"]["start"], json!({ "line": 1, "character": 9 }));
+    }
+
+    #[test]
+    fn definition_and_references() {
+        let (_, sent) = serve(vec![
+            open("var a = 1;\nfun f(a) {\n  return a;\n}\nprint f(a);\n"),
+            request(1, "textDocument/definition", 2, 9),
+            request(2, "textDocument/references", 4, 8),
+            request(3, "textDocument/definition", 4, 6),
+        ]);
+
+        assert_eq!(result(&sent, 1)["range"]["start"], json!({ "line": 1, "character": 6 }));
+
+        let references = result(&sent, 2);
+        let lines: Vec<&Value> = references.as_array().unwrap().iter().map(|location| &location["range"]["start"]).collect();
+        assert_eq!(lines, vec![&json!({ "line": 0, "character": 4 }), &json!({ "line": 4, "character": 8 })]);
+
+        assert_eq!(result(&sent, 3)["range"]["start"], json!({ "line": 1, "character": 4 }));
+    }
+
+    #[test]
+    fn utf16_positions() {
+        // The emoji is one character but two UTF-16 code units.
+        let text = "var s = \"😀\"; var a = 1;\nprint a;\n";
+        let (_, sent) = serve(vec![
+            open(text),
+            request(1, "textDocument/definition", 1, 6),
+            request(2, "textDocument/hover", 0, 18),
+        ]);
+
+        assert_eq!(result(&sent, 1)["range"]["start"], json!({ "line": 0, "character": 18 }));
+        assert_eq!(result(&sent, 2)["range"], json!({
+            "start": { "line": 0, "character": 18 },
+            "end": { "line": 0, "character": 19 },
+        }));
+
+        // Clients that accept UTF-32 get positions in characters.
+        let (_, sent) = serve(vec![
+            json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "initialize",
+                "params": { "capabilities": { "general": { "positionEncodings": ["utf-16", "utf-32"] } } },
+            }),
+            open(text),
+            request(2, "textDocument/definition", 1, 6),
+        ]);
+
+        assert_eq!(result(&sent, 1)["capabilities"]["positionEncoding"], "utf-32");
+        assert_eq!(result(&sent, 2)["range"]["start"], json!({ "line": 0, "character": 17 }));
+    }
+
+    #[test]
+    fn hover() {
+        let (_, sent) = serve(vec![
+            open("fun add(a, b) { return a + b; }\nprint add(clock(), 1);\nprint doc(add);\n"),
+            request(1, "textDocument/hover", 1, 7),
+            request(2, "textDocument/hover", 1, 11),
+            request(3, "textDocument/hover", 1, 0),
+            request(4, "textDocument/hover", 2, 7),
+        ]);
+
+        assert_eq!(result(&sent, 1)["contents"]["value"], "```lox\nfun add(a, b)\n```");
+        assert_eq!(result(&sent, 2)["contents"]["value"], "```lox\nfun clock()\n```");
+        assert_eq!(result(&sent, 4)["contents"]["value"], "```lox\nfun doc(arg1)\n```");
+        assert_eq!(result(&sent, 3), Value::Null);
+    }
+
+    #[test]
+    fn hover_doc_comment() {
+        let (_, sent) = serve(vec![
+            open("/// Adds the numbers.\nfun add(a, b) { return a + b; }\n/// The sum.\nvar sum = add(1, 2);\nprint sum + /// Not attached.\n1;\n"),
+            request(1, "textDocument/hover", 3, 11),
+            request(2, "textDocument/hover", 4, 7),
+        ]);
+
+        assert_eq!(result(&sent, 1)["contents"]["value"], "```lox\nfun add(a, b)\n```\n\nAdds the numbers.");
+        assert_eq!(result(&sent, 2)["contents"]["value"], "```lox\nvar sum\n```\n\nThe sum.");
+    }
+
+    #[test]
+    fn completion() {
+        let (_, sent) = serve(vec![
+            open("var count = 0;\nfun f() { var local; }\n"),
+            request(1, "textDocument/completion", 1, 0),
+        ]);
+
+        let items = result(&sent, 1);
+        let labels: Vec<&str> = items.as_array().unwrap().iter().map(|item| item["label"].as_str().unwrap()).collect();
+
+        assert!(labels.contains(&"clock"));
+        assert!(labels.contains(&"count"));
+        assert!(labels.contains(&"f"));
+        assert!(labels.contains(&"while"));
+        assert!(!labels.contains(&"local"));
+    }
+
+    #[test]
+    fn document_symbols() {
+        let (_, sent) = serve(vec![
+            open("fun outer(a) {\n  fun inner() {}\n  return inner;\n}\n"),
+            request(1, "textDocument/documentSymbol", 0, 0),
+        ]);
+
+        let symbols = result(&sent, 1);
+        assert_eq!(symbols[0]["name"], "outer");
+        assert_eq!(symbols[0]["detail"], "fun outer(a)");
+        assert_eq!(symbols[0]["range"]["end"], json!({ "line": 3, "character": 1 }));
+        assert_eq!(symbols[0]["children"][0]["name"], "inner");
+    }
+}
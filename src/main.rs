@@ -1,10 +1,31 @@
-use std::{env, process};
+use std::{env, fs, io, process};
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 
+use ::rlox::{ast, coverage, dap, debugger, formatter, linter, lsp, profiler, tester, tracer};
+use ::rlox::error::{rloxError, RuntimeErrorKind};
+use ::rlox::scanner::Scanner;
+use ::rlox::parser::Parser;
+use ::rlox::stmt::Stmt;
 use rlox::rlox;
 
 fn main() {
     let args: Vec<String> = env::args().collect();
 
+    match args.get(1).map(String::as_str) {
+        Some("run") => process::exit(run(&args[2..])),
+        Some("test") => process::exit(test(&args[2..])),
+        Some("tokens") => process::exit(tokens(&args[2..])),
+        Some("ast") => process::exit(ast(&args[2..])),
+        Some("fmt") => process::exit(fmt(&args[2..])),
+        Some("lint") => process::exit(lint(&args[2..])),
+        Some("debug") => process::exit(debug(&args[2..])),
+        Some("dap") => process::exit(dap::Server::new(io::stdin().lock(), io::stdout()).run()),
+        Some("lsp") => process::exit(lsp::Server::new(io::stdin().lock(), io::stdout()).run()),
+        _ => (),
+    }
+
     let mut rlox = rlox::new();
 
     println!("rlox v{}", env!("CARGO_PKG_VERSION"));
@@ -12,6 +33,15 @@ fn main() {
     match args.len() {
         n if n > 2 => {
             println!("Usage: rlox [script]");
+            println!("       rlox run [--profile[=<file>]] [--coverage[=<file>]] [--trace[=<file>]] [--trace-filter=<functions>] <script>");
+            println!("       rlox test <files|directories>");
+            println!("       rlox tokens [--format text|json] <script>");
+            println!("       rlox ast [--format sexpr|json] <script>");
+            println!("       rlox fmt [--check] <files>");
+            println!("       rlox lint [--disable <rule>]... <files>");
+            println!("       rlox debug <script>");
+            println!("       rlox dap");
+            println!("       rlox lsp");
             process::exit(64);
         },
         2 => rlox.run_file(args[1].clone()),
@@ -19,3 +49,299 @@ fn main() {
     };
 }
 
+/// Reads and parses the script, reporting any errors.
+fn load(path: &str) -> Option<(String, Vec<Stmt>)> {
+    let source = fs::read_to_string(path)
+        .expect("Should have been able to read the file");
+
+    let mut scanner = Scanner::new(source.clone());
+    let tokens = scanner.scan_tokens();
+    let mut parser = Parser::new(tokens);
+    let statements = parser.parse();
+
+    if !scanner.errors.is_empty() || !parser.errors.is_empty() {
+        return None;
+    }
+
+    Some((source, statements))
+}
+
+/// Runs the script, instrumented by the given options.
+fn run(args: &[String]) -> i32 {
+    let usage = "Usage: rlox run [--profile[=<file>]] [--coverage[=<file>]] [--trace[=<file>]] [--trace-filter=<functions>] <script>";
+
+    let mut path = None;
+    let mut profile = None;
+    let mut coverage = None;
+    let mut trace = None;
+    let mut filter = vec![];
+
+    for arg in args {
+        match arg.as_str() {
+            "--profile" => profile = Some("profile.folded"),
+            arg if arg.starts_with("--profile=") => profile = Some(&arg["--profile=".len()..]),
+            "--coverage" => coverage = Some("lcov.info"),
+            arg if arg.starts_with("--coverage=") => coverage = Some(&arg["--coverage=".len()..]),
+            "--trace" => trace = Some(None),
+            arg if arg.starts_with("--trace=") => trace = Some(Some(&arg["--trace=".len()..])),
+            arg if arg.starts_with("--trace-filter=") => {
+                filter = arg["--trace-filter=".len()..].split(',').map(String::from).collect();
+            },
+            arg if !arg.starts_with("--") && path.is_none() => path = Some(arg),
+            _ => {
+                println!("{usage}");
+                return 64;
+            },
+        }
+    }
+
+    let Some(path) = path else {
+        println!("{usage}");
+        return 64;
+    };
+
+    let Some((source, statements)) = load(path) else { return 65 };
+
+    let mut interpreter = ::rlox::script_interpreter(Path::new(path));
+
+    // The trace goes to stderr unless a file is given.
+    if let Some(file) = trace {
+        let output: Box<dyn io::Write> = match file {
+            Some(file) => Box::new(fs::File::create(file).expect("Should have been able to create the trace")),
+            None => Box::new(io::stderr()),
+        };
+
+        interpreter.add_hook(Box::new(tracer::Tracer::new(output, &source).only(filter)));
+    }
+
+    let recorded = profile.map(|_| {
+        let recorded = Rc::new(RefCell::new(profiler::Profile::default()));
+        interpreter.add_hook(Box::new(profiler::Profiler::new(Rc::clone(&recorded))));
+        recorded
+    });
+
+    let covered = coverage.map(|_| {
+        let covered = Rc::new(RefCell::new(coverage::Coverage::new(&statements)));
+        interpreter.add_hook(Box::new(coverage::Collector::new(Rc::clone(&covered))));
+        covered
+    });
+
+    let code = match interpreter.interpret(&statements) {
+        Ok(_) => 0,
+        Err(error) => {
+            error.throw();
+            70
+        },
+    };
+
+    // The report goes to stderr to keep it apart from the output of the script.
+    if let (Some(file), Some(recorded)) = (profile, recorded) {
+        let recorded = recorded.borrow();
+        eprint!("{}", recorded.report());
+        fs::write(file, recorded.folded()).expect("Should have been able to write the profile");
+        eprintln!("Wrote folded stacks to {file}");
+    }
+
+    if let (Some(file), Some(covered)) = (coverage, covered) {
+        fs::write(file, covered.borrow().lcov(path)).expect("Should have been able to write the coverage");
+        eprintln!("Wrote coverage to {file}");
+    }
+
+    code
+}
+
+/// Runs the Lox scripts against the expectations in their comments and prints a summary.
+fn test(args: &[String]) -> i32 {
+    if args.is_empty() {
+        println!("Usage: rlox test <files|directories>");
+        return 64;
+    }
+
+    let paths: Vec<PathBuf> = args.iter().map(PathBuf::from).collect();
+    let scripts = tester::collect(&paths).expect("Should have been able to read the directories");
+    let interpreter = env::current_exe().expect("Should have been able to find the interpreter");
+
+    let mut failed = 0;
+
+    for script in &scripts {
+        let failures = tester::run(&interpreter, script).expect("Should have been able to run the script");
+
+        if failures.is_empty() {
+            println!("PASS {}", script.display());
+        } else {
+            println!("FAIL {}", script.display());
+            failures.iter().for_each(|failure| println!("  {failure}"));
+            failed += 1;
+        }
+    }
+
+    println!("\n{} passed, {failed} failed", scripts.len() - failed);
+
+    if failed > 0 { 1 } else { 0 }
+}
+
+/// Returns the format given with `--format`, the first of the formats by default, and the script.
+fn dump_args<'a>(args: &'a [String], formats: &[&'a str]) -> Option<(&'a str, &'a str)> {
+    match args {
+        [path] => Some((formats[0], path)),
+        [flag, format, path] if flag == "--format" && formats.contains(&format.as_str()) => Some((format, path)),
+        _ => None,
+    }
+}
+
+/// Prints the tokens of the script.
+fn tokens(args: &[String]) -> i32 {
+    let Some((format, path)) = dump_args(args, &["text", "json"]) else {
+        println!("Usage: rlox tokens [--format text|json] <script>");
+        return 64;
+    };
+
+    let source = fs::read_to_string(path)
+        .expect("Should have been able to read the file");
+
+    let mut scanner = Scanner::new(source);
+    let tokens = scanner.scan_tokens();
+
+    if !scanner.errors.is_empty() {
+        return 65;
+    }
+
+    if format == "json" {
+        let tokens: Vec<_> = tokens.iter().map(ast::token_json).collect();
+        println!("{}", serde_json::to_string_pretty(&tokens).unwrap());
+        return 0;
+    }
+
+    for token in tokens {
+        let literal = token.literal.as_ref().map(|literal| format!(" {literal}")).unwrap_or_default();
+        println!("{}:{} {:?} {}{literal}", token.line, token.column, token.r#type, token.lexeme);
+    }
+
+    0
+}
+
+/// Prints the syntax tree of the script.
+fn ast(args: &[String]) -> i32 {
+    let Some((format, path)) = dump_args(args, &["sexpr", "json"]) else {
+        println!("Usage: rlox ast [--format sexpr|json] <script>");
+        return 64;
+    };
+
+    let Some((_, statements)) = load(path) else { return 65 };
+
+    if format == "json" {
+        println!("{}", serde_json::to_string_pretty(&ast::JSONPrinter.print(&statements)).unwrap());
+    } else {
+        print!("{}", ast::SExprPrinter.print(&statements));
+    }
+
+    0
+}
+
+/// Formats the files in place, or only reports the unformatted ones with `--check`.
+fn fmt(args: &[String]) -> i32 {
+    let check = args.iter().any(|arg| arg == "--check");
+    let paths: Vec<&String> = args.iter().filter(|arg| *arg != "--check").collect();
+
+    if paths.is_empty() {
+        println!("Usage: rlox fmt [--check] <files>");
+        return 64;
+    }
+
+    let mut code = 0;
+
+    for path in paths {
+        let source = fs::read_to_string(path)
+            .expect("Should have been able to read the file");
+
+        let Some(formatted) = formatter::format(&source) else {
+            println!("Could not format {path}");
+            code = 65;
+            continue;
+        };
+
+        if formatted == source {
+            continue;
+        }
+
+        if check {
+            println!("Would reformat {path}");
+            code = code.max(1);
+        } else {
+            fs::write(path, formatted).expect("Should have been able to write the file");
+        }
+    }
+
+    code
+}
+
+/// Reports suspicious code in the files, skipping the rules given with `--disable`.
+fn lint(args: &[String]) -> i32 {
+    let mut linter = linter::Linter::new();
+    let mut paths = vec![];
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        if arg != "--disable" {
+            paths.push(arg);
+            continue;
+        }
+
+        match args.next().and_then(|name| linter::Rule::from_name(name)) {
+            Some(rule) => linter.disable(rule),
+            None => {
+                let names: Vec<&str> = linter::Rule::ALL.iter().map(|rule| rule.name()).collect();
+                println!("Unknown rule, expected one of: {}", names.join(", "));
+                return 64;
+            },
+        }
+    }
+
+    if paths.is_empty() {
+        println!("Usage: rlox lint [--disable <rule>]... <files>");
+        return 64;
+    }
+
+    let mut code = 0;
+
+    for path in paths {
+        println!("{path}");
+
+        let Some((_, statements)) = load(path) else {
+            code = 65;
+            continue;
+        };
+
+        let warnings = linter.lint(&statements);
+        warnings.iter().for_each(|warning| warning.throw());
+
+        if !warnings.is_empty() {
+            code = 65;
+        }
+    }
+
+    code
+}
+
+/// Runs the script under the debugger, paused before the first statement.
+fn debug(args: &[String]) -> i32 {
+    let [path] = args else {
+        println!("Usage: rlox debug <script>");
+        return 64;
+    };
+
+    let Some((source, statements)) = load(path) else { return 65 };
+
+    let console = debugger::Console::new(io::stdin().lock(), io::stdout(), &source);
+    let mut interpreter = ::rlox::script_interpreter(Path::new(path));
+    interpreter.add_hook(Box::new(debugger::Debugger::new(Box::new(console))));
+
+    match interpreter.interpret(&statements) {
+        Ok(_) => 0,
+        Err(error) if error.kind == RuntimeErrorKind::Interrupted => 0,
+        Err(error) => {
+            error.throw();
+            70
+        },
+    }
+}
@@ -1,29 +1,44 @@
+use std::cell::RefCell;
 use std::fmt;
+use std::rc::Rc;
 
+use crate::environment::Environment;
 use crate::error::RuntimeError;
 use crate::function::{Function, NativeFunction};
 use crate::literal::Literal;
 use crate::interpreter::Interpreter;
+use crate::token::Token;
 
 #[derive(Debug, Clone)]
 pub enum Object {
     Literal(Literal),
-    Function(Function),
+    Function(Rc<Function>),
     NativeFunction(NativeFunction),
+    Module(Rc<Module>),
+}
+
+/// Represents an imported module, exposing the top level bindings of its script.
+#[derive(Debug)]
+pub struct Module {
+    /// The path of the module as written in the import.
+    pub name: String,
+    pub environment: Rc<RefCell<Environment>>,
 }
 
 impl Object {
-    pub fn as_number(&self) -> f32 {
+    /// Returns the object as a number, or an error at the operator for functions and modules.
+    pub fn as_number(&self, operator: &Token, message: &str) -> Result<f64, RuntimeError> {
         match self {
-            Object::Literal(literal) => literal.as_number(),
-            _ => panic!("Cannot convert object to number"),
+            Object::Literal(literal) => Ok(literal.as_number()),
+            _ => Err(RuntimeError::new(operator.clone(), message.to_string())),
         }
     }
 
+    /// Returns the truthiness of the object, functions and modules are always truthy.
     pub fn as_bool(&self) -> bool {
         match self {
             Object::Literal(literal) => literal.as_bool(),
-            _ => panic!("Cannot convert object to bool"),
+            _ => true,
         }
     }
 }
@@ -34,8 +49,8 @@ impl From<Literal> for Object {
     }
 }
 
-impl From<f32> for Object {
-    fn from(number: f32) -> Self {
+impl From<f64> for Object {
+    fn from(number: f64) -> Self {
         Object::Literal(Literal::Number(number))
     }
 }
@@ -60,7 +75,7 @@ impl From<&str> for Object {
 
 impl From<Function> for Object {
     fn from(value: Function) -> Self {
-        Object::Function(value)
+        Object::Function(Rc::new(value))
     }
 }
 
@@ -85,6 +100,7 @@ impl fmt::Display for Object {
             Object::Literal(literal) => write!(f, "{literal}"),
             Object::Function(function) => write!(f, "{function}"),
             Object::NativeFunction(function) => write!(f, "{function}"),
+            Object::Module(module) => write!(f, "<module {}>", module.name),
         }
     }
 }
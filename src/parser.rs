@@ -1,8 +1,10 @@
+use std::collections::VecDeque;
+
 use crate::error::{rloxError, ParseError};
 use crate::token::{Token, Type};
 use crate::literal::Literal;
-use crate::expr::{Expr, BinaryData, UnaryData, GroupingData, VariableData, AssignData, LogicalData, CallData};
-use crate::stmt::{Stmt, PrintData, ExpressionData, VarData, WhileData, BlockData, IfData, ReturnData, FunctionData};
+use crate::expr::{LiteralData, Expr, BinaryData, UnaryData, GroupingData, VariableData, AssignData, UpdateData, LogicalData, ConditionalData, CallData, GetData, InterpolationData};
+use crate::stmt::{Stmt, PrintData, ExpressionData, VarData, WhileData, BlockData, IfData, ReturnData, FunctionData, ImportData};
 
 type ParseResult<T> = Result<T, ParseError>;
 
@@ -23,7 +25,7 @@ macro_rules! matches {
 /// Parses the tokens and returns the resulting expression.
 ///
 /// - Program     -> Decleration* EOF ;
-/// - Decleration -> FunDecl | VarDecl | Statement ;
+/// - Decleration -> FunDecl | VarDecl | ImportDecl | Statement ;
 /// - Statement   -> ExprStmt | ForStmt | IfStmt | PrintStmt | ReturnStmt | WhileStmt | Block ;
 /// - ForStmt     -> "for" "(" ( Decleration | ExprStmt | ";" ) Expression? ";" Expression? ")" Statement ;
 /// - ReturnStmt  -> "return" Expression? ";" ;
@@ -34,33 +36,71 @@ macro_rules! matches {
 /// - Function    -> IDENTIFIER "(" Parameters? ")" Block ;
 /// - Parameters  -> IDENTIFIER ( "," IDENTIFIER )* ;
 /// - VarDecl     -> "var" IDENTIFIER ( "=" Expression )? ";" ;
+/// - ImportDecl  -> "import" STRING "as" IDENTIFIER ";" ;
 /// - ExprStmt    -> Expression ";" ;
 /// - PrintStmt   -> "print" Expression ";" ;
-/// - Expression  -> Assignment ;
-/// - Assignment  -> IDENTIFIER "=" Assignment | LogicOr ;
+/// - Expression  -> Comma ;
+/// - Comma       -> Assignment ( "," Assignment )* ;
+/// - Assignment  -> IDENTIFIER ( "=" | "+=" | "-=" | "*=" | "/=" | "%=" ) Assignment | Conditional ;
+/// - Conditional -> Coalesce ( "?" Expression ":" Conditional )? ;
+/// - Coalesce    -> LogicOr ( "??" LogicOr )* ;
 /// - LogicOr     -> LogicAnd ( "or" LogicAnd )* ;
 /// - LogicAnd    -> Equality ( "and" Equality )* ;
 /// - Equality    -> Comparison ( ( "!=" | "==" ) Comparison )* ;
-/// - Comparison  -> Term ( ( ">" | ">=" | "<" | "<=" ) Term )* ;
+/// - Comparison  -> BitOr ( ( ">" | ">=" | "<" | "<=" ) BitOr )* ;
+/// - BitOr       -> BitXor ( "|" BitXor )* ;
+/// - BitXor      -> BitAnd ( "^" BitAnd )* ;
+/// - BitAnd      -> Shift ( "&" Shift )* ;
+/// - Shift       -> Term ( ( "<<" | ">>" ) Term )* ;
 /// - Term        -> Factor ( ( "+" | "-" ) Factor )* ;
-/// - Factor      -> Unary ( ( "*" | "/" ) Unary )* ;
-/// - Unary       -> ( "!" | "-" ) Unary | Primary ;
-/// - Arguments   -> Expression ( "," Expression )* ;
-/// - Call        -> Primary ( "(" Arguments? ")" )* ;
-/// - Primary     -> NUMBER | STRING | false | true | null | "(" Expression ")" | IDENTIFIER ;
+/// - Factor      -> Unary ( ( "*" | "/" | "%" | "~/" ) Unary )* ;
+/// - Unary       -> ( "!" | "-" | "~" ) Unary | ( "++" | "--" ) IDENTIFIER | Power ;
+/// - Power       -> Postfix ( "**" Unary )? ;
+/// - Postfix     -> Call ( "++" | "--" )? ;
+/// - Arguments   -> Assignment ( "," Assignment )* ;
+/// - Call        -> Primary ( ( "(" | "?." "(" ) Arguments? ")" | "." IDENTIFIER )* ;
+/// - Primary     -> NUMBER | STRING | Interpolation | false | true | null | "(" Expression ")" | IDENTIFIER ;
+/// - Interpolation -> ( INTERPOLATION Expression )+ STRING ;
+///
+/// Tokens are pulled from their source as they are needed, a scanner can be parsed while it
+/// scans. Doc comments are kept with the token that follows them, those written before a `fun` or
+/// `var` declaration end up in it, the others are ignored.
 pub struct Parser {
-    tokens: Vec<Token>,
-    current: u32,
+    tokens: Box<dyn Iterator<Item = Token>>,
+    // Tokens pulled from the source but not consumed yet, the next one first, with their doc.
+    lookahead: VecDeque<(Token, Option<String>)>,
+    previous: Option<Token>,
+    // Doc comment written before the previous token.
+    doc: Option<String>,
+    quiet: bool,
+    // How many function bodies enclose the current token.
+    functions: usize,
+    pub errors: Vec<ParseError>,
 }
 
 impl Parser {
-    pub fn new(tokens: Vec<Token>) -> Self {
+    pub fn new<I>(tokens: I) -> Self
+    where
+        I: IntoIterator<Item = Token>,
+        I::IntoIter: 'static,
+    {
         Parser {
-            tokens,
-            current: 0,
+            tokens: Box::new(tokens.into_iter()),
+            lookahead: VecDeque::new(),
+            previous: None,
+            doc: None,
+            quiet: false,
+            functions: 0,
+            errors: vec![],
         }
     }
 
+    /// Keeps the errors without reporting them, for callers that own the output.
+    pub fn quiet(mut self) -> Self {
+        self.quiet = true;
+        self
+    }
+
     /// Parses the tokens and returns the resulting expression.
     pub fn parse(&mut self) -> Vec<Stmt> {
         let mut statements = Vec::new();
@@ -74,14 +114,48 @@ impl Parser {
         statements
     }
 
+    /// Reports the error and keeps it for the caller.
+    fn error(&mut self, error: ParseError) {
+        if !self.quiet {
+            error.throw();
+        }
+
+        self.errors.push(error);
+    }
+
     /// Returns the next token without consuming it.
     fn peek(&mut self) -> &Token {
-        &self.tokens[self.current as usize]
+        if self.lookahead.is_empty() {
+            let mut doc = vec![];
+
+            let token = loop {
+                match self.tokens.next() {
+                    Some(token) if token.r#type == Type::DocComment => {
+                        doc.push(token.literal.map(|text| text.to_string()).unwrap_or_default());
+                    },
+                    Some(token) => break token,
+                    // Sources that end without an end of file token are ended on the last line.
+                    None => {
+                        let line = self.previous.as_ref().map_or(1, |token| token.line);
+                        break Token::new(Type::EOF, String::new(), None, line);
+                    },
+                }
+            };
+
+            let doc = (!doc.is_empty()).then(|| doc.join("\n"));
+            self.lookahead.push_back((token, doc));
+        }
+
+        &self.lookahead[0].0
     }
 
-    /// Returns the previous token without consuming it.
+    /// Returns the previous token without consuming it, the next one before any is consumed.
     fn previous(&mut self) -> &Token {
-        &self.tokens[(self.current - 1) as usize]
+        if self.previous.is_none() {
+            return self.peek();
+        }
+
+        self.previous.as_ref().unwrap()
     }
 
     /// Returns if the parser has reached the end of the file.
@@ -101,7 +175,10 @@ impl Parser {
     /// Consumes the next token and returns it.
     fn advance(&mut self) -> &Token {
         if !self.is_at_end() {
-            self.current += 1;
+            if let Some((token, doc)) = self.lookahead.pop_front() {
+                self.previous = Some(token);
+                self.doc = doc;
+            }
         }
 
         self.previous()
@@ -125,6 +202,8 @@ impl Parser {
             self.function("function")
         } else if matches!(self, Type::Var) {
             self.var_decleration()
+        } else if matches!(self, Type::Import) {
+            self.import_decleration()
         } else {
             self.statement()
         };
@@ -132,7 +211,7 @@ impl Parser {
         match statement {
             Ok(stmt) => Some(stmt),
             Err(error) => {
-                error.throw();
+                self.error(error);
                 self.synchronize();
                 None
             }
@@ -141,6 +220,7 @@ impl Parser {
 
     /// Parses a variable decleration.
     fn var_decleration(&mut self) -> ParseResult<Stmt> {
+        let doc = self.doc.take();
         let name = self.consume(Type::Identifier, "Expect variable name")?.clone();
 
         let mut initializer: Option<Expr> = None;
@@ -152,17 +232,30 @@ impl Parser {
         }
 
         self.consume(Type::Semicolon, "Expect ';' after variable decleration")?;
-        Ok(Stmt::Var(VarData { name, initializer }))
+        Ok(Stmt::Var(VarData { name, initializer, doc }))
+    }
+
+    /// Parses an import decleration.
+    fn import_decleration(&mut self) -> ParseResult<Stmt> {
+        let keyword = self.previous().to_owned();
+        let path = self.consume(Type::String, "Expect module path after 'import'")?.clone();
+        self.consume(Type::As, "Expect 'as' after module path")?;
+        let name = self.consume(Type::Identifier, "Expect module name after 'as'")?.clone();
+        self.consume(Type::Semicolon, "Expect ';' after import")?;
+
+        Ok(Stmt::Import(ImportData { keyword, path, name }))
     }
 
     /// Parses a while statement.
     fn while_statement(&mut self) -> ParseResult<Stmt> {
+        let keyword = self.previous().to_owned();
         self.consume(Type::LeftParen, "Expect '(' after while.")?;
         let condition = self.expression()?;
         self.consume(Type::RightParen, "Expect ')' after condition.")?;
         let body = self.statement()?;
 
         Ok(Stmt::While(WhileData {
+            keyword,
             condition,
             body: Box::new(body),
         }))
@@ -170,7 +263,7 @@ impl Parser {
 
     /// Parses an expression.
     fn expression(&mut self) -> ParseResult<Expr> {
-        self.assignment()
+        self.comma()
     }
 
     /// Parses a statement.
@@ -196,7 +289,10 @@ impl Parser {
         }
 
         if matches!(self, Type::LeftBrace) {
-            return Ok(Stmt::Block(BlockData { statements: self.block()? }));
+            let start = self.previous().to_owned();
+            let statements = self.block()?;
+            let end = self.previous().to_owned();
+            return Ok(Stmt::Block(BlockData { start, statements, end }));
         }
 
         self.expression_statement()
@@ -204,6 +300,7 @@ impl Parser {
 
     /// Parses a for statement.
     fn for_statement(&mut self) -> ParseResult<Stmt> {
+        let keyword = self.previous().to_owned();
         self.consume(Type::LeftParen, "Expect '(' after 'for'")?;
 
         let initializer: Option<Stmt>;
@@ -227,42 +324,38 @@ impl Parser {
         };
         self.consume(Type::RightParen, "Expect ')' after loop clauses")?;
 
-        let mut body = self.statement()?;
+        let body = self.statement()?;
+
+        // The loop is always desugared into the same shape so that tools such as the formatter can
+        // recover the original for statement from the keyword of the while statement:
+        // { initializer? while (condition) { body increment? } }
+        let mut statements = vec![body];
 
         // Execute the increment after the body.
         if let Some(increment) = increment {
-            body = Stmt::Block(BlockData {
-                statements: vec![
-                    body,
-                    Stmt::Expression(ExpressionData {
-                        expr: increment
-                    }),
-                ],
-            });
+            statements.push(Stmt::Expression(ExpressionData { expr: increment }));
         }
 
         // Wrap the body into a while loop.
         // If there is no condition, use true.
-        body = Stmt::While(WhileData {
-            condition: condition.unwrap_or(Expr::Literal(Literal::Bool(true))),
-            body: Box::new(body),
+        let body = Stmt::While(WhileData {
+            keyword: keyword.clone(),
+            condition: condition.unwrap_or(Expr::Literal(Literal::Bool(true).into())),
+            body: Box::new(Stmt::Block(BlockData { start: keyword.clone(), statements, end: keyword.clone() })),
         });
 
         // Add the initializer before the loop if there is one.
-        if let Some(initializer) = initializer {
-            body = Stmt::Block(BlockData {
-                statements: vec![
-                    initializer,
-                    body,
-                ],
-            });
-        }
+        let statements = match initializer {
+            Some(initializer) => vec![initializer, body],
+            None => vec![body],
+        };
 
-        Ok(body)
+        Ok(Stmt::Block(BlockData { start: keyword.clone(), statements, end: keyword }))
     }
 
     /// Parses an if statement.
     fn if_statement(&mut self) -> ParseResult<Stmt> {
+        let keyword = self.previous().to_owned();
         self.consume(Type::LeftParen, "Expect '(' after 'if'")?;
         let condition = self.expression()?;
         self.consume(Type::RightParen, "Expect ')' after if condition")?;
@@ -273,25 +366,30 @@ impl Parser {
             else_branch = Some(Box::new(self.statement()?));
         }
 
-        Ok(Stmt::If(IfData { condition, then_branch, else_branch }))
+        Ok(Stmt::If(IfData { keyword, condition, then_branch, else_branch }))
     }
 
     /// Parses a print statement.
     fn print_statement(&mut self) -> ParseResult<Stmt> {
-        let expr = match self.expression() {
-            Ok(expr) => expr,
-            Err(error) => return Err(error),
-        };
+        let keyword = self.previous().to_owned();
+        let expr = self.expression()?;
 
         self.consume(Type::Semicolon, "Expect ';' after value")?;
 
-        Ok(Stmt::Print(PrintData { expr }))
+        Ok(Stmt::Print(PrintData { keyword, expr }))
     }
 
     /// Parses a return statement.
     fn return_statement(&mut self) -> ParseResult<Stmt> {
         let keyword = self.previous().to_owned();
 
+        if self.functions == 0 {
+            self.error(ParseError {
+                token: keyword.clone(),
+                message: "Can't return from top-level code.".to_string()
+            });
+        }
+
         let value = match self.check(Type::Semicolon) {
             true => None,
             false => Some(self.expression()?),
@@ -303,10 +401,7 @@ impl Parser {
 
     /// Parses an expression statement.
     fn expression_statement(&mut self) -> ParseResult<Stmt> {
-        let expr = match self.expression() {
-            Ok(expr) => expr,
-            Err(error) => return Err(error),
-        };
+        let expr = self.expression()?;
 
         self.consume(Type::Semicolon, "Expect ';' after expression")?;
 
@@ -315,6 +410,7 @@ impl Parser {
 
     /// Parses a function decleration.
     fn function(&mut self, kind: &str) -> ParseResult<Stmt> {
+        let doc = self.doc.take();
         let name = self.consume(Type::Identifier, &format!("Expect {kind} name"))?.to_owned();
 
         self.consume(Type::LeftParen, &format!("Expect '(' after {kind} name"))?;
@@ -342,9 +438,14 @@ impl Parser {
 
         self.consume(Type::LeftBrace, &format!("Expect '{{' before {kind} body"))?;
 
-        let body = self.block()?;
+        self.functions += 1;
+        let body = self.block();
+        self.functions -= 1;
+
+        let body = body?;
+        let end = self.previous().to_owned();
 
-        Ok(Stmt::Function(FunctionData { name, params, body }))
+        Ok(Stmt::Function(FunctionData { name, params, body, end, doc }))
     }
 
     /// Parses a block statement.
@@ -362,9 +463,27 @@ impl Parser {
         Ok(statements)
     }
 
+    /// Parses a comma expression, which evaluates its operands in order and results in the last.
+    fn comma(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.assignment()?;
+
+        while matches!(self, Type::Comma) {
+            let operator = self.previous().clone();
+            let right = self.assignment()?;
+
+            expr = Expr::Binary(BinaryData {
+                left: Box::new(expr),
+                operator,
+                right: Box::new(right)
+            });
+        }
+
+        Ok(expr)
+    }
+
     /// Parses an assignment expression.
     fn assignment(&mut self) -> ParseResult<Expr> {
-        let expr = self.or()?;
+        let expr = self.conditional()?;
 
         if matches!(self, Type::Equal) {
             let equals = self.previous().to_owned();
@@ -376,10 +495,68 @@ impl Parser {
                 return Ok(Expr::Assign(AssignData { name, value: Box::new(value) }))
             }
 
-            ParseError {
+            self.error(ParseError {
                 token: equals,
                 message: "Invalid assignment target".to_string()
-            }.throw();
+            });
+        } else if matches!(self, Type::PlusEqual, Type::MinusEqual, Type::StarEqual, Type::SlashEqual, Type::PercentEqual) {
+            let operator = self.previous().to_owned();
+            let value = self.assignment()?;
+
+            return Ok(self.update(expr, operator, Some(value), true));
+        }
+
+        Ok(expr)
+    }
+
+    /// Builds a compound assignment, increment or decrement of the target, which must be a variable.
+    fn update(&mut self, target: Expr, operator: Token, value: Option<Expr>, prefix: bool) -> Expr {
+        let Expr::Variable(data) = target else {
+            self.error(ParseError {
+                token: operator,
+                message: "Invalid assignment target".to_string()
+            });
+
+            return target;
+        };
+
+        Expr::Update(UpdateData { name: data.name, operator, value: value.map(Box::new), prefix })
+    }
+
+    /// Parses a conditional expression, which is right associative.
+    fn conditional(&mut self) -> ParseResult<Expr> {
+        let condition = self.coalesce()?;
+
+        if matches!(self, Type::Question) {
+            let question = self.previous().clone();
+            let then_branch = self.expression()?;
+            self.consume(Type::Colon, "Expect ':' after then branch of conditional expression")?;
+            let else_branch = self.conditional()?;
+
+            return Ok(Expr::Conditional(ConditionalData {
+                condition: Box::new(condition),
+                question,
+                then_branch: Box::new(then_branch),
+                else_branch: Box::new(else_branch),
+            }));
+        }
+
+        Ok(condition)
+    }
+
+    /// Parses a null-coalescing expression, whose right operand is only evaluated when the left is null.
+    fn coalesce(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.or()?;
+
+        while matches!(self, Type::QuestionQuestion) {
+            let operator = self.previous().clone();
+            let right = self.or()?;
+
+            expr = Expr::Logical(LogicalData {
+                left: Box::new(expr),
+                operator,
+                right: Box::new(right)
+            });
         }
 
         Ok(expr)
@@ -421,17 +598,11 @@ impl Parser {
 
     /// Parses an equality expression.
     fn equality(&mut self) -> ParseResult<Expr> {
-        let mut expr = match self.comparison() {
-            Ok(expr) => expr,
-            Err(error) => return Err(error),
-        };
+        let mut expr = self.comparison()?;
 
         while matches!(self, Type::BangEqual, Type::EqualEqual) {
             let operator = self.previous().clone();
-            let right = match self.comparison() {
-                Ok(expr) => expr,
-                Err(error) => return Err(error),
-            };
+            let right = self.comparison()?;
 
             expr = Expr::Binary(BinaryData {
                 left: Box::new(expr),
@@ -445,17 +616,83 @@ impl Parser {
 
     /// Parses a comparison expression.
     fn comparison(&mut self) -> ParseResult<Expr> {
-        let mut expr = match self.term() {
-            Ok(expr) => expr,
-            Err(error) => return Err(error),
-        };
+        let mut expr = self.bit_or()?;
 
         while matches!(self, Type::Greater, Type::GreaterEqual, Type::Less, Type::LessEqual) {
             let operator = self.previous().clone();
-            let right = match self.term() {
-                Ok(expr) => expr,
-                Err(error) => return Err(error),
-            };
+            let right = self.bit_or()?;
+
+            expr = Expr::Binary(BinaryData {
+                left: Box::new(expr),
+                operator,
+                right: Box::new(right)
+            });
+        }
+
+        Ok(expr)
+    }
+
+    /// Parses a bitwise or expression.
+    fn bit_or(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.bit_xor()?;
+
+        while matches!(self, Type::Pipe) {
+            let operator = self.previous().clone();
+            let right = self.bit_xor()?;
+
+            expr = Expr::Binary(BinaryData {
+                left: Box::new(expr),
+                operator,
+                right: Box::new(right)
+            });
+        }
+
+        Ok(expr)
+    }
+
+    /// Parses a bitwise xor expression.
+    fn bit_xor(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.bit_and()?;
+
+        while matches!(self, Type::Caret) {
+            let operator = self.previous().clone();
+            let right = self.bit_and()?;
+
+            expr = Expr::Binary(BinaryData {
+                left: Box::new(expr),
+                operator,
+                right: Box::new(right)
+            });
+        }
+
+        Ok(expr)
+    }
+
+    /// Parses a bitwise and expression.
+    fn bit_and(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.shift()?;
+
+        while matches!(self, Type::Ampersand) {
+            let operator = self.previous().clone();
+            let right = self.shift()?;
+
+            expr = Expr::Binary(BinaryData {
+                left: Box::new(expr),
+                operator,
+                right: Box::new(right)
+            });
+        }
+
+        Ok(expr)
+    }
+
+    /// Parses a shift expression.
+    fn shift(&mut self) -> ParseResult<Expr> {
+        let mut expr = self.term()?;
+
+        while matches!(self, Type::LessLess, Type::GreaterGreater) {
+            let operator = self.previous().clone();
+            let right = self.term()?;
 
             expr = Expr::Binary(BinaryData {
                 left: Box::new(expr),
@@ -469,17 +706,11 @@ impl Parser {
 
     /// Parses a term expression.
     fn term(&mut self) -> ParseResult<Expr> {
-        let mut expr = match self.factor() {
-            Ok(expr) => expr,
-            Err(error) => return Err(error),
-        };
+        let mut expr = self.factor()?;
 
         while matches!(self, Type::Minus, Type::Plus) {
             let operator = self.previous().clone();
-            let right = match self.factor() {
-                Ok(expr) => expr,
-                Err(error) => return Err(error),
-            };
+            let right = self.factor()?;
 
             expr = Expr::Binary(BinaryData {
                 left: Box::new(expr),
@@ -493,17 +724,11 @@ impl Parser {
 
     /// Parses a factor expression.
     fn factor(&mut self) -> ParseResult<Expr> {
-        let mut expr = match self.unary() {
-            Ok(expr) => expr,
-            Err(error) => return Err(error),
-        };
+        let mut expr = self.unary()?;
 
-        while matches!(self, Type::Slash, Type::Star) {
+        while matches!(self, Type::Slash, Type::Star, Type::Percent, Type::TildeSlash) {
             let operator = self.previous().clone();
-            let right = match self.unary() {
-                Ok(expr) => expr,
-                Err(error) => return Err(error),
-            };
+            let right = self.unary()?;
 
             expr = Expr::Binary(BinaryData {
                 left: Box::new(expr),
@@ -517,12 +742,9 @@ impl Parser {
 
     /// Parses a unary expression.
     fn unary(&mut self) -> ParseResult<Expr> {
-        if matches!(self, Type::Bang, Type::Minus) {
+        if matches!(self, Type::Bang, Type::Minus, Type::Tilde) {
             let operator = self.previous().clone();
-            let right = match self.unary() {
-                Ok(expr) => expr,
-                Err(error) => return Err(error),
-            };
+            let right = self.unary()?;
 
             return Ok(Expr::Unary(UnaryData {
                 operator,
@@ -530,23 +752,64 @@ impl Parser {
             }));
         }
 
-        self.call()
+        if matches!(self, Type::PlusPlus, Type::MinusMinus) {
+            let operator = self.previous().clone();
+            let target = self.unary()?;
+
+            return Ok(self.update(target, operator, None, true));
+        }
+
+        self.power()
+    }
+
+    /// Parses an exponentiation, which is right associative and binds tighter than unary
+    /// operators on its left.
+    fn power(&mut self) -> ParseResult<Expr> {
+        let expr = self.postfix()?;
+
+        if matches!(self, Type::StarStar) {
+            let operator = self.previous().clone();
+            let right = self.unary()?;
+
+            return Ok(Expr::Binary(BinaryData {
+                left: Box::new(expr),
+                operator,
+                right: Box::new(right)
+            }));
+        }
+
+        Ok(expr)
+    }
+
+    /// Parses a postfix increment or decrement, which evaluates to the previous value.
+    fn postfix(&mut self) -> ParseResult<Expr> {
+        let expr = self.call()?;
+
+        if matches!(self, Type::PlusPlus, Type::MinusMinus) {
+            let operator = self.previous().clone();
+
+            return Ok(self.update(expr, operator, None, false));
+        }
+
+        Ok(expr)
     }
 
     /// Parses a call arguments.
-    fn finish_call(&mut self, callee: &Expr) -> ParseResult<Expr> {
+    fn finish_call(&mut self, callee: &Expr, optional: Option<Token>) -> ParseResult<Expr> {
         let mut arguments = vec![];
 
         if !self.check(Type::RightParen) {
             while { 
                 if arguments.len() >= 255 {
-                    ParseError {
-                        token: self.peek().to_owned(),
+                    let token = self.peek().to_owned();
+                    self.error(ParseError {
+                        token,
                         message: "Can't have more than 255 arguments".to_string(),
-                    }.throw();
+                    });
                 }
 
-                arguments.push(self.expression()?);
+                // A comma separates arguments, a comma expression must be parenthesized.
+                arguments.push(self.assignment()?);
                 matches!(self, Type::Comma)
             } {}
         }
@@ -557,6 +820,7 @@ impl Parser {
             callee: Box::new(callee.to_owned()),
             paren: paren.to_owned(),
             arguments,
+            optional,
         }))
     }
 
@@ -566,7 +830,14 @@ impl Parser {
 
         loop {
             if matches!(self, Type::LeftParen) {
-                expr = self.finish_call(&expr)?;
+                expr = self.finish_call(&expr, None)?;
+            } else if matches!(self, Type::QuestionDot) {
+                let question = self.previous().clone();
+                self.consume(Type::LeftParen, "Expect '(' after '?.'")?;
+                expr = self.finish_call(&expr, Some(question))?;
+            } else if matches!(self, Type::Dot) {
+                let name = self.consume(Type::Identifier, "Expect property name after '.'")?.clone();
+                expr = Expr::Get(GetData { object: Box::new(expr), name });
             } else {
                 break;
             }
@@ -575,23 +846,46 @@ impl Parser {
         Ok(expr)
     }
 
-    /// Parses a primary expression.
-    fn primary(&mut self) -> ParseResult<Expr> {
-        if matches!(self, Type::False) {
-            return Ok(Expr::Literal(Literal::Bool(false)));
-        }
+    /// Parses a string with embedded expressions, after its first part.
+    fn interpolation(&mut self) -> ParseResult<Expr> {
+        let token = self.previous().to_owned();
+        let mut strings = vec![];
+        let mut expressions = vec![];
+
+        loop {
+            let Some(Literal::String(string)) = self.previous().literal.clone() else { unreachable!() };
+            strings.push(string);
+
+            if self.previous().r#type == Type::String {
+                break;
+            }
 
-        if matches!(self, Type::True) {
-            return Ok(Expr::Literal(Literal::Bool(true)));
+            expressions.push(self.expression()?);
+
+            if !matches!(self, Type::Interpolation) {
+                self.consume(Type::String, "Expect end of string after embedded expression")?;
+            }
         }
 
-        if matches!(self, Type::Null) {
-            return Ok(Expr::Literal(Literal::Null));
+        Ok(Expr::Interpolation(InterpolationData { token, strings, expressions }))
+    }
+
+    /// Parses a primary expression.
+    fn primary(&mut self) -> ParseResult<Expr> {
+        if matches!(self, Type::False, Type::True, Type::Null, Type::Number, Type::String) {
+            let token = self.previous().clone();
+            let value = match token.r#type {
+                Type::False => Literal::Bool(false),
+                Type::True => Literal::Bool(true),
+                Type::Null => Literal::Null,
+                _ => token.literal.clone().expect("number or string to have a literal value"),
+            };
+
+            return Ok(Expr::Literal(LiteralData { value, token: Some(token) }));
         }
 
-        if matches!(self, Type::Number, Type::String) {
-            return Ok(Expr::Literal(self.previous().clone().literal
-                .expect("number or string to have a literal value")));
+        if matches!(self, Type::Interpolation) {
+            return self.interpolation();
         }
 
         if matches!(self, Type::Identifier) {
@@ -601,17 +895,15 @@ impl Parser {
         }
 
         if matches!(self, Type::LeftParen) {
-            let expr = match self.expression() {
-                Ok(expr) => expr,
-                Err(error) => return Err(error),
-            };
+            let paren = self.previous().clone();
+            let expr = self.expression()?;
 
             match self.consume(Type::RightParen, "Expected ')' after expression") {
                 Ok(_) => (),
                 Err(error) => return Err(error),
             };
 
-            return Ok(Expr::Grouping(GroupingData { expr: Box::new(expr) }));
+            return Ok(Expr::Grouping(GroupingData { paren, expr: Box::new(expr) }));
         }
 
         Err(ParseError {
@@ -633,6 +925,7 @@ impl Parser {
                 Type::Class => return,
                 Type::Fun => return,
                 Type::Var => return,
+                Type::Import => return,
                 Type::For => return,
                 Type::If => return,
                 Type::While => return,
@@ -647,8 +940,16 @@ impl Parser {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::scanner::Scanner;
+    use crate::ast::ASTPrinter;
     use crate::token::Type;
 
+    /// Returns the literal parsed from a number token on the first line.
+    fn number(value: f64) -> Expr {
+        let token = Token::new(Type::Number, value.to_string(), Some(Literal::Number(value)), 1);
+        Expr::Literal(LiteralData { value: Literal::Number(value), token: Some(token) })
+    }
+
     #[test]
     fn test_matches() {
         let mut parser = Parser::new(vec![
@@ -675,9 +976,9 @@ mod test {
         let expr = parser.expression().unwrap();
 
         assert_eq!(expr, Expr::Logical(LogicalData {
-            left: Box::new(Expr::Literal(Literal::Number(123.0))),
+            left: Box::new(number(123.0)),
             operator: Token::new(Type::Or, "or".to_string(), None, 1),
-            right: Box::new(Expr::Literal(Literal::Number(456.0)))
+            right: Box::new(number(456.0))
         }));
     }
 
@@ -693,9 +994,9 @@ mod test {
         let expr = parser.expression().unwrap();
 
         assert_eq!(expr, Expr::Logical(LogicalData {
-            left: Box::new(Expr::Literal(Literal::Number(123.0))),
+            left: Box::new(number(123.0)),
             operator: Token::new(Type::And, "and".to_string(), None, 1),
-            right: Box::new(Expr::Literal(Literal::Number(456.0)))
+            right: Box::new(number(456.0))
         }));
     }
 
@@ -713,12 +1014,12 @@ mod test {
         let expr = parser.expression().unwrap();
 
         assert_eq!(expr, Expr::Logical(LogicalData {
-            left: Box::new(Expr::Literal(Literal::Number(123.0))),
+            left: Box::new(number(123.0)),
             operator: Token::new(Type::Or, "or".to_string(), None, 1),
             right: Box::new(Expr::Logical(LogicalData {
-                left: Box::new(Expr::Literal(Literal::Number(456.0))),
+                left: Box::new(number(456.0)),
                 operator: Token::new(Type::And, "and".to_string(), None, 1),
-                right: Box::new(Expr::Literal(Literal::Number(789.0)))
+                right: Box::new(number(789.0))
             }))
         }));
     }
@@ -735,9 +1036,9 @@ mod test {
         let expr = parser.expression().unwrap();
 
         assert_eq!(expr, Expr::Binary(BinaryData {
-            left: Box::new(Expr::Literal(Literal::Number(123.0))),
+            left: Box::new(number(123.0)),
             operator: Token::new(Type::Plus, "+".to_string(), None, 1),
-            right: Box::new(Expr::Literal(Literal::Number(456.0)))
+            right: Box::new(number(456.0))
         }));
     }
 
@@ -753,7 +1054,7 @@ mod test {
 
         assert_eq!(expr, Expr::Unary(UnaryData {
             operator: Token::new(Type::Minus, "-".to_string(), None, 1),
-            expr: Box::new(Expr::Literal(Literal::Number(123.0)))
+            expr: Box::new(number(123.0))
         }));
     }
 
@@ -769,7 +1070,8 @@ mod test {
         let expr = parser.expression().unwrap();
 
         assert_eq!(expr, Expr::Grouping(GroupingData {
-            expr: Box::new(Expr::Literal(Literal::Number(123.0)))
+            paren: Token::new(Type::LeftParen, String::from("("), None, 1),
+            expr: Box::new(number(123.0))
         }));
     }
 
@@ -790,16 +1092,16 @@ mod test {
 
         assert_eq!(expr, Expr::Binary(BinaryData {
             left: Box::new(Expr::Binary(BinaryData {
-                left: Box::new(Expr::Literal(Literal::Number(1.0))),
+                left: Box::new(number(1.0)),
                 operator: Token::new(Type::Minus, "-".to_string(), None, 1),
                 right: Box::new(Expr::Binary(BinaryData {
-                    left: Box::new(Expr::Literal(Literal::Number(2.0))),
+                    left: Box::new(number(2.0)),
                     operator: Token::new(Type::Star, "*".to_string(), None, 1),
-                    right: Box::new(Expr::Literal(Literal::Number(3.0)))
+                    right: Box::new(number(3.0))
                 }))
             })),
             operator: Token::new(Type::Plus, "+".to_string(), None, 1),
-            right: Box::new(Expr::Literal(Literal::Number(4.0)))
+            right: Box::new(number(4.0))
         }));
     }
 
@@ -818,12 +1120,12 @@ mod test {
 
         assert_eq!(expr, Expr::Binary(BinaryData {
             left: Box::new(Expr::Binary(BinaryData {
-                left: Box::new(Expr::Literal(Literal::Number(1.0))),
+                left: Box::new(number(1.0)),
                 operator: Token::new(Type::BangEqual, "!=".to_string(), None, 1),
-                right: Box::new(Expr::Literal(Literal::Number(2.0)))
+                right: Box::new(number(2.0))
             })),
             operator: Token::new(Type::EqualEqual, "==".to_string(), None, 1),
-            right: Box::new(Expr::Literal(Literal::Number(3.0)))
+            right: Box::new(number(3.0))
         }));
     }
 
@@ -848,18 +1150,18 @@ mod test {
             left: Box::new(Expr::Binary(BinaryData {
                 left: Box::new(Expr::Binary(BinaryData {
                     left: Box::new(Expr::Binary(BinaryData {
-                        left: Box::new(Expr::Literal(Literal::Number(1.0))),
+                        left: Box::new(number(1.0)),
                         operator: Token::new(Type::Greater, ">".to_string(), None, 1),
-                        right: Box::new(Expr::Literal(Literal::Number(2.0)))
+                        right: Box::new(number(2.0))
                     })),
                     operator: Token::new(Type::Less, "<".to_string(), None, 1),
-                    right: Box::new(Expr::Literal(Literal::Number(3.0)))
+                    right: Box::new(number(3.0))
                 })),
                 operator: Token::new(Type::GreaterEqual, ">=".to_string(), None, 1),
-                right: Box::new(Expr::Literal(Literal::Number(4.0)))
+                right: Box::new(number(4.0))
             })),
             operator: Token::new(Type::LessEqual, "<=".to_string(), None, 1),
-            right: Box::new(Expr::Literal(Literal::Number(5.0))),
+            right: Box::new(number(5.0)),
         }));
     }
 
@@ -875,7 +1177,8 @@ mod test {
         let stmt = parser.statement().unwrap();
 
         assert_eq!(stmt, Stmt::Print(PrintData {
-            expr: Expr::Literal(Literal::Number(123.0))
+            keyword: Token::new(Type::Print, "print".to_string(), None, 1),
+            expr: number(123.0)
         }));
     }
 
@@ -890,7 +1193,7 @@ mod test {
         let stmt = parser.statement().unwrap();
 
         assert_eq!(stmt, Stmt::Expression(ExpressionData {
-            expr: Expr::Literal(Literal::Number(123.0))
+            expr: number(123.0)
         }));
     }
 
@@ -912,11 +1215,15 @@ mod test {
         let stmt = parser.statement().unwrap();
 
         assert_eq!(stmt, Stmt::If(IfData {
-            condition: Expr::Literal(Literal::Number(123.0)),
+            keyword: Token::new(Type::If, "if".to_string(), None, 1),
+            condition: number(123.0),
             then_branch: Box::new(Stmt::Block(BlockData {
+                start: Token::new(Type::LeftBrace, "{".to_string(), None, 1),
                 statements: vec![Stmt::Print(PrintData {
-                    expr: Expr::Literal(Literal::Number(123.0))
+                    keyword: Token::new(Type::Print, "print".to_string(), None, 1),
+                    expr: number(123.0)
                 })],
+                end: Token::new(Type::RightBrace, "}".to_string(), None, 1),
             })),
             else_branch: None
         }));
@@ -946,16 +1253,23 @@ mod test {
         let stmt = parser.statement().unwrap();
 
         assert_eq!(stmt, Stmt::If(IfData {
-            condition: Expr::Literal(Literal::Number(123.0)),
+            keyword: Token::new(Type::If, "if".to_string(), None, 1),
+            condition: number(123.0),
             then_branch: Box::new(Stmt::Block(BlockData {
+                start: Token::new(Type::LeftBrace, "{".to_string(), None, 1),
                 statements: vec![Stmt::Print(PrintData {
-                    expr: Expr::Literal(Literal::Number(123.0))
+                    keyword: Token::new(Type::Print, "print".to_string(), None, 1),
+                    expr: number(123.0)
                 })],
+                end: Token::new(Type::RightBrace, "}".to_string(), None, 1),
             })),
             else_branch: Some(Box::new(Stmt::Block(BlockData {
+                start: Token::new(Type::LeftBrace, "{".to_string(), None, 1),
                 statements: vec![Stmt::Print(PrintData {
-                    expr: Expr::Literal(Literal::Number(456.0))
+                    keyword: Token::new(Type::Print, "print".to_string(), None, 1),
+                    expr: number(456.0)
                 })],
+                end: Token::new(Type::RightBrace, "}".to_string(), None, 1),
             })))
         }));
     }
@@ -976,7 +1290,8 @@ mod test {
 
         assert_eq!(stmt, Stmt::Var(VarData {
             name: Token::new(Type::Identifier, "a".to_string(), None, 1),
-            initializer: Some(Expr::Literal(Literal::Number(123.0)))
+            initializer: Some(number(123.0)),
+            doc: None,
         }));
     }
 
@@ -995,7 +1310,8 @@ mod test {
 
         assert_eq!(stmt, Stmt::Var(VarData {
             name: Token::new(Type::Identifier, "a".to_string(), None, 1),
-            initializer: Some(Expr::Literal(Literal::Number(123.0)))
+            initializer: Some(number(123.0)),
+            doc: None,
         }));
     }
 
@@ -1015,7 +1331,7 @@ mod test {
             stmt,
             Expr::Assign(AssignData {
                 name: Token::new(Type::Identifier, "a".to_string(), None, 1),
-                value: Box::new(Expr::Literal(Literal::Number(123.0)))
+                value: Box::new(number(123.0))
             })
         );
     }
@@ -1038,11 +1354,15 @@ mod test {
         let stmt = parser.statement().unwrap();
 
         assert_eq!(stmt, Stmt::While(WhileData {
-            condition: Expr::Literal(Literal::Number(123.0)),
+            keyword: Token::new(Type::While, "while".to_string(), None, 1),
+            condition: number(123.0),
             body: Box::new(Stmt::Block(BlockData {
+                start: Token::new(Type::LeftBrace, "{".to_string(), None, 1),
                 statements: vec![Stmt::Print(PrintData {
-                    expr: Expr::Literal(Literal::Number(123.0))
+                    keyword: Token::new(Type::Print, "print".to_string(), None, 1),
+                    expr: number(123.0)
                 })],
+                end: Token::new(Type::RightBrace, "}".to_string(), None, 1),
             }))
         }));
     }
@@ -1067,8 +1387,123 @@ mod test {
             stmt,
             vec![Stmt::Var(VarData {
                 name: Token::new(Type::Identifier, "a".to_string(), None, 1),
-                initializer: Some(Expr::Literal(Literal::Number(123.0)))
+                initializer: Some(number(123.0)),
+                doc: None,
             })]
         );
     }
+
+    #[test]
+    fn parse_top_level_return() {
+        let mut parser = Parser::new(Scanner::new(String::from("print 1; return 2;")).scan_tokens());
+        parser.parse();
+        assert_eq!(parser.errors.len(), 1);
+        assert_eq!(parser.errors[0].message, "Can't return from top-level code.");
+
+        let source = "fun f() { if (true) { return 1; } return; } { fun g() { return f(); } }";
+        let mut parser = Parser::new(Scanner::new(String::from(source)).scan_tokens());
+        parser.parse();
+        assert!(parser.errors.is_empty());
+    }
+
+    #[test]
+    fn parse_on_demand() {
+        let scanner = Scanner::new(String::from("var a = 1;\nprint a;\n"));
+
+        // Nothing past the end of file token is pulled.
+        let tokens = scanner
+            .filter_map(Result::ok)
+            .chain(std::iter::from_fn(|| panic!("pulled a token past the end of the file")));
+        let mut parser = Parser::new(tokens);
+
+        assert_eq!(parser.parse().len(), 2);
+        assert!(parser.errors.is_empty());
+    }
+
+    #[test]
+    fn parse_doc_comments() {
+        let source = "/// Adds two numbers.\n///\n/// Returns their sum.\nfun add(a, b) { return a + b; }\n\
+            /// The answer.\nvar answer = 42;\nprint 1 + /// Ignored.\n2;\nvar plain;\n";
+        let mut parser = Parser::new(Scanner::new(String::from(source)).scan_tokens());
+        let statements = parser.parse();
+
+        let Stmt::Function(function) = &statements[0] else { panic!("expected a function") };
+        assert_eq!(function.doc.as_deref(), Some("Adds two numbers.\n\nReturns their sum."));
+
+        let Stmt::Var(answer) = &statements[1] else { panic!("expected a variable") };
+        assert_eq!(answer.doc.as_deref(), Some("The answer."));
+
+        let Stmt::Var(plain) = &statements[3] else { panic!("expected a variable") };
+        assert_eq!(plain.doc, None);
+        assert!(parser.errors.is_empty());
+    }
+
+    #[test]
+    fn parse_conditional_and_comma() {
+        let source = "a ? b : c ? d : e; a = b ? 1 : 2; a or b ? c, d : e; 1, 2, 3; f(a, (b, c));";
+        let mut parser = Parser::new(Scanner::new(String::from(source)).scan_tokens());
+        let statements = parser.parse();
+        assert!(parser.errors.is_empty());
+
+        let expressions: Vec<Expr> = statements.into_iter().map(|stmt| {
+            let Stmt::Expression(data) = stmt else { panic!("expected an expression") };
+            data.expr
+        }).collect();
+
+        let printed: Vec<String> = expressions.iter().take(4).map(|expr| ASTPrinter.print(expr.clone())).collect();
+        assert_eq!(printed, [
+            "(?: a b (?: c d e))", "(= a (?: b 1 2))", "(?: (or a b) (, c d) e)", "(, (, 1 2) 3)",
+        ]);
+
+        let Expr::Call(call) = &expressions[4] else { panic!("expected a call") };
+        assert_eq!(call.arguments.len(), 2);
+
+        for (source, message) in [
+            ("a ? b;", "Expect ':' after then branch of conditional expression"),
+            ("a ? b : c = d;", "Invalid assignment target"),
+        ] {
+            let mut parser = Parser::new(Scanner::new(String::from(source)).scan_tokens());
+            parser.parse();
+            assert_eq!(parser.errors[0].message, message, "{source}");
+        }
+    }
+
+    #[test]
+    fn parse_null_operators() {
+        let source = "a ?? b ?? c; a ?? b ? c : d; a or b ?? c; f?.(1)?.(); a ?? b = c;";
+        let mut parser = Parser::new(Scanner::new(String::from(source)).scan_tokens());
+        let statements = parser.parse();
+        assert_eq!(parser.errors.len(), 1);
+        assert_eq!(parser.errors[0].message, "Invalid assignment target");
+
+        let printed: Vec<String> = statements.iter().take(4).map(|stmt| {
+            let Stmt::Expression(data) = stmt else { panic!("expected an expression") };
+            ASTPrinter.print(data.expr.clone())
+        }).collect();
+        assert_eq!(printed, ["(?? (?? a b) c)", "(?: (?? a b) c d)", "(?? (or a b) c)", "f?.(1)?.()"]);
+
+        let mut parser = Parser::new(Scanner::new(String::from("f?.x;")).scan_tokens());
+        parser.parse();
+        assert_eq!(parser.errors[0].message, "Expect '(' after '?.'");
+    }
+
+    #[test]
+    fn parse_update() {
+        let source = "a += 1 + 2; ++a; a--; -b++; c = d *= 2; for (var i = 0; i < 3; i++) print i;";
+        let mut parser = Parser::new(Scanner::new(String::from(source)).scan_tokens());
+        let statements = parser.parse();
+        assert!(parser.errors.is_empty());
+
+        let printed: Vec<String> = statements.iter().take(5).map(|stmt| {
+            let Stmt::Expression(data) = stmt else { panic!("expected an expression") };
+            ASTPrinter.print(data.expr.clone())
+        }).collect();
+        assert_eq!(printed, ["(+= a (+ 1 2))", "(++ a)", "(a --)", "(- (b ++))", "(= c (*= d 2))"]);
+
+        for source in ["1 += 2;", "a + b -= 1;", "++1;", "f()--;", "(a)++;"] {
+            let mut parser = Parser::new(Scanner::new(String::from(source)).scan_tokens());
+            parser.parse();
+            assert_eq!(parser.errors[0].message, "Invalid assignment target", "{source}");
+        }
+    }
 }
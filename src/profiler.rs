@@ -0,0 +1,283 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use crate::error::RuntimeError;
+use crate::interpreter::{Hook, Interpreter};
+use crate::object::Object;
+use crate::stmt::Stmt;
+
+/// Name of the frame running the top level statements.
+const SCRIPT: &str = "script";
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct FunctionStats {
+    pub calls: u64,
+    /// Time spent in the function and the functions it called, recursive calls are counted once.
+    pub inclusive: Duration,
+    /// Time spent in the function itself.
+    pub exclusive: Duration,
+}
+
+/// Results recorded by a `Profiler`.
+#[derive(Debug, Default)]
+pub struct Profile {
+    pub functions: HashMap<String, FunctionStats>,
+    /// Number of statements executed on every line, by module and line; the script has no module.
+    pub lines: HashMap<(Option<String>, usize), u64>,
+    /// Exclusive time of every distinct call stack, outermost first.
+    pub stacks: HashMap<Vec<String>, Duration>,
+}
+
+fn milliseconds(duration: Duration) -> String {
+    format!("{:.3}ms", duration.as_secs_f64() * 1000.0)
+}
+
+impl Profile {
+    /// Returns the functions sorted by exclusive time, followed by the lines sorted by hits.
+    pub fn report(&self) -> String {
+        let mut functions: Vec<(&String, &FunctionStats)> = self.functions.iter().collect();
+        functions.sort_by(|a, b| b.1.exclusive.cmp(&a.1.exclusive).then(a.0.cmp(b.0)));
+
+        let width = functions.iter().map(|(name, _)| name.len()).max().unwrap_or(0).max("function".len());
+
+        let mut report = format!("{:width$}  {:>8}  {:>12}  {:>12}\n", "function", "calls", "inclusive", "exclusive");
+
+        for (name, stats) in functions {
+            writeln!(
+                report,
+                "{name:width$}  {:>8}  {:>12}  {:>12}",
+                stats.calls,
+                milliseconds(stats.inclusive),
+                milliseconds(stats.exclusive),
+            ).unwrap();
+        }
+
+        let mut lines: Vec<(&(Option<String>, usize), &u64)> = self.lines.iter().collect();
+        lines.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+
+        let lines: Vec<(String, &u64)> = lines
+            .into_iter()
+            .map(|((module, line), hits)| match module {
+                Some(module) => (format!("{module}:{line}"), hits),
+                None => (line.to_string(), hits),
+            })
+            .collect();
+
+        let width = lines.iter().map(|(line, _)| line.len()).max().unwrap_or(0).max(8);
+
+        writeln!(report, "\n{:>width$}  {:>8}", "line", "hits").unwrap();
+
+        for (line, hits) in lines {
+            writeln!(report, "{line:>width$}  {hits:>8}").unwrap();
+        }
+
+        report
+    }
+
+    /// Returns the call stacks in the folded format read by flamegraph tools, weighted in microseconds.
+    pub fn folded(&self) -> String {
+        let mut stacks: Vec<(String, u128)> = self.stacks
+            .iter()
+            .map(|(stack, time)| (stack.join(";"), time.as_micros()))
+            .filter(|(_, weight)| *weight > 0)
+            .collect();
+        stacks.sort();
+
+        stacks.iter().map(|(stack, weight)| format!("{stack} {weight}\n")).collect()
+    }
+}
+
+/// Represents a call being timed.
+struct Call {
+    function: String,
+    start: Instant,
+    // Time spent in the functions it called.
+    children: Duration,
+}
+
+/// Records call counts, timings and line hits into a shared `Profile`.
+///
+/// Native functions are timed like any other function.
+pub struct Profiler {
+    profile: Rc<RefCell<Profile>>,
+    calls: Vec<Call>,
+}
+
+impl Profiler {
+    pub fn new(profile: Rc<RefCell<Profile>>) -> Self {
+        Profiler { profile, calls: vec![] }
+    }
+
+    fn push(&mut self, function: &str) {
+        self.calls.push(Call { function: function.to_string(), start: Instant::now(), children: Duration::ZERO });
+    }
+
+    /// Stops timing the innermost call and returns its stats.
+    fn pop(&mut self) -> Option<(String, Duration, Duration)> {
+        let stack: Vec<String> = self.calls.iter().map(|call| call.function.clone()).collect();
+        let call = self.calls.pop()?;
+
+        let elapsed = call.start.elapsed();
+        let exclusive = elapsed.saturating_sub(call.children);
+
+        if let Some(parent) = self.calls.last_mut() {
+            parent.children += elapsed;
+        }
+
+        *self.profile.borrow_mut().stacks.entry(stack).or_default() += exclusive;
+
+        Some((call.function, elapsed, exclusive))
+    }
+}
+
+impl Hook for Profiler {
+    fn statement(&mut self, interpreter: &mut Interpreter, stmt: &Stmt) -> Result<(), RuntimeError> {
+        if self.calls.is_empty() {
+            self.push(SCRIPT);
+        }
+
+        if let Some(line) = stmt.line() {
+            let module = interpreter.module().map(str::to_string);
+            *self.profile.borrow_mut().lines.entry((module, line)).or_default() += 1;
+        }
+
+        Ok(())
+    }
+
+    fn enter(&mut self, _interpreter: &Interpreter, function: &str, _arguments: &[Object]) {
+        self.profile.borrow_mut().functions.entry(function.to_string()).or_default().calls += 1;
+        self.push(function);
+    }
+
+    fn leave(&mut self, _interpreter: &Interpreter, _function: &str, _value: Option<&Object>) {
+        let Some((function, elapsed, exclusive)) = self.pop() else { return };
+        let recursive = self.calls.iter().any(|call| call.function == function);
+
+        let mut profile = self.profile.borrow_mut();
+        let stats = profile.functions.entry(function).or_default();
+
+        stats.exclusive += exclusive;
+
+        if !recursive {
+            stats.inclusive += elapsed;
+        }
+    }
+
+    fn finish(&mut self, _interpreter: &Interpreter) {
+        // Calls left open by an error have already been left.
+        while self.pop().is_some() {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+    use crate::testing::files;
+
+    fn profile(source: &str) -> Profile {
+        let tokens = Scanner::new(source.to_string()).scan_tokens();
+        let statements = Parser::new(tokens).parse();
+
+        let profile = Rc::new(RefCell::new(Profile::default()));
+        let mut interpreter = Interpreter::new();
+        interpreter.set_output(Box::new(std::io::sink()));
+        interpreter.add_hook(Box::new(Profiler::new(Rc::clone(&profile))));
+        let _ = interpreter.interpret(&statements);
+
+        profile.take()
+    }
+
+    const FIB: &str = "\
+fun fib(n) {
+  if (n < 2) return n;
+  return fib(n - 1) + fib(n - 2);
+}
+print fib(5);
+print clock();
+";
+
+    #[test]
+    fn calls_and_lines() {
+        let profile = profile(FIB);
+
+        assert_eq!(profile.functions["fib"].calls, 15);
+        assert_eq!(profile.functions["clock"].calls, 1);
+        // The `if` runs on every call, its `return` on the 8 base cases.
+        assert_eq!(profile.lines[&(None, 2)], 23);
+        assert_eq!(profile.lines[&(None, 3)], 7);
+        assert_eq!(profile.lines[&(None, 5)], 1);
+    }
+
+    #[test]
+    fn recursive_time_is_counted_once() {
+        let profile = profile(FIB);
+        let fib = &profile.functions["fib"];
+
+        // The outermost call spans every recursive call.
+        let outermost: Duration = profile.stacks
+            .iter()
+            .filter(|(stack, _)| stack.len() >= 2 && stack[1] == "fib")
+            .map(|(_, time)| *time)
+            .sum();
+
+        assert_eq!(fib.inclusive, outermost);
+        assert!(fib.exclusive <= fib.inclusive);
+    }
+
+    #[test]
+    fn folded_stacks() {
+        let profile = profile(FIB);
+
+        let stacks: Vec<&Vec<String>> = profile.stacks.keys().collect();
+        assert!(stacks.contains(&&vec!["script".to_string()]));
+        assert!(stacks.contains(&&vec!["script".to_string(), "fib".to_string(), "fib".to_string()]));
+        assert!(stacks.contains(&&vec!["script".to_string(), "clock".to_string()]));
+
+        for line in profile.folded().lines() {
+            let (stack, weight) = line.rsplit_once(' ').unwrap();
+            assert!(stack.starts_with("script"));
+            assert!(weight.parse::<u128>().unwrap() > 0);
+        }
+    }
+
+    #[test]
+    fn report_after_error() {
+        let profile = profile("fun f() { return g(); }\nf();\n");
+
+        assert_eq!(profile.functions["f"].calls, 1);
+        assert!(profile.report().starts_with("function  "));
+    }
+
+    #[test]
+    fn module_lines() {
+        let directory = files("profiler", &[
+            ("main.lox", "import \"lib.lox\" as lib;\nprint lib.f();\n"),
+            ("lib.lox", "fun f() {\n  return 1;\n}\n"),
+        ]);
+
+        let path = directory.join("main.lox");
+        let tokens = Scanner::new(std::fs::read_to_string(&path).unwrap()).scan_tokens();
+        let statements = Parser::new(tokens).parse();
+
+        let profile = Rc::new(RefCell::new(Profile::default()));
+        let mut interpreter = Interpreter::new();
+        interpreter.set_script(&path);
+        interpreter.set_output(Box::new(std::io::sink()));
+        interpreter.add_hook(Box::new(Profiler::new(Rc::clone(&profile))));
+        interpreter.interpret(&statements).unwrap();
+
+        let profile = profile.take();
+        let lib = Some("lib.lox".to_string());
+
+        assert_eq!(profile.lines[&(None, 2)], 1);
+        assert_eq!(profile.lines[&(lib.clone(), 1)], 1);
+        assert_eq!(profile.lines[&(lib, 2)], 1);
+        assert_eq!(profile.lines.len(), 4);
+        assert!(profile.report().contains("lib.lox:2"));
+    }
+}
@@ -1,56 +1,211 @@
-use substring::Substring;
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Read};
+
+use unicode_xid::UnicodeXID;
 
 use crate::token::{Token, Type};
 use crate::literal::Literal;
 use crate::error::{rloxError, ScanError};
 
+/// Turns source code into tokens.
+///
+/// Positions in the source are byte offsets, columns of tokens are counted in characters.
+///
+/// Tokens are scanned on demand when the scanner is used as an iterator. A scanner reading from
+/// a reader only keeps the part of the source it has not scanned yet.
 pub struct Scanner {
     source: String,
-    pub tokens: Vec<Token>,
+    reader: Option<Box<dyn BufRead>>,
+    // Offset in the whole source of the start of `source`, after the scanned part is dropped.
+    base: usize,
+    /// Scanned tokens that have not been returned yet.
+    pub tokens: VecDeque<Token>,
     start: usize,
     current: usize,
     line: usize,
+    line_start: usize,
+    // Line and column the current token starts at, a string or comment may end on a later line.
+    start_line: usize,
+    column: u32,
+    // Offset on the current line up to which characters are counted, with their count, so columns
+    // are found without counting the line again for every token.
+    counted: (usize, u32),
+    comments: bool,
+    quiet: bool,
+    // Strings with an open `${`, innermost last, with the number of unclosed braces inside the
+    // expression and where the string started.
+    interpolations: Vec<(usize, usize, usize)>,
+    pub errors: Vec<ScanError>,
+    // Number of errors returned by the iterator.
+    returned: usize,
+    // If the end of file token has been scanned.
+    finished: bool,
 }
 
+/// Number of bytes read past the current character, enough for the longest lookahead.
+const LOOKAHEAD: usize = 8;
+
+/// Size of the scanned part of the source that is dropped at once when reading from a reader.
+const SCANNED: usize = 1 << 16;
+
 impl Scanner {
     /// Creates a new scanner.
     pub fn new(source: String) -> Scanner {
-        Scanner { source, tokens: vec!(), start: 0, current: 0, line: 1 }
+        Scanner {
+            source,
+            reader: None,
+            base: 0,
+            tokens: VecDeque::new(),
+            start: 0,
+            current: 0,
+            line: 1,
+            line_start: 0,
+            start_line: 1,
+            column: 0,
+            counted: (0, 0),
+            comments: false,
+            quiet: false,
+            interpolations: vec!(),
+            errors: vec!(),
+            returned: 0,
+            finished: false,
+        }
+    }
+
+    /// Creates a new scanner reading the source code from the reader as it scans it.
+    pub fn from_reader(reader: impl Read + 'static) -> Scanner {
+        Scanner { reader: Some(Box::new(BufReader::new(reader))), ..Scanner::new(String::new()) }
+    }
+
+    /// Creates a new scanner that keeps comments as `Type::Comment` tokens.
+    ///
+    /// The parser does not accept comments, they need to be filtered out before parsing.
+    pub fn with_comments(source: String) -> Scanner {
+        Scanner { comments: true, ..Scanner::new(source) }
+    }
+
+    /// Keeps the errors without reporting them, for callers that own the output.
+    pub fn quiet(mut self) -> Scanner {
+        self.quiet = true;
+        self
     }
 
     /// Scans the source code and returns a vector of tokens.
     pub fn scan_tokens(&mut self) -> Vec<Token> {
-        while !self.is_at_end() {
+        self.by_ref().filter_map(Result::ok).collect()
+    }
+
+    /// Scans tokens until one is ready to be returned, or the end of the file.
+    fn scan_next(&mut self) {
+        while self.tokens.is_empty() && self.returned == self.errors.len() && !self.finished {
+            if self.is_at_end() {
+                self.finish();
+                return;
+            }
+
             self.start = self.current;
+            self.start_line = self.line;
+            self.column = self.column_at(self.start);
+            self.drop_scanned();
             self.scan_token();
         }
+    }
+
+    /// Reports the unterminated strings and adds the end of file token.
+    fn finish(&mut self) {
+        while let Some((_, line, location)) = self.interpolations.pop() {
+            self.error(ScanError {
+                line,
+                location,
+                message: String::from("Unterminated string interpolation"),
+            });
+        }
+
+        let mut eof = Token::new(Type::EOF, String::from(""), None, self.line);
+        eof.column = self.column_at(self.current);
+        self.tokens.push_back(eof);
+        self.finished = true;
+    }
+
+    /// Drops the scanned part of the source read from a reader, once it is large enough.
+    ///
+    /// Nothing is dropped inside a string interpolation, which may still report its start.
+    fn drop_scanned(&mut self) {
+        if self.reader.is_none() || self.start < SCANNED || !self.interpolations.is_empty() {
+            return;
+        }
+
+        // The count of the line is kept, its start may be dropped.
+        self.source.drain(..self.start);
+        self.base += self.start;
+        self.line_start = self.line_start.saturating_sub(self.start);
+        self.counted = (0, self.column);
+        self.current -= self.start;
+        self.start = 0;
+    }
+
+    /// Reads lines from the reader until the lookahead past the current character is available.
+    fn fill(&mut self) {
+        while self.source.len() < self.current + LOOKAHEAD {
+            let Some(reader) = self.reader.as_mut() else { return };
+
+            match reader.read_line(&mut self.source) {
+                Ok(0) => self.reader = None,
+                Ok(_) => (),
+                Err(error) => {
+                    self.reader = None;
+                    self.error(ScanError {
+                        line: self.line,
+                        location: self.source.len(),
+                        message: format!("Could not read the source: {error}"),
+                    });
+                },
+            }
+        }
+    }
+
+    /// Reports the error and keeps it for the caller.
+    fn error(&mut self, mut error: ScanError) {
+        error.location += self.base;
+
+        if !self.quiet {
+            error.throw();
+        }
+
+        self.errors.push(error);
+    }
 
-        self.tokens.push(
-            Token::new(
-                Type::EOF,
-                String::from(""),
-                None,
-                self.line
-            )
-        );
+    /// Returns the column of the offset on the current line, in characters.
+    fn column_at(&mut self, offset: usize) -> u32 {
+        if self.counted.0 < self.line_start || self.counted.0 > offset {
+            self.counted = (self.line_start, 0);
+        }
 
-        self.tokens.clone()
+        let (from, count) = self.counted;
+        self.counted = (offset, count + self.source[from..offset].chars().count() as u32);
+        self.counted.1
     }
 
     /// Returns the next character.
     fn advance(&mut self) -> char {
-        let temp = self.current;
-        self.current += 1;
+        match self.source[self.current..].chars().next() {
+            Some(char) => {
+                self.current += char.len_utf8();
 
-        match self.source.chars().nth(temp) {
-            Some(char) => char,
+                if char == '\n' {
+                    self.line += 1;
+                    self.line_start = self.current;
+                }
+
+                char
+            },
             None => panic!("tried to advance past end of the file."),
         }
     }
 
     /// Returns the next character without consuming it.
     fn peek(&self) -> char {
-        match self.source.chars().nth(self.current) {
+        match self.source[self.current..].chars().next() {
             Some(char) => char,
             None => panic!("tried to peek past end of the file."),
         }
@@ -58,112 +213,407 @@ impl Scanner {
 
     /// Returns the next next character without consuming it.
     fn peek_next(&self) -> char {
-        match self.source.chars().nth(self.current + 1) {
+        match self.source[self.current..].chars().nth(1) {
             Some(char) => char,
             None => panic!("tried to peek next past end of the file."),
         }
     }
 
+    /// Returns if the source continues with the text, without consuming it.
+    fn next_is(&self, text: &str) -> bool {
+        self.source[self.current..].starts_with(text)
+    }
+
     /// Returns if the next character is the expected character.
     fn match_next(&mut self, expected: char) -> bool {
-        match self.source.chars().nth(self.current) {
-            Some(char) if char == expected => {
-                self.current += 1;
-                true
-            },
-            Some(_) => false,
-            None => false,
+        if self.next_is(expected.encode_utf8(&mut [0; 4])) {
+            self.current += expected.len_utf8();
+            true
+        } else {
+            false
         }
     }
 
     /// Adds a new token to the list of tokens.
     fn add_token(&mut self, r#type: Type, literal: Option<Literal>) {
-        let text = self.source.substring(self.start, self.current);
-        self.tokens.push(
-            Token::new(
-                r#type,
-                String::from(text),
-                literal,
-                self.line
-            )
-        );
+        let text = &self.source[self.start..self.current];
+        let mut token = Token::new(r#type, String::from(text), literal, self.start_line);
+        token.column = self.column;
+        self.tokens.push_back(token);
     }
 
-    /// Returns if the scanner has reached the end of the file.
-    fn is_at_end(&self) -> bool {
-       self.current >= self.source.len()
+    /// Returns if the scanner has reached the end of the file, reading more of it when needed.
+    fn is_at_end(&mut self) -> bool {
+        self.fill();
+        self.current >= self.source.len()
     }
 
-    /// Handles a string literal.
+    /// Handles a string literal, or the part of one that follows an embedded expression.
+    ///
+    /// A `${` ends the part as an `Interpolation` token, the expression is scanned as usual up to
+    /// the matching `}`, which continues the string.
     fn string(&mut self) {
         let start = (self.line, self.start);
+        // The part starts after the opening quote or the `}` ending an embedded expression.
+        let part = (self.line, self.current);
 
         while !self.is_at_end() && self.peek() != '"' {
-            if self.peek() == '\n' {
-                self.line += 1;
+            if self.peek() == '\\' {
+                self.advance();  // The escaped character is never the end of the part.
+
+                if self.is_at_end() {
+                    break;
+                }
+            } else if self.next_is("${") {
+                self.current += 2;
+
+                let value = self.unescape(part.1, self.current - 2, part.0);
+                self.add_token(Type::Interpolation, Some(Literal::String(value)));
+                self.interpolations.push((0, start.0, start.1));
+                return;
             }
 
             self.advance();
         }
 
         if self.is_at_end() {
-            ScanError {
+            self.error(ScanError {
                 line: start.0,
                 location: start.1,
                 message: String::from("Unterminated string"),
-            }.throw();
+            });
             return;
         }
 
         self.advance();  // Move to the closing double quotes.
 
         // Literal does not include the double quotes unlike the lexeme.
-        let value = self.source.substring(self.start + 1, self.current - 1);
+        let value = self.unescape(part.1, self.current - 1, part.0);
+        self.add_token(Type::String, Some(Literal::String(value)));
+    }
+
+    /// Handles a raw string literal `r"..."`, taken as written without escapes or interpolation.
+    fn raw_string(&mut self) {
+        let line = self.line;
+        self.advance();  // Move past the opening double quotes.
+
+        while !self.is_at_end() && self.peek() != '"' {
+            self.advance();
+        }
+
+        if self.is_at_end() {
+            self.error(ScanError {
+                line,
+                location: self.start,
+                message: String::from("Unterminated string"),
+            });
+            return;
+        }
+
+        self.advance();
+
+        let value = &self.source[self.start + 2..self.current - 1];
         self.add_token(Type::String, Some(Literal::String(String::from(value))));
     }
 
-    /// Handles a number literal.
-    fn number(&mut self) {
-        while self.peek().is_ascii_digit() {
+    /// Handles a multi-line string literal between triple double quotes, raw when prefixed by `r`.
+    ///
+    /// The rest of the opening line and the line of the closing quotes are dropped when blank. The
+    /// smallest indentation of the remaining non-blank lines, and of the closing quotes, is removed
+    /// from every line, so the literal can be indented with the code around it. Escapes are
+    /// replaced after the indentation is removed, and the string is never interpolated.
+    fn multiline_string(&mut self, raw: bool) {
+        let start = (self.line, self.start);
+
+        // Move past the opening quotes, the first one is already consumed.
+        self.current += 2;
+        let from = self.current;
+
+        while !self.is_at_end() && !self.next_is("\"\"\"") {
+            if self.peek() == '\\' && !raw {
+                self.advance();
+
+                if self.is_at_end() {
+                    break;
+                }
+            }
+
             self.advance();
         }
 
-        if self.peek() == '.' {
-            if self.peek_next().is_ascii_digit() {
-                self.advance();  // Consume the dot.
+        if self.is_at_end() {
+            self.error(ScanError {
+                line: start.0,
+                location: start.1,
+                message: String::from("Unterminated string"),
+            });
+            return;
+        }
+
+        let text = String::from(&self.source[from..self.current]);
+        self.current += 3;
+
+        // Lines with their number and their offset in the source.
+        let mut lines = vec![];
+        let mut offset = from;
+        for (index, line) in text.split('\n').enumerate() {
+            let content = line.strip_suffix('\r').unwrap_or(line);
+            lines.push((start.0 + index, offset, content));
+            offset += line.len() + 1;
+        }
+
+        let blank = |line: &str| line.chars().all(|c| c == ' ' || c == '\t');
+        // Indentation characters are all one byte long.
+        let indentation = |line: &str| line.chars().take_while(|c| *c == ' ' || *c == '\t').count();
+
+        if lines.len() > 1 && blank(lines[0].2) {
+            lines.remove(0);
+        }
+
+        let mut indent = usize::MAX;
+        if lines.len() > 1 && blank(lines[lines.len() - 1].2) {
+            let (_, _, closing) = lines.pop().unwrap();
+            indent = indentation(closing);
+        }
+
+        for (_, _, line) in &lines {
+            if !blank(line) {
+                indent = indent.min(indentation(line));
+            }
+        }
+
+        let mut value = vec![];
+        for (line, offset, content) in lines {
+            if blank(content) {
+                value.push(String::new());
+            } else if raw {
+                value.push(String::from(&content[indent..]));
+            } else {
+                value.push(self.unescape(offset + indent, offset + content.len(), line));
+            }
+        }
+
+        self.add_token(Type::String, Some(Literal::String(value.join("\n"))));
+    }
+
+    /// Returns the source between the offsets with its escape sequences replaced.
+    ///
+    /// Invalid escapes are reported at their backslash and left out of the string.
+    fn unescape(&mut self, from: usize, to: usize, mut line: usize) -> String {
+        let chars: Vec<(usize, char)> = self.source[from..to]
+            .char_indices()
+            .map(|(offset, c)| (from + offset, c))
+            .collect();
+        let mut value = String::new();
+        let mut index = 0;
+
+        while index < chars.len() {
+            let (location, c) = chars[index];
+            index += 1;
+
+            if c == '\n' {
+                line += 1;
+            }
+
+            if c != '\\' {
+                value.push(c);
+                continue;
+            }
+
+            let Some(&(_, escaped)) = chars.get(index) else { break };
+            index += 1;
+
+            let message = match escaped {
+                'n' => { value.push('\n'); continue },
+                't' => { value.push('\t'); continue },
+                'r' => { value.push('\r'); continue },
+                '0' => { value.push('\0'); continue },
+                '\\' | '"' | '$' => { value.push(escaped); continue },
+                'u' => {
+                    // Up to six hexadecimal digits between braces.
+                    let close = chars[index..chars.len().min(index + 8)].iter().position(|(_, c)| *c == '}');
+
+                    match (chars.get(index), close) {
+                        (Some((_, '{')), Some(close)) => {
+                            let digits: String = chars[index + 1..index + close].iter().map(|(_, c)| c).collect();
+                            let code = u32::from_str_radix(&digits, 16).ok().filter(|_| !digits.starts_with('+'));
+                            index += close + 1;
+
+                            match code.and_then(char::from_u32) {
+                                Some(char) => { value.push(char); continue },
+                                None => format!("Invalid unicode escape '\\u{{{digits}}}'"),
+                            }
+                        },
+                        _ => String::from("Unicode escape must be written '\\u{XXXX}' with 1 to 6 hexadecimal digits"),
+                    }
+                },
+                '\n' => {
+                    line += 1;
+                    String::from("Invalid escape sequence at end of line")
+                },
+                _ => format!("Invalid escape sequence '\\{escaped}'"),
+            };
+
+            self.error(ScanError { line, location, message });
+        }
+
+        value
+    }
+
+    /// Handles a block comment, which can contain other block comments.
+    fn block_comment(&mut self) {
+        let line = self.line;
+        let mut depth = 1;
+
+        while depth > 0 && !self.is_at_end() {
+            if self.next_is("/*") {
+                self.current += 2;
+                depth += 1;
+            } else if self.next_is("*/") {
+                self.current += 2;
+                depth -= 1;
+            } else {
+                self.advance();
+            }
+        }
+
+        if depth > 0 {
+            self.error(ScanError {
+                line,
+                location: self.start,
+                message: String::from("Unterminated block comment"),
+            });
+        } else if self.comments {
+            self.add_token(Type::Comment, None);
+        }
+    }
+
+    /// Handles a number literal.
+    ///
+    /// Numbers are decimals with an optional fraction and exponent, or integers written in
+    /// hexadecimal, binary or octal after `0x`, `0b` or `0o`. Digits can be separated by `_`.
+    fn number(&mut self) {
+        let zero = &self.source[self.start..self.current] == "0";
+        let radix = match zero {
+            true if self.match_next('x') => 16,
+            true if self.match_next('b') => 2,
+            true if self.match_next('o') => 8,
+            _ => 10,
+        };
+
+        let name = match radix {
+            16 => "hexadecimal",
+            2 => "binary",
+            8 => "octal",
+            _ => "decimal",
+        };
+
+        let mut error = None;
+        let count = self.digits(radix, &mut error);
+
+        if radix != 10 {
+            if count == 0 {
+                let prefix = &self.source[self.start..self.current];
+                error.get_or_insert(format!("Expected {name} digits after '{prefix}'"));
+            }
+        } else {
+            if self.next_is(".") {
+                let fraction = self.current + 1 < self.source.len() && self.peek_next().is_ascii_digit();
+                self.advance();
+
+                if fraction {
+                    self.digits(10, &mut error);
+                } else {
+                    error.get_or_insert(String::from("Expected digits after '.' in number"));
+                }
+            }
+
+            if self.match_next('e') || self.match_next('E') {
+                if !self.match_next('+') {
+                    self.match_next('-');
+                }
 
-                while self.peek().is_ascii_digit() {
-                    self.advance();
+                if self.digits(10, &mut error) == 0 {
+                    error.get_or_insert(String::from("Expected digits in exponent"));
                 }
+            }
+        }
+
+        // A letter or digit right after the number is part of the mistake, not another token.
+        if !self.is_at_end() && self.peek().is_xid_continue() {
+            let c = self.peek();
+
+            if c.is_ascii_digit() {
+                error.get_or_insert(format!("Invalid digit '{c}' in {name} number"));
             } else {
-                ScanError {
+                error.get_or_insert(format!("Unexpected character '{c}' in number"));
+            }
+
+            while !self.is_at_end() && self.peek().is_xid_continue() {
+                self.advance();
+            }
+        }
+
+        let text: String = self.source[self.start..self.current].chars().filter(|c| *c != '_').collect();
+        let value = match radix {
+            10 => text.parse::<f64>().ok(),
+            _ => u64::from_str_radix(&text[2..], radix).ok().map(|value| value as f64),
+        };
+
+        match (error, value) {
+            (None, Some(value)) if value.is_finite() => {
+                self.add_token(Type::Number, Some(Literal::Number(value)));
+            },
+            (error, _) => {
+                self.error(ScanError {
                     line: self.line,
                     location: self.start,
-                    message: String::from("Unterminated number"),
-                }.throw();
-                return;
+                    message: error.unwrap_or_else(|| String::from("Number literal is too large")),
+                });
+            },
+        }
+    }
+
+    /// Consumes the digits of the radix and the `_` separating them, returns the number of digits.
+    fn digits(&mut self, radix: u32, error: &mut Option<String>) -> usize {
+        let mut count = 0;
+
+        while !self.is_at_end() && (self.peek().is_digit(radix) || self.peek() == '_') {
+            if self.advance() != '_' {
+                count += 1;
+                continue;
+            }
+
+            let before = self.source[..self.current - 1].ends_with(|c: char| c.is_digit(radix));
+            let after = !self.is_at_end() && self.peek().is_digit(radix);
+
+            if !before || !after {
+                error.get_or_insert(String::from("Digit separator '_' must be between digits"));
             }
         }
 
-        let value: f32 = self.source.substring(self.start, self.current).parse().unwrap();
-        self.add_token(Type::Number, Some(Literal::Number(value)));
+        count
     }
 
     /// Handles an identifier or a keyword.
+    ///
+    /// Identifiers follow the Unicode XID rules, with `_` allowed as a first character.
     fn identifier(&mut self) {
-        while self.peek().is_alphanumeric() {
+        while !self.is_at_end() && self.peek().is_xid_continue() {
             self.advance();
         }
 
-        let value = self.source.substring(self.start, self.current);
+        let value = &self.source[self.start..self.current];
         let token_type = match value {
             "and"    => Type::And,
+            "as"     => Type::As,
             "class"  => Type::Class,
             "else"   => Type::Else,
             "false"  => Type::False,
             "for"    => Type::For,
             "fun"    => Type::Fun,
             "if"     => Type::If,
+            "import" => Type::Import,
             "null"   => Type::Null,
             "or"     => Type::Or,
             "print"  => Type::Print,
@@ -186,16 +636,87 @@ impl Scanner {
             // One character tokens
             '(' => self.add_token(Type::LeftParen, None),
             ')' => self.add_token(Type::RightParen, None),
-            '{' => self.add_token(Type::LeftBrace, None),
-            '}' => self.add_token(Type::RightBrace, None),
+            '{' => {
+                if let Some((depth, _, _)) = self.interpolations.last_mut() {
+                    *depth += 1;
+                }
+
+                self.add_token(Type::LeftBrace, None);
+            },
+            '}' => {
+                match self.interpolations.last_mut() {
+                    // Closes the embedded expression, the string goes on.
+                    Some((0, _, _)) => {
+                        self.interpolations.pop();
+                        self.string();
+                    },
+                    Some((depth, _, _)) => {
+                        *depth -= 1;
+                        self.add_token(Type::RightBrace, None);
+                    },
+                    None => self.add_token(Type::RightBrace, None),
+                }
+            },
             ',' => self.add_token(Type::Comma, None),
             '.' => self.add_token(Type::Dot, None),
-            '-' => self.add_token(Type::Minus, None),
-            '+' => self.add_token(Type::Plus, None),
             ';' => self.add_token(Type::Semicolon, None),
-            '*' => self.add_token(Type::Star, None),
+            '&' => self.add_token(Type::Ampersand, None),
+            '|' => self.add_token(Type::Pipe, None),
+            '^' => self.add_token(Type::Caret, None),
+            ':' => self.add_token(Type::Colon, None),
 
             // Two character tokens
+            '-' => {
+                if self.match_next('-') {
+                    self.add_token(Type::MinusMinus, None);
+                } else if self.match_next('=') {
+                    self.add_token(Type::MinusEqual, None);
+                } else {
+                    self.add_token(Type::Minus, None)
+                };
+            },
+            '+' => {
+                if self.match_next('+') {
+                    self.add_token(Type::PlusPlus, None);
+                } else if self.match_next('=') {
+                    self.add_token(Type::PlusEqual, None);
+                } else {
+                    self.add_token(Type::Plus, None)
+                };
+            },
+            '%' => {
+                if self.match_next('=') {
+                    self.add_token(Type::PercentEqual, None);
+                } else {
+                    self.add_token(Type::Percent, None)
+                };
+            },
+            '*' => {
+                if self.match_next('*') {
+                    self.add_token(Type::StarStar, None);
+                } else if self.match_next('=') {
+                    self.add_token(Type::StarEqual, None);
+                } else {
+                    self.add_token(Type::Star, None)
+                };
+            },
+            // Floor division, `//` already starts a comment.
+            '~' => {
+                if self.match_next('/') {
+                    self.add_token(Type::TildeSlash, None);
+                } else {
+                    self.add_token(Type::Tilde, None)
+                };
+            },
+            '?' => {
+                if self.match_next('?') {
+                    self.add_token(Type::QuestionQuestion, None);
+                } else if self.match_next('.') {
+                    self.add_token(Type::QuestionDot, None);
+                } else {
+                    self.add_token(Type::Question, None)
+                };
+            },
             '!' => {
                 if self.match_next('=') {
                     self.add_token(Type::BangEqual, None);
@@ -213,6 +734,8 @@ impl Scanner {
             '<' => {
                 if self.match_next('=') {
                     self.add_token(Type::LessEqual, None);
+                } else if self.match_next('<') {
+                    self.add_token(Type::LessLess, None);
                 } else {
                     self.add_token(Type::Less, None)
                 };
@@ -220,51 +743,98 @@ impl Scanner {
             '>' => {
                 if self.match_next('=') {
                     self.add_token(Type::GreaterEqual, None);
+                } else if self.match_next('>') {
+                    self.add_token(Type::GreaterGreater, None);
                 } else {
                     self.add_token(Type::Greater, None)
                 };
             },
             '/' => {
                 if self.match_next('/') {
-                    while self.peek() != '\n' && !self.is_at_end() {
+                    // Exactly three slashes start a doc comment, more are a regular comment.
+                    let doc = self.next_is("/") && !self.next_is("//");
+
+                    while !self.is_at_end() && self.peek() != '\n' {
                         self.advance();
                     }
+
+                    if doc {
+                        let text = self.source[self.start + 3..self.current].trim_end();
+                        let text = text.strip_prefix(' ').unwrap_or(text);
+                        self.add_token(Type::DocComment, Some(Literal::from(text)));
+                    } else if self.comments {
+                        self.add_token(Type::Comment, None);
+                    }
+                } else if self.match_next('*') {
+                    self.block_comment();
+                } else if self.match_next('=') {
+                    self.add_token(Type::SlashEqual, None);
                 } else {
                     self.add_token(Type::Slash, None);
                 }
             },
 
-            // Ignore whitespace
-            ' ' | '\r' | '\t' => {},
-
-            // Update line counter
-            '\n' => self.line += 1,
+            // Ignore whitespace, lines are counted when advancing
+            ' ' | '\r' | '\t' | '\n' => {},
 
             // String
-            '"' => self.string(),
+            '"' => {
+                if self.next_is("\"\"") {
+                    self.multiline_string(false);
+                } else {
+                    self.string();
+                }
+            },
+
+            // Raw strings
+            'r' if self.next_is("\"\"\"") => {
+                self.advance();
+                self.multiline_string(true);
+            },
+            'r' if self.next_is("\"") => self.raw_string(),
 
             _ => {
                 // Numbers
                 if c.is_ascii_digit() {
                     self.number();
                 // Identifiers
-                } else if c.is_alphabetic() || c == '_' {
+                } else if c.is_xid_start() || c == '_' {
                     self.identifier();
                 // Unknown
                 } else {
-                    ScanError {
+                    self.error(ScanError {
                         line: self.line,
-                        location: self.current,
+                        location: self.start,
                         message: format!("Unexpected character '{c}'"),
-                    }.throw();
+                    });
                 }
             },
         }
     }
 }
 
+/// Scans the next token, or returns an error found since the previous one.
+///
+/// Errors are also reported and kept in `errors` as they are found.
+impl Iterator for Scanner {
+    type Item = Result<Token, ScanError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.scan_next();
+
+        if self.returned < self.errors.len() {
+            self.returned += 1;
+            return Some(Err(self.errors[self.returned - 1].clone()));
+        }
+
+        self.tokens.pop_front().map(Ok)
+    }
+}
+
 #[cfg(test)]
 mod test {
+    use std::io;
+
     use super::*;
 
     #[test]
@@ -431,29 +1001,278 @@ mod test {
     #[test]
     fn scan_tokens() {
         let mut scanner = Scanner::new(String::from("var a = 123;\n"));
-        scanner.scan_tokens();
-
-        assert_eq!(scanner.tokens.len(), 6);
-        assert_eq!(scanner.tokens[0].r#type, Type::Var);
-        assert_eq!(scanner.tokens[1].r#type, Type::Identifier);
-        assert_eq!(scanner.tokens[2].r#type, Type::Equal);
-        assert_eq!(scanner.tokens[3].r#type, Type::Number);
-        assert_eq!(scanner.tokens[4].r#type, Type::Semicolon);
-        assert_eq!(scanner.tokens[5].r#type, Type::EOF);
+        let tokens = scanner.scan_tokens();
+
+        assert_eq!(tokens.len(), 6);
+        assert_eq!(tokens[0].r#type, Type::Var);
+        assert_eq!(tokens[1].r#type, Type::Identifier);
+        assert_eq!(tokens[2].r#type, Type::Equal);
+        assert_eq!(tokens[3].r#type, Type::Number);
+        assert_eq!(tokens[4].r#type, Type::Semicolon);
+        assert_eq!(tokens[5].r#type, Type::EOF);
     }
 
     #[test]
     fn scan_tokens_with_comments() {
         let mut scanner = Scanner::new(String::from("var a = 123; // This is a comment\n"));
+        let tokens = scanner.scan_tokens();
+
+        assert_eq!(tokens.len(), 6);
+        assert_eq!(tokens[0].r#type, Type::Var);
+        assert_eq!(tokens[1].r#type, Type::Identifier);
+        assert_eq!(tokens[2].r#type, Type::Equal);
+        assert_eq!(tokens[3].r#type, Type::Number);
+        assert_eq!(tokens[4].r#type, Type::Semicolon);
+        assert_eq!(tokens[5].r#type, Type::EOF);
+    }
+
+    #[test]
+    fn scan_tokens_keeping_comments() {
+        let mut scanner = Scanner::with_comments(String::from("var a = 123; // This is a comment\n// Another"));
+        let tokens = scanner.scan_tokens();
+
+        assert_eq!(tokens.len(), 8);
+        assert_eq!(tokens[5].r#type, Type::Comment);
+        assert_eq!(tokens[5].lexeme, "// This is a comment");
+        assert_eq!(tokens[5].line, 1);
+        assert_eq!(tokens[6].r#type, Type::Comment);
+        assert_eq!(tokens[6].lexeme, "// Another");
+        assert_eq!(tokens[6].line, 2);
+    }
+
+    #[test]
+    fn scan_tokens_interpolation() {
+        let mut scanner = Scanner::new(String::from("\"a ${b} c ${ \"${d}\" + {} } e\""));
+        let tokens = scanner.scan_tokens();
+
+        let types: Vec<Type> = tokens.iter().map(|token| token.r#type).collect();
+        assert_eq!(types, vec![
+            Type::Interpolation, Type::Identifier, Type::Interpolation,
+            Type::Interpolation, Type::Identifier, Type::String, Type::Plus,
+            Type::LeftBrace, Type::RightBrace, Type::String, Type::EOF,
+        ]);
+
+        assert_eq!(tokens[0].literal, Some(Literal::from("a ")));
+        assert_eq!(tokens[2].literal, Some(Literal::from(" c ")));
+        assert_eq!(tokens[5].literal, Some(Literal::from("")));
+        assert_eq!(tokens[9].literal, Some(Literal::from(" e")));
+        assert!(scanner.errors.is_empty());
+    }
+
+    #[test]
+    fn scan_tokens_unterminated_interpolation() {
+        let mut scanner = Scanner::new(String::from("\"a ${b")).quiet();
         scanner.scan_tokens();
 
-        assert_eq!(scanner.tokens.len(), 6);
-        assert_eq!(scanner.tokens[0].r#type, Type::Var);
-        assert_eq!(scanner.tokens[1].r#type, Type::Identifier);
-        assert_eq!(scanner.tokens[2].r#type, Type::Equal);
-        assert_eq!(scanner.tokens[3].r#type, Type::Number);
-        assert_eq!(scanner.tokens[4].r#type, Type::Semicolon);
-        assert_eq!(scanner.tokens[5].r#type, Type::EOF);
+        assert_eq!(scanner.errors.len(), 1);
+        assert_eq!(scanner.errors[0].message, "Unterminated string interpolation");
     }
-}
 
+    fn literal(source: &str) -> (Option<Literal>, Vec<String>) {
+        let mut scanner = Scanner::new(String::from(source)).quiet();
+        let tokens = scanner.scan_tokens();
+        let errors = scanner.errors.iter().map(|error| error.to_string()).collect();
+
+        (tokens[0].literal.clone(), errors)
+    }
+
+    #[test]
+    fn scan_tokens_escapes() {
+        let (value, errors) = literal(r#""a\tb\n\"c\" \\ \0 \${} \u{41}\u{1F600}""#);
+
+        assert_eq!(value, Some(Literal::from("a\tb\n\"c\" \\ \0 ${} A\u{1F600}")));
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn scan_tokens_invalid_escapes() {
+        let (_, errors) = literal("\"a\\q \\u41 \\u{D800} \\u{1234567}\"");
+
+        assert_eq!(errors, vec![
+            "[line 1:3] Error: Invalid escape sequence '\\q'",
+            "[line 1:6] Error: Unicode escape must be written '\\u{XXXX}' with 1 to 6 hexadecimal digits",
+            "[line 1:11] Error: Invalid unicode escape '\\u{D800}'",
+            "[line 1:20] Error: Unicode escape must be written '\\u{XXXX}' with 1 to 6 hexadecimal digits",
+        ]);
+    }
+
+    #[test]
+    fn scan_tokens_raw_strings() {
+        assert_eq!(literal(r#"r"C:\dir\${x}""#).0, Some(Literal::from(r"C:\dir\${x}")));
+        assert_eq!(literal("r\"\"\"\n  a\\n\n    b\n  \"\"\"").0, Some(Literal::from("a\\n\n  b")));
+        assert_eq!(literal("r\"a").1, vec!["[line 1:1] Error: Unterminated string"]);
+    }
+
+    #[test]
+    fn scan_tokens_multiline_strings() {
+        let source = "var s = \"\"\"\n    first\\t\"quoted\"\n\n      second ${x}\n    \"\"\"; print s;";
+        let mut scanner = Scanner::new(String::from(source));
+        let tokens = scanner.scan_tokens();
+
+        assert_eq!(tokens[3].literal, Some(Literal::from("first\t\"quoted\"\n\n  second ${x}")));
+        assert_eq!(tokens[4].line, 5);
+
+        // The closing quotes set the indentation when they are less indented than the text.
+        assert_eq!(literal("\"\"\"\n    a\n  b\"\"\"").0, Some(Literal::from("  a\nb")));
+        assert_eq!(literal("\"\"\"\n    a\n  \"\"\"").0, Some(Literal::from("  a")));
+        assert_eq!(literal("\"\"\"a \\q\"\"\"").1, vec!["[line 1:6] Error: Invalid escape sequence '\\q'"]);
+    }
+
+    #[test]
+    fn scan_tokens_unicode() {
+        let source = "// héllo wörld\nvar naïve_π = \"😀\"; 变量 = 1;";
+        let tokens = Scanner::new(String::from(source)).scan_tokens();
+
+        let lexemes: Vec<(&str, usize, u32)> = tokens
+            .iter()
+            .map(|token| (token.lexeme.as_str(), token.line, token.column))
+            .collect();
+
+        assert_eq!(lexemes, vec![
+            ("var", 2, 0), ("naïve_π", 2, 4), ("=", 2, 12), ("\"😀\"", 2, 14), (";", 2, 17),
+            ("变量", 2, 19), ("=", 2, 22), ("1", 2, 24), (";", 2, 25), ("", 2, 26),
+        ]);
+    }
+
+    #[test]
+    fn scan_tokens_never_panics() {
+        let source = "var é = 1.5 + 123; \"a${b}é\" r\"é\" \"\"\"\n  é\n\"\"\" € 1. 123";
+
+        // Every prefix ends the source somewhere else, in the middle of any kind of token.
+        for (end, _) in source.char_indices().chain([(source.len(), ' ')]) {
+            Scanner::new(String::from(&source[..end])).quiet().scan_tokens();
+        }
+
+        let mut scanner = Scanner::new(String::from("a € b")).quiet();
+        let tokens = scanner.scan_tokens();
+
+        assert_eq!(tokens.len(), 3);
+        assert_eq!(scanner.errors[0].message, "Unexpected character '€'");
+        assert_eq!(scanner.errors[0].location, 2);
+    }
+
+    #[test]
+    fn iterator() {
+        let mut scanner = Scanner::new(String::from("a @ \"b")).quiet();
+
+        assert_eq!(scanner.next().unwrap().unwrap().lexeme, "a");
+        assert_eq!(scanner.next().unwrap().unwrap_err().message, "Unexpected character '@'");
+        assert_eq!(scanner.next().unwrap().unwrap_err().message, "Unterminated string");
+        assert_eq!(scanner.next().unwrap().unwrap().r#type, Type::EOF);
+        assert!(scanner.next().is_none());
+        assert_eq!(scanner.errors.len(), 2);
+    }
+
+    #[test]
+    fn from_reader() {
+        // Long enough for the scanned part to be dropped while reading.
+        let source = "var a = \"é\";\n".repeat(10_000) + "  a @";
+        let mut scanner = Scanner::from_reader(io::Cursor::new(source.clone())).quiet();
+        let tokens = scanner.scan_tokens();
+
+        assert_eq!(tokens.len(), 50_002);
+        assert_eq!((tokens[50_000].line, tokens[50_000].column), (10_001, 2));
+        assert_eq!(scanner.errors[0].location, source.find('@').unwrap());
+        assert!(scanner.source.len() < source.len());
+
+        let mut scanner = Scanner::from_reader(io::Cursor::new(b"a \xFF".to_vec())).quiet();
+        scanner.scan_tokens();
+
+        assert!(scanner.errors[0].message.starts_with("Could not read the source"));
+    }
+
+    #[test]
+    fn scan_tokens_numbers() {
+        let source = "0xFF 0b1010 0o17 1_000_000 6.02e23 1.5E-3 2e+2 0x1_F 0";
+        let tokens = Scanner::new(String::from(source)).scan_tokens();
+
+        let numbers: Vec<Option<Literal>> = tokens.into_iter().map(|token| token.literal).collect();
+        assert_eq!(numbers, vec![
+            Some(Literal::from(255.0)), Some(Literal::from(10.0)), Some(Literal::from(15.0)),
+            Some(Literal::from(1_000_000.0)), Some(Literal::from(6.02e23)), Some(Literal::from(1.5e-3)),
+            Some(Literal::from(200.0)), Some(Literal::from(31.0)), Some(Literal::from(0.0)), None,
+        ]);
+
+        // Precision beyond `f32`.
+        assert_eq!(literal("16777217").0, Some(Literal::from(16_777_217.0)));
+    }
+
+    #[test]
+    fn scan_tokens_invalid_numbers() {
+        let errors = |source: &str| literal(source).1;
+
+        assert_eq!(errors("0x;"), vec!["[line 1:1] Error: Expected hexadecimal digits after '0x'"]);
+        assert_eq!(errors("1e;"), vec!["[line 1:1] Error: Expected digits in exponent"]);
+        assert_eq!(errors("1.;"), vec!["[line 1:1] Error: Expected digits after '.' in number"]);
+        assert_eq!(errors("0b102;"), vec!["[line 1:1] Error: Invalid digit '2' in binary number"]);
+        assert_eq!(errors("12ab;"), vec!["[line 1:1] Error: Unexpected character 'a' in number"]);
+        assert_eq!(errors("1__0;"), vec!["[line 1:1] Error: Digit separator '_' must be between digits"]);
+        assert_eq!(errors("1_;"), vec!["[line 1:1] Error: Digit separator '_' must be between digits"]);
+        assert_eq!(errors("0x_1;"), vec!["[line 1:1] Error: Digit separator '_' must be between digits"]);
+        assert_eq!(errors("1e999;"), vec!["[line 1:1] Error: Number literal is too large"]);
+        assert_eq!(errors("0xFFFFFFFFFFFFFFFFF;"), vec!["[line 1:1] Error: Number literal is too large"]);
+    }
+
+    #[test]
+    fn scan_tokens_block_comments() {
+        let source = "a /* one /* two */ still\n one */ b /**/ c\n/* open /* closed */";
+        let mut scanner = Scanner::with_comments(String::from(source)).quiet();
+        let tokens = scanner.scan_tokens();
+
+        let types: Vec<(Type, usize)> = tokens.iter().map(|token| (token.r#type, token.line)).collect();
+        assert_eq!(types, vec![
+            (Type::Identifier, 1), (Type::Comment, 1), (Type::Identifier, 2), (Type::Comment, 2),
+            (Type::Identifier, 2), (Type::EOF, 3),
+        ]);
+
+        assert_eq!(tokens[1].lexeme, "/* one /* two */ still\n one */");
+        assert_eq!(scanner.errors.len(), 1);
+        assert_eq!(scanner.errors[0].to_string(), "[line 3:43] Error: Unterminated block comment");
+    }
+
+    #[test]
+    fn scan_tokens_doc_comments() {
+        let source = "/// Adds.  \n///\n//// Not a doc comment.\nfun";
+        let tokens = Scanner::new(String::from(source)).scan_tokens();
+
+        let types: Vec<Type> = tokens.iter().map(|token| token.r#type).collect();
+        assert_eq!(types, vec![Type::DocComment, Type::DocComment, Type::Fun, Type::EOF]);
+        assert_eq!(tokens[0].literal, Some(Literal::from("Adds.")));
+        assert_eq!(tokens[1].literal, Some(Literal::from("")));
+    }
+
+    #[test]
+    fn scan_tokens_operators() {
+        let tokens = Scanner::new(String::from("% ** * ~/ ~ & | ^ << <= < >> >= >")).scan_tokens();
+
+        let types: Vec<Type> = tokens.iter().map(|token| token.r#type).collect();
+        assert_eq!(types, vec![
+            Type::Percent, Type::StarStar, Type::Star, Type::TildeSlash, Type::Tilde, Type::Ampersand,
+            Type::Pipe, Type::Caret, Type::LessLess, Type::LessEqual, Type::Less, Type::GreaterGreater,
+            Type::GreaterEqual, Type::Greater, Type::EOF,
+        ]);
+    }
+
+    #[test]
+    fn scan_tokens_null_operators() {
+        let tokens = Scanner::new(String::from("a ?? b ? f?.() : c")).scan_tokens();
+
+        let types: Vec<Type> = tokens.iter().map(|token| token.r#type).collect();
+        assert_eq!(types, vec![
+            Type::Identifier, Type::QuestionQuestion, Type::Identifier, Type::Question, Type::Identifier,
+            Type::QuestionDot, Type::LeftParen, Type::RightParen, Type::Colon, Type::Identifier, Type::EOF,
+        ]);
+    }
+
+    #[test]
+    fn scan_tokens_update_operators() {
+        let tokens = Scanner::new(String::from("+= -= *= /= %= ++ -- + - a---b **=")).scan_tokens();
+
+        let types: Vec<Type> = tokens.iter().map(|token| token.r#type).collect();
+        assert_eq!(types, vec![
+            Type::PlusEqual, Type::MinusEqual, Type::StarEqual, Type::SlashEqual, Type::PercentEqual,
+            Type::PlusPlus, Type::MinusMinus, Type::Plus, Type::Minus, Type::Identifier, Type::MinusMinus,
+            Type::Minus, Type::Identifier, Type::StarStar, Type::Equal, Type::EOF,
+        ]);
+    }
+}
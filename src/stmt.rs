@@ -12,10 +12,15 @@ pub struct FunctionData {
     pub name: Token,
     pub params: Vec<Token>,
     pub body: Vec<Stmt>,
+    /// The closing brace of the body.
+    pub end: Token,
+    /// The doc comment written before the declaration.
+    pub doc: Option<String>,
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct IfData {
+    pub keyword: Token,
     pub condition: Expr,
     pub then_branch: Box<Stmt>,
     pub else_branch: Option<Box<Stmt>>,
@@ -24,6 +29,7 @@ pub struct IfData {
 /// Represents a print statement's data in the language
 #[derive(Debug, PartialEq, Clone)]
 pub struct PrintData {
+    pub keyword: Token,
     pub expr: Expr,
 }
 
@@ -37,17 +43,33 @@ pub struct ReturnData {
 pub struct VarData {
     pub name: Token,
     pub initializer: Option<Expr>,
+    /// The doc comment written before the declaration.
+    pub doc: Option<String>,
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct WhileData {
+    pub keyword: Token,
     pub condition: Expr,
     pub body: Box<Stmt>,
 }
 
+/// Represents an import of a module, bound to a name.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ImportData {
+    pub keyword: Token,
+    /// The string token holding the path of the module.
+    pub path: Token,
+    pub name: Token,
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct BlockData {
+    /// The opening brace of the block, or the keyword of a desugared `for` loop.
+    pub start: Token,
     pub statements: Vec<Stmt>,
+    /// The closing brace of the block.
+    pub end: Token,
 }
 
 /// Represents a statement in the language
@@ -61,6 +83,7 @@ pub enum Stmt {
     Var(VarData),
     While(WhileData),
     Block(BlockData),
+    Import(ImportData),
 }
 
 impl Stmt {
@@ -75,10 +98,36 @@ impl Stmt {
             Stmt::Var(_) => visitor.visit_var_stmt(self),
             Stmt::While(_) => visitor.visit_while_stmt(self),
             Stmt::Block(_) => visitor.visit_block_stmt(self),
+            Stmt::Import(_) => visitor.visit_import_stmt(self),
         }
     }
 }
 
+impl Stmt {
+    /// Returns the first token of the statement, if it is known.
+    pub fn token(&self) -> Option<&Token> {
+        match self {
+            Stmt::Expression(data) => data.expr.token(),
+            Stmt::Function(data) => Some(&data.name),
+            Stmt::If(data) => Some(&data.keyword),
+            Stmt::Print(data) => Some(&data.keyword),
+            Stmt::Return(data) => Some(&data.keyword),
+            Stmt::Var(data) => Some(&data.name),
+            Stmt::While(data) => Some(&data.keyword),
+            Stmt::Block(data) => match data.statements.first() {
+                Some(stmt) => stmt.token(),
+                None => Some(&data.end),
+            },
+            Stmt::Import(data) => Some(&data.keyword),
+        }
+    }
+
+    /// Returns the line the statement starts at, if it is known.
+    pub fn line(&self) -> Option<usize> {
+        self.token().map(|token| token.line)
+    }
+}
+
 pub trait StmtVisitor<T> {
     fn visit_expression_stmt(&mut self, stmt: &Stmt) -> T;
     fn visit_function_stmt(&mut self, stmt: &Stmt) -> T;
@@ -88,6 +137,7 @@ pub trait StmtVisitor<T> {
     fn visit_var_stmt(&mut self, stmt: &Stmt) -> T;
     fn visit_while_stmt(&mut self, stmt: &Stmt) -> T;
     fn visit_block_stmt(&mut self, stmt: &Stmt) -> T;
+    fn visit_import_stmt(&mut self, stmt: &Stmt) -> T;
 }
 
 #[cfg(test)]
@@ -99,7 +149,7 @@ mod test {
 
     #[test]
     fn test_expression_stmt() {
-        let expr = Expr::Literal(Literal::Number(1.0));
+        let expr = Expr::Literal(Literal::Number(1.0).into());
         let stmt = Stmt::Expression(ExpressionData { expr });
 
         let mut ast = ASTPrinter {};
@@ -109,14 +159,15 @@ mod test {
 
     #[test]
     fn test_if_stmt() {
-        let condition = Expr::Literal(Literal::Number(1.0));
+        let condition = Expr::Literal(Literal::Number(1.0).into());
         let then_branch = Stmt::Expression(ExpressionData {
-            expr: Expr::Literal(Literal::Number(2.0)),
+            expr: Expr::Literal(Literal::Number(2.0).into()),
         });
         let else_branch = Some(Box::new(Stmt::Expression(ExpressionData {
-            expr: Expr::Literal(Literal::Number(3.0)),
+            expr: Expr::Literal(Literal::Number(3.0).into()),
         })));
         let stmt = Stmt::If(IfData {
+            keyword: Token::new(Type::If, "if".to_string(), None, 1),
             condition,
             then_branch: Box::new(then_branch),
             else_branch,
@@ -129,12 +180,13 @@ mod test {
 
     #[test]
     fn test_if_stmt_without_else() {
-        let condition = Expr::Literal(Literal::Number(1.0));
+        let condition = Expr::Literal(Literal::Number(1.0).into());
         let then_branch = Stmt::Expression(ExpressionData {
-            expr: Expr::Literal(Literal::Number(2.0)),
+            expr: Expr::Literal(Literal::Number(2.0).into()),
         });
         let else_branch = None;
         let stmt = Stmt::If(IfData {
+            keyword: Token::new(Type::If, "if".to_string(), None, 1),
             condition,
             then_branch: Box::new(then_branch),
             else_branch,
@@ -147,8 +199,9 @@ mod test {
 
     #[test]
     fn test_print_stmt() {
-        let expr = Expr::Literal(Literal::Number(1.0));
-        let stmt = Stmt::Print(PrintData { expr });
+        let expr = Expr::Literal(Literal::Number(1.0).into());
+        let keyword = Token::new(Type::Print, "print".to_string(), None, 1);
+        let stmt = Stmt::Print(PrintData { keyword, expr });
 
         let mut ast = ASTPrinter;
 
@@ -158,8 +211,8 @@ mod test {
     #[test]
     fn test_var_stmt_with_initializer() {
         let name = Token::new(Type::Identifier, "a".to_string(), None, 1);
-        let initializer = Some(Expr::Literal(Literal::Number(1.0)));
-        let stmt = Stmt::Var(VarData { name, initializer });
+        let initializer = Some(Expr::Literal(Literal::Number(1.0).into()));
+        let stmt = Stmt::Var(VarData { name, initializer, doc: None });
 
         let mut ast = ASTPrinter;
 
@@ -170,7 +223,7 @@ mod test {
     fn test_var_stmt_without_initializer() {
         let name = Token::new(Type::Identifier, "a".to_string(), None, 1);
         let initializer = None;
-        let stmt = Stmt::Var(VarData { name, initializer });
+        let stmt = Stmt::Var(VarData { name, initializer, doc: None });
 
         let mut ast = ASTPrinter;
 
@@ -179,11 +232,12 @@ mod test {
 
     #[test]
     fn test_while_stmt() {
-        let condition = Expr::Literal(Literal::Bool(true));
+        let condition = Expr::Literal(Literal::Bool(true).into());
         let body = Stmt::Expression(ExpressionData {
-            expr: Expr::Literal(Literal::Number(2.0)),
+            expr: Expr::Literal(Literal::Number(2.0).into()),
         });
         let stmt = Stmt::While(WhileData {
+            keyword: Token::new(Type::While, "while".to_string(), None, 1),
             condition,
             body: Box::new(body),
         });
@@ -197,13 +251,16 @@ mod test {
     fn test_block_stmt() {
         let stmts = vec![
             Stmt::Expression(ExpressionData {
-                expr: Expr::Literal(Literal::Number(1.0)),
+                expr: Expr::Literal(Literal::Number(1.0).into()),
             }),
             Stmt::Print(PrintData {
-                expr: Expr::Literal(Literal::Number(2.0)),
+                keyword: Token::new(Type::Print, "print".to_string(), None, 1),
+                expr: Expr::Literal(Literal::Number(2.0).into()),
             }),
         ];
-        let stmt = Stmt::Block(BlockData { statements: stmts });
+        let end = Token::new(Type::RightBrace, "}".to_string(), None, 1);
+        let start = Token::new(Type::LeftBrace, "{".to_string(), None, 1);
+        let stmt = Stmt::Block(BlockData { start, statements: stmts, end });
 
         let mut ast = ASTPrinter;
 
@@ -215,12 +272,14 @@ mod test {
         let name = Token::new(Type::Identifier, "a".to_string(), None, 1);
         let params = vec![Token::new(Type::Identifier, "b".to_string(), None, 1)];
         let body = vec![Stmt::Expression(ExpressionData {
-            expr: Expr::Literal(Literal::Number(1.0)),
+            expr: Expr::Literal(Literal::Number(1.0).into()),
         })];
         let stmt = Stmt::Function(FunctionData {
             name,
             params,
             body,
+            end: Token::new(Type::RightBrace, "}".to_string(), None, 1),
+            doc: None,
         });
 
         let mut ast = ASTPrinter;
@@ -231,7 +290,7 @@ mod test {
     #[test]
     fn test_return_stmt() {
         let keyword = Token::new(Type::Return, "return".to_string(), None, 1);
-        let value = Expr::Literal(Literal::Number(1.0));
+        let value = Expr::Literal(Literal::Number(1.0).into());
         let stmt = Stmt::Return(ReturnData { keyword, value: Some(value) });
 
         let mut ast = ASTPrinter;
@@ -0,0 +1,173 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Expected results of a test script, read from its comments.
+///
+/// `// expect: <value>` expects a printed line, `// [line N] Error ...` or `// Error ...` a compile
+/// error, and `// expect runtime error: <message>` the runtime error ending the script.
+#[derive(Debug, Default, PartialEq)]
+pub struct Expectations {
+    /// Lines of standard output, with the line of the script expecting them.
+    pub output: Vec<(usize, String)>,
+    /// Message of the runtime error, with the line it is raised at.
+    pub runtime_error: Option<(usize, String)>,
+    pub code: i32,
+}
+
+impl Expectations {
+    pub fn parse(source: &str) -> Self {
+        let mut expectations = Expectations::default();
+
+        for (index, text) in source.lines().enumerate() {
+            let line = index + 1;
+            let Some(start) = text.find("// ") else { continue };
+            let comment = &text[start + 3..];
+
+            if let Some(value) = comment.strip_prefix("expect: ") {
+                expectations.output.push((line, value.to_string()));
+            } else if let Some(message) = comment.strip_prefix("expect runtime error: ") {
+                expectations.runtime_error = Some((line, message.to_string()));
+                expectations.code = 70;
+            } else if comment.starts_with("[line ") {
+                expectations.output.push((line, comment.to_string()));
+                expectations.code = 65;
+            } else if comment.starts_with("Error") {
+                expectations.output.push((line, format!("[line {line}] {comment}")));
+                expectations.code = 65;
+            }
+        }
+
+        expectations
+    }
+
+    /// Returns how the results of a run differ from the expected ones.
+    pub fn check(&self, stdout: &str, stderr: &str, code: i32) -> Vec<String> {
+        let mut failures = vec![];
+        let mut actual = stdout.lines().peekable();
+
+        for (line, expected) in &self.output {
+            match actual.next() {
+                Some(output) if output == expected => (),
+                Some(output) => failures.push(format!("Expected '{expected}' from line {line}, got '{output}'")),
+                None => failures.push(format!("Missing '{expected}' from line {line}")),
+            }
+        }
+
+        if let Some((line, message)) = &self.runtime_error {
+            let prefix = format!("[line {line}] Error");
+            let suffix = format!(": {message}");
+
+            match actual.next() {
+                Some(output) if output.starts_with(&prefix) && output.ends_with(&suffix) => {
+                    // The stack trace of the error is not checked.
+                    while actual.next_if(|output| output.starts_with("  ")).is_some() {}
+                },
+                Some(output) => failures.push(format!("Expected runtime error '{message}' at line {line}, got '{output}'")),
+                None => failures.push(format!("Missing runtime error '{message}' at line {line}")),
+            }
+        }
+
+        failures.extend(actual.map(|output| format!("Unexpected output '{output}'")));
+        failures.extend(stderr.lines().map(|output| format!("Unexpected error output '{output}'")));
+
+        if code != self.code {
+            failures.push(format!("Expected exit code {}, got {code}", self.code));
+        }
+
+        failures
+    }
+}
+
+/// Returns the paths, replacing directories by the Lox scripts they contain.
+pub fn collect(paths: &[PathBuf]) -> io::Result<Vec<PathBuf>> {
+    let mut scripts = vec![];
+
+    for path in paths {
+        if path.is_dir() {
+            search(path, &mut scripts)?;
+        } else {
+            scripts.push(path.clone());
+        }
+    }
+
+    Ok(scripts)
+}
+
+/// Adds the Lox scripts in the directory and its subdirectories, in sorted order.
+fn search(directory: &Path, scripts: &mut Vec<PathBuf>) -> io::Result<()> {
+    let mut entries = fs::read_dir(directory)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<Vec<PathBuf>>>()?;
+    entries.sort();
+
+    for entry in entries {
+        if entry.is_dir() {
+            search(&entry, scripts)?;
+        } else if entry.extension().is_some_and(|extension| extension == "lox") {
+            scripts.push(entry);
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs the script with the interpreter executable and returns how it failed its expectations.
+pub fn run(interpreter: &Path, script: &Path) -> io::Result<Vec<String>> {
+    let expectations = Expectations::parse(&fs::read_to_string(script)?);
+    let output = Command::new(interpreter).arg("run").arg(script).output()?;
+
+    Ok(expectations.check(
+        &String::from_utf8_lossy(&output.stdout),
+        &String::from_utf8_lossy(&output.stderr),
+        output.status.code().unwrap_or(-1),
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_expectations() {
+        let expectations = Expectations::parse("\
+print 1; // expect: 1
+print \"a\" + 1; // expect runtime error: Operands must be two numbers or two strings
+var; // Error at ';': Expected variable name
+// [line 5] Error at end: Expected expression
+");
+
+        assert_eq!(expectations, Expectations {
+            output: vec![
+                (1, "1".to_string()),
+                (3, "[line 3] Error at ';': Expected variable name".to_string()),
+                (4, "[line 5] Error at end: Expected expression".to_string()),
+            ],
+            runtime_error: Some((2, "Operands must be two numbers or two strings".to_string())),
+            code: 65,
+        });
+    }
+
+    #[test]
+    fn passing() {
+        let expectations = Expectations::parse("print 1; // expect: 1\nprint 2; // expect: 2\nf(); // expect runtime error: Boom\n");
+        let stdout = "1\n2\n[line 3] Error at ')': Boom\n  [line 3] in f()\n  [line 3] in script\n";
+
+        assert_eq!(expectations.check(stdout, "", 70), Vec::<String>::new());
+    }
+
+    #[test]
+    fn failing() {
+        let expectations = Expectations::parse("print 1; // expect: 1\nprint 2; // expect: 2\n");
+
+        assert_eq!(expectations.check("1\n3\n4\n", "panicked\n", 101), vec![
+            "Expected '2' from line 2, got '3'",
+            "Unexpected output '4'",
+            "Unexpected error output 'panicked'",
+            "Expected exit code 0, got 101",
+        ]);
+
+        assert_eq!(expectations.check("1\n", "", 0), vec!["Missing '2' from line 2"]);
+    }
+}
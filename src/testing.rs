@@ -0,0 +1,35 @@
+//! Helpers shared by the tests of several modules.
+
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+/// Removes the directory of a test when dropped.
+pub struct TempDir(PathBuf);
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Writes the files into a directory unique to the test and returns it.
+pub fn files(name: &str, files: &[(&str, &str)]) -> TempDir {
+    let directory = std::env::temp_dir().join(format!("rlox-{}-{name}", std::process::id()));
+
+    for (path, source) in files {
+        let path = directory.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, source).unwrap();
+    }
+
+    TempDir(directory)
+}
@@ -8,20 +8,33 @@ pub enum Type {
   // Single-character tokens.
   LeftParen, RightParen, LeftBrace, RightBrace,
   Comma, Dot, Minus, Plus, Semicolon, Slash, Star,
+  Percent, Ampersand, Pipe, Caret, Question, Colon,
 
   // One or two character tokens.
   Bang, BangEqual,
   Equal, EqualEqual,
-  Greater, GreaterEqual,
-  Less, LessEqual,
+  Greater, GreaterEqual, GreaterGreater,
+  Less, LessEqual, LessLess,
+  StarStar, Tilde, TildeSlash,
+  QuestionQuestion, QuestionDot,
+  PlusEqual, MinusEqual, StarEqual, SlashEqual, PercentEqual,
+  PlusPlus, MinusMinus,
 
   // Literals.
   Identifier, String, Number,
+  // The part of a string before an embedded `${` expression.
+  Interpolation,
 
   // Keywords.
-  And, Class, Else, False, Fun, For, If, Null, Or,
+  And, As, Class, Else, False, Fun, For, If, Import, Null, Or,
   Print, Return, Super, This, True, Var, While,
 
+  // Trivia, only produced when the scanner keeps comments.
+  Comment,
+
+  // A `///` line, documenting the declaration that follows it.
+  DocComment,
+
   EOF
 }
 
@@ -32,6 +45,7 @@ pub struct Token {
     pub lexeme: String, // String representation of the token
     pub literal: Option<Literal>,
     pub line: usize,
+    pub column: u32, // Zero-based character offset of the token in its line
 }
 
 impl Token {
@@ -42,7 +56,7 @@ impl Token {
         literal: Option<Literal>,
         line: usize
     ) -> Token {
-        Token { r#type, lexeme, literal, line }
+        Token { r#type, lexeme, literal, line, column: 0 }
     }
 }
 
@@ -133,7 +147,7 @@ mod test {
     }
 
     #[test]
-    fn from_f32_literal() {
+    fn from_f64_literal() {
         let literal = Literal::from(12.0);
 
         assert_eq!(literal, Literal::Number(12.0));
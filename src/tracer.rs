@@ -0,0 +1,219 @@
+use std::collections::HashMap;
+use std::io::Write;
+
+use crate::error::{location, RuntimeError};
+use crate::interpreter::{Hook, Interpreter};
+use crate::object::Object;
+use crate::stmt::Stmt;
+use crate::token::Token;
+
+/// Logs every executed statement, call and variable change, indented by call depth.
+pub struct Tracer {
+    output: Box<dyn Write>,
+    source: Vec<String>,
+    /// Source lines of the modules imported by the script, by name.
+    modules: HashMap<String, Vec<String>>,
+    /// Functions to limit the trace to, everything is traced when empty.
+    functions: Vec<String>,
+}
+
+fn lines(source: &str) -> Vec<String> {
+    source.lines().map(String::from).collect()
+}
+
+impl Tracer {
+    pub fn new(output: Box<dyn Write>, source: &str) -> Self {
+        Tracer { output, source: lines(source), modules: HashMap::new(), functions: vec![] }
+    }
+
+    /// Limits the trace to the calls of the functions, including the calls they make.
+    pub fn only(mut self, functions: Vec<String>) -> Self {
+        self.functions = functions;
+        self
+    }
+
+    fn traced(&self, interpreter: &Interpreter) -> bool {
+        self.functions.is_empty() || interpreter.call_stack()
+            .iter()
+            .any(|frame| self.functions.contains(&frame.function))
+    }
+
+    fn log(&mut self, interpreter: &Interpreter, depth: usize, message: &str) {
+        if self.traced(interpreter) {
+            // Failing to trace should not stop the program.
+            let _ = writeln!(self.output, "{}{message}", "  ".repeat(depth));
+        }
+    }
+}
+
+impl Hook for Tracer {
+    fn statement(&mut self, interpreter: &mut Interpreter, stmt: &Stmt) -> Result<(), RuntimeError> {
+        if let (false, Some(line)) = (matches!(stmt, Stmt::Block(_)), stmt.line()) {
+            let module = interpreter.module();
+            let source = match module {
+                Some(module) => self.modules.get(module).unwrap_or(&self.source),
+                None => &self.source,
+            };
+            let text = source.get(line - 1).map_or("", |text| text.trim());
+            let message = format!("{} {text}", location(module, line));
+            self.log(interpreter, interpreter.call_stack().len(), &message);
+        }
+
+        Ok(())
+    }
+
+    fn import(&mut self, _interpreter: &Interpreter, module: &str, source: &str, _statements: &[Stmt]) {
+        self.modules.insert(module.to_string(), lines(source));
+    }
+
+    fn enter(&mut self, interpreter: &Interpreter, function: &str, arguments: &[Object]) {
+        let arguments: Vec<String> = arguments.iter().map(|argument| argument.to_string()).collect();
+        let message = format!("call {function}({})", arguments.join(", "));
+
+        // The frame of the call is already pushed, log it at the depth of the caller.
+        self.log(interpreter, interpreter.call_stack().len() - 1, &message);
+    }
+
+    fn leave(&mut self, interpreter: &Interpreter, function: &str, value: Option<&Object>) {
+        let message = match value {
+            Some(value) => format!("return {function} = {value}"),
+            None => format!("fail {function}"),
+        };
+
+        self.log(interpreter, interpreter.call_stack().len() - 1, &message);
+    }
+
+    fn define(&mut self, interpreter: &Interpreter, name: &Token, value: &Object) {
+        let message = format!("define {} = {value}", name.lexeme);
+        self.log(interpreter, interpreter.call_stack().len(), &message);
+    }
+
+    fn assign(&mut self, interpreter: &Interpreter, name: &Token, value: &Object) {
+        let message = format!("assign {} = {value}", name.lexeme);
+        self.log(interpreter, interpreter.call_stack().len(), &message);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+    use std::io;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+    use crate::testing::files;
+
+    /// Output shared with the test after the tracer is moved into the interpreter.
+    struct Shared(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn trace(source: &str, functions: &[&str]) -> String {
+        let tokens = Scanner::new(source.to_string()).scan_tokens();
+        let statements = Parser::new(tokens).parse();
+
+        let output = Rc::new(RefCell::new(vec![]));
+        let functions = functions.iter().map(|function| function.to_string()).collect();
+        let tracer = Tracer::new(Box::new(Shared(Rc::clone(&output))), source).only(functions);
+
+        let mut interpreter = Interpreter::new();
+        interpreter.set_output(Box::new(io::sink()));
+        interpreter.add_hook(Box::new(tracer));
+        let _ = interpreter.interpret(&statements);
+
+        let output = output.borrow();
+        String::from_utf8(output.clone()).unwrap()
+    }
+
+    const SOURCE: &str = "\
+fun add(a, b) {
+  var sum = a + b;
+  return sum;
+}
+fun twice(x) {
+  return add(x, x);
+}
+var n = twice(2);
+n = n + 1;
+";
+
+    #[test]
+    fn everything() {
+        assert_eq!(trace(SOURCE, &[]), "\
+[line 1] fun add(a, b) {
+define add = <fn add>
+[line 5] fun twice(x) {
+define twice = <fn twice>
+[line 8] var n = twice(2);
+call twice(2)
+  [line 6] return add(x, x);
+  call add(2, 2)
+    [line 2] var sum = a + b;
+    define sum = 4
+    [line 3] return sum;
+  return add = 4
+return twice = 4
+define n = 4
+[line 9] n = n + 1;
+assign n = 5
+");
+    }
+
+    #[test]
+    fn only_named_functions() {
+        // Indented by the depth of the call to `twice` that is not traced.
+        assert_eq!(trace(SOURCE, &["add"]), "  call add(2, 2)
+    [line 2] var sum = a + b;
+    define sum = 4
+    [line 3] return sum;
+  return add = 4
+");
+    }
+
+    #[test]
+    fn failed_call() {
+        let output = trace("fun f() { return g(); }\nf();\n", &["f"]);
+
+        assert_eq!(output, "call f()\n  [line 1] fun f() { return g(); }\nfail f\n");
+    }
+
+    #[test]
+    fn module_lines() {
+        let directory = files("tracer", &[
+            ("main.lox", "import \"lib.lox\" as lib;\nprint lib.f();\n"),
+            ("lib.lox", "fun f() {\n  return 1;\n}\n"),
+        ]);
+
+        let path = directory.join("main.lox");
+        let source = std::fs::read_to_string(&path).unwrap();
+        let statements = Parser::new(Scanner::new(source.clone()).scan_tokens()).parse();
+
+        let output = Rc::new(RefCell::new(vec![]));
+        let mut interpreter = Interpreter::new();
+        interpreter.set_script(&path);
+        interpreter.set_output(Box::new(io::sink()));
+        interpreter.add_hook(Box::new(Tracer::new(Box::new(Shared(Rc::clone(&output))), &source)));
+        interpreter.interpret(&statements).unwrap();
+
+        assert_eq!(String::from_utf8(output.take()).unwrap(), "\
+[line 1] import \"lib.lox\" as lib;
+  [lib.lox, line 1] fun f() {
+  define f = <fn f>
+define lib = <module lib.lox>
+[line 2] print lib.f();
+call f()
+  [lib.lox, line 2] return 1;
+return f = 1
+");
+    }
+}